@@ -1,23 +1,38 @@
-use clap::ArgMatches;
-use hyper::client::connect::Connect;
+use std::io::{self, BufRead, Write};
+
+use clap::{value_t, Arg, ArgMatches};
 use tokio::runtime::current_thread::Runtime;
 
+use soma::docker::ContainerEngine;
 use soma::prelude::*;
-use soma::{Environment, Printer};
+use soma::problem::Problem;
+use soma::{Environment, NameString, Printer};
 
 pub use self::{
-    add::AddCommand, build::BuildCommand, clean::CleanCommand, fetch::FetchCommand,
-    list::ListCommand, remove::RemoveCommand, run::RunCommand, stop::StopCommand,
-    update::UpdateCommand,
+    add::AddCommand, build::BuildCommand, bundle::BundleCommand, clean::CleanCommand,
+    config::ConfigCommand, doctor::DoctorCommand, exec::ExecCommand, export::ExportCommand,
+    fetch::FetchCommand, gc::GcCommand, import::ImportCommand, list::ListCommand,
+    logs::LogsCommand, remove::RemoveCommand, restart::RestartCommand, run::RunCommand,
+    status::StatusCommand, stop::StopCommand, update::UpdateCommand,
 };
 
 pub mod add;
 pub mod build;
+pub mod bundle;
 pub mod clean;
+pub mod config;
+pub mod doctor;
+pub mod exec;
+pub mod export;
 pub mod fetch;
+pub mod gc;
+pub mod import;
 pub mod list;
+pub mod logs;
 pub mod remove;
+pub mod restart;
 pub mod run;
+pub mod status;
 pub mod stop;
 pub mod update;
 
@@ -29,7 +44,7 @@ pub trait SomaCommand {
     fn app(&self) -> App;
     fn handle_match(
         &self,
-        env: Environment<impl Connect, impl Printer>,
+        env: Environment<impl ContainerEngine, impl Printer>,
         matches: &ArgMatches,
     ) -> SomaResult<()>;
 }
@@ -37,3 +52,73 @@ pub trait SomaCommand {
 fn default_runtime() -> Runtime {
     Runtime::new().expect("Failed to initialize tokio runtime")
 }
+
+// Query argument shared by commands that operate on multiple problems
+fn problem_query_arg() -> Arg<'static, 'static> {
+    Arg::with_name("problem")
+        .required_unless("all")
+        .conflicts_with("all")
+        .help("problem name, repository name, or glob pattern such as 'pwn-*'")
+}
+
+fn instance_arg(help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name("instance")
+        .long("instance")
+        .takes_value(true)
+        .value_name("NAME")
+        .help(help)
+}
+
+// Instance names follow the same rules as repository and problem names
+fn selected_instance(matches: &ArgMatches) -> SomaResult<Option<NameString>> {
+    if matches.is_present("instance") {
+        Ok(Some(value_t!(matches, "instance", NameString)?))
+    } else {
+        Ok(None)
+    }
+}
+
+// Asks on stderr, so that stdout only carries the output of the command
+// Anything other than an explicit yes, including a closed stdin, declines
+fn confirm(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    question: &str,
+) -> SomaResult<bool> {
+    if !env.printer().is_interactive() || !atty::is(atty::Stream::Stdin) {
+        Err(SomaError::ConfirmationUnavailable)?;
+    }
+
+    eprint!("{} [y/N] ", question);
+    if io::stderr().flush().is_err() {
+        return Ok(false);
+    }
+
+    let mut answer = String::new();
+    match io::stdin().lock().read_line(&mut answer) {
+        Ok(_) => Ok(["y", "yes"].contains(&answer.trim().to_lowercase().as_str())),
+        Err(_) => Ok(false),
+    }
+}
+
+fn all_problems_arg() -> Arg<'static, 'static> {
+    Arg::with_name("all")
+        .long("all")
+        .help("selects every problem in all repositories")
+}
+
+// Every problem is selected when the query is omitted
+fn selected_problems(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    query: Option<&str>,
+) -> SomaResult<Vec<Problem>> {
+    match query {
+        Some(query) => env.repo_manager().select_probs(query),
+        None => {
+            let problems: Vec<_> = env.repo_manager().list_prob().collect();
+            if problems.is_empty() {
+                Err(SomaError::ProblemNotFound)?
+            }
+            Ok(problems)
+        }
+    }
+}
@@ -1,8 +1,8 @@
 use std::str::FromStr;
 
 use clap::{Arg, ArgMatches, SubCommand};
-use hyper::client::connect::Connect;
 
+use soma::docker::ContainerEngine;
 use soma::ops::add;
 use soma::prelude::*;
 use soma::{Environment, NameString, Printer};
@@ -39,7 +39,7 @@ impl SomaCommand for AddCommand {
 
     fn handle_match(
         &self,
-        mut env: Environment<impl Connect, impl Printer>,
+        mut env: Environment<impl ContainerEngine, impl Printer>,
         matches: &ArgMatches,
     ) -> SomaResult<()> {
         add(
@@ -1,11 +1,15 @@
-use clap::{Arg, ArgMatches, SubCommand};
-use hyper::client::connect::Connect;
+use clap::{value_t, Arg, ArgMatches, SubCommand};
 
+use soma::docker::ContainerEngine;
 use soma::prelude::*;
 use soma::{Environment, Printer};
 
-use crate::commands::{default_runtime, App, SomaCommand};
-use soma::ops::build;
+use crate::commands::{
+    all_problems_arg, default_runtime, problem_query_arg, selected_problems, App, SomaCommand,
+};
+use soma::ops::{build_batch, build_outdated};
+
+const DEFAULT_BUILD_JOBS: usize = 4;
 
 pub struct BuildCommand;
 
@@ -20,23 +24,53 @@ impl SomaCommand for BuildCommand {
 
     fn app(&self) -> App {
         SubCommand::with_name(Self::NAME)
-            .about("Builds a problem image")
+            .about("Builds problem images")
+            .arg(
+                problem_query_arg()
+                    .required_unless_one(&["all", "outdated"])
+                    .conflicts_with("outdated"),
+            )
+            .arg(all_problems_arg().conflicts_with("outdated"))
+            .arg(
+                Arg::with_name("jobs")
+                    .short("j")
+                    .long("jobs")
+                    .takes_value(true)
+                    .value_name("JOBS")
+                    .help("the number of images to build concurrently, 4 unless configured"),
+            )
+            .arg(
+                Arg::with_name("outdated")
+                    .long("outdated")
+                    .help("rebuilds every image that is out of date with its repository"),
+            )
             .arg(
-                Arg::with_name("problem")
-                    .required(true)
-                    .help("problem name with optional repository name prefix"),
+                Arg::with_name("no-pull")
+                    .long("no-pull")
+                    .help("uses locally available base images without pulling them"),
             )
     }
 
     fn handle_match(
         &self,
-        env: Environment<impl Connect, impl Printer>,
+        env: Environment<impl ContainerEngine, impl Printer>,
         matches: &ArgMatches,
     ) -> SomaResult<()> {
-        build(
-            &env,
-            matches.value_of("problem").unwrap(),
-            &mut default_runtime(),
-        )
+        let pull = !matches.is_present("no-pull");
+        let jobs = if matches.is_present("jobs") {
+            value_t!(matches, "jobs", usize)?
+        } else {
+            env.config_manager()
+                .config()
+                .build_jobs()
+                .unwrap_or(DEFAULT_BUILD_JOBS)
+        };
+        let mut runtime = default_runtime();
+        if matches.is_present("outdated") {
+            build_outdated(&env, pull, jobs, &mut runtime)
+        } else {
+            let problems = selected_problems(&env, matches.value_of("problem"))?;
+            build_batch(&env, &problems, pull, jobs, &mut runtime)
+        }
     }
 }
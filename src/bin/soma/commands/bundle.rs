@@ -0,0 +1,93 @@
+use std::str::FromStr;
+
+use clap::{AppSettings, Arg, ArgMatches, SubCommand};
+
+use soma::docker::ContainerEngine;
+use soma::ops::{bundle_create, bundle_load};
+use soma::prelude::*;
+use soma::{Environment, NameString, Printer};
+
+use crate::commands::{default_runtime, App, SomaCommand};
+
+const CREATE_NAME: &str = "create";
+const LOAD_NAME: &str = "load";
+
+pub struct BundleCommand;
+
+impl BundleCommand {
+    pub fn new() -> BundleCommand {
+        BundleCommand {}
+    }
+}
+
+impl SomaCommand for BundleCommand {
+    const NAME: &'static str = "bundle";
+
+    fn app(&self) -> App {
+        SubCommand::with_name(Self::NAME)
+            .about("Moves repositories between data directories")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name(CREATE_NAME)
+                    .about("Packs repositories into a bundle file")
+                    .arg(
+                        Arg::with_name("repository")
+                            .required(true)
+                            .multiple(true)
+                            .help("names of the repositories to pack"),
+                    )
+                    .arg(
+                        Arg::with_name("output")
+                            .short("o")
+                            .long("output")
+                            .help("path of the bundle to create")
+                            .value_name("PATH")
+                            .takes_value(true)
+                            .default_value("soma-bundle.tar.gz"),
+                    )
+                    .arg(
+                        Arg::with_name("images")
+                            .long("images")
+                            .help("includes built problem images in the bundle"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(LOAD_NAME)
+                    .about("Restores repositories from a bundle file")
+                    .arg(
+                        Arg::with_name("bundle")
+                            .required(true)
+                            .help("path of a bundle created by bundle create command"),
+                    ),
+            )
+    }
+
+    fn handle_match(
+        &self,
+        mut env: Environment<impl ContainerEngine, impl Printer>,
+        matches: &ArgMatches,
+    ) -> SomaResult<()> {
+        match matches.subcommand() {
+            (CREATE_NAME, Some(matches)) => {
+                let repo_names = matches
+                    .values_of("repository")
+                    .unwrap()
+                    .map(NameString::from_str)
+                    .collect::<Result<Vec<_>, _>>()?;
+                bundle_create(
+                    &env,
+                    &repo_names,
+                    matches.value_of("output").unwrap(),
+                    matches.is_present("images"),
+                    &mut default_runtime(),
+                )
+            }
+            (LOAD_NAME, Some(matches)) => bundle_load(
+                &mut env,
+                matches.value_of("bundle").unwrap(),
+                &mut default_runtime(),
+            ),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -1,11 +1,13 @@
-use clap::{Arg, ArgMatches, SubCommand};
-use hyper::client::connect::Connect;
+use clap::{ArgMatches, SubCommand};
 
-use soma::ops::clean;
+use soma::docker::ContainerEngine;
+use soma::ops::clean_batch;
 use soma::prelude::*;
 use soma::{Environment, Printer};
 
-use crate::commands::{default_runtime, App, SomaCommand};
+use crate::commands::{
+    all_problems_arg, default_runtime, problem_query_arg, selected_problems, App, SomaCommand,
+};
 
 pub struct CleanCommand;
 
@@ -20,23 +22,17 @@ impl SomaCommand for CleanCommand {
 
     fn app(&self) -> App {
         SubCommand::with_name(Self::NAME)
-            .about("Cleans up a problem image")
-            .arg(
-                Arg::with_name("problem")
-                    .required(true)
-                    .help("problem name with optional repository name prefix"),
-            )
+            .about("Cleans up problem images")
+            .arg(problem_query_arg())
+            .arg(all_problems_arg())
     }
 
     fn handle_match(
         &self,
-        env: Environment<impl Connect, impl Printer>,
+        env: Environment<impl ContainerEngine, impl Printer>,
         matches: &ArgMatches,
     ) -> SomaResult<()> {
-        clean(
-            &env,
-            matches.value_of("problem").unwrap(),
-            &mut default_runtime(),
-        )
+        let problems = selected_problems(&env, matches.value_of("problem"))?;
+        clean_batch(&env, &problems, &mut default_runtime())
     }
 }
@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+
+use clap::{AppSettings, Arg, ArgMatches, SubCommand};
+
+use soma::config::{ConfigManager, CONFIG_KEYS};
+use soma::prelude::*;
+use soma::Printer;
+
+use crate::commands::App;
+
+const GET_NAME: &str = "get";
+const SET_NAME: &str = "set";
+const UNSET_NAME: &str = "unset";
+
+// Not a SomaCommand, since it has to work when the configured endpoint is unusable
+pub struct ConfigCommand;
+
+fn key_arg() -> Arg<'static, 'static> {
+    Arg::with_name("key")
+        .possible_values(CONFIG_KEYS)
+        .help("name of the configuration entry")
+}
+
+impl ConfigCommand {
+    pub const NAME: &'static str = "config";
+
+    pub fn new() -> ConfigCommand {
+        ConfigCommand {}
+    }
+
+    pub fn app(&self) -> App {
+        SubCommand::with_name(Self::NAME)
+            .about("Reads or changes the defaults in the configuration file")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name(GET_NAME)
+                    .about("Shows a configuration entry, or every entry without the key")
+                    .arg(key_arg()),
+            )
+            .subcommand(
+                SubCommand::with_name(SET_NAME)
+                    .about("Changes a configuration entry")
+                    .arg(key_arg().required(true))
+                    .arg(
+                        Arg::with_name("value")
+                            .required(true)
+                            .help("new value of the configuration entry"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name(UNSET_NAME)
+                    .about("Removes a configuration entry to use the built-in default")
+                    .arg(key_arg().required(true)),
+            )
+    }
+
+    pub fn handle(
+        &self,
+        config_manager: &mut ConfigManager,
+        printer: &mut impl Printer,
+        matches: &ArgMatches,
+    ) -> SomaResult<()> {
+        match matches.subcommand() {
+            (GET_NAME, Some(matches)) => {
+                let keys = match matches.value_of("key") {
+                    Some(key) => vec![key],
+                    None => CONFIG_KEYS.to_vec(),
+                };
+
+                let mut document = BTreeMap::new();
+                for key in keys {
+                    let value = config_manager.config().get(key)?;
+                    match (&value, matches.is_present("key")) {
+                        (Some(value), true) => printer.write_line(value),
+                        (Some(value), false) => printer.write_line(&format!("{} = {}", key, value)),
+                        (None, true) => printer.write_line(&format!("'{}' is not set", key)),
+                        (None, false) => (),
+                    }
+                    document.insert(key, value);
+                }
+                printer.write_document(&document);
+                Ok(())
+            }
+            (SET_NAME, Some(matches)) => {
+                let key = matches.value_of("key").unwrap();
+                config_manager.set(key, matches.value_of("value").unwrap())?;
+                printer.write_line(&format!("Configuration updated: '{}'", key));
+                Ok(())
+            }
+            (UNSET_NAME, Some(matches)) => {
+                let key = matches.value_of("key").unwrap();
+                config_manager.set(key, "")?;
+                printer.write_line(&format!("Configuration removed: '{}'", key));
+                Ok(())
+            }
+            _ => unreachable!(),
+        }
+    }
+}
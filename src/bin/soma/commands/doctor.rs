@@ -0,0 +1,180 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use clap::{ArgMatches, SubCommand};
+use serde::Serialize;
+use whoami::username;
+
+use soma::config::OutputFormat;
+use soma::docker::{self, ContainerEngine, DockerEngine, DockerHost, EngineKind};
+use soma::doctor::{self, Check, CheckStatus};
+use soma::prelude::*;
+use soma::{NameString, Printer};
+
+use crate::commands::{default_runtime, App};
+use crate::json_printer::{JsonDocument, JsonPrinter};
+use crate::terminal_printer::TerminalPrinter;
+
+// Not a SomaCommand, since it diagnoses what the environment is built from
+pub struct DoctorCommand;
+
+#[derive(Serialize)]
+struct DoctorDocument<'a> {
+    checks: &'a [Check],
+}
+
+impl DoctorCommand {
+    pub const NAME: &'static str = "doctor";
+
+    pub fn new() -> DoctorCommand {
+        DoctorCommand {}
+    }
+
+    pub fn app(&self) -> App {
+        SubCommand::with_name(Self::NAME)
+            .about("Checks the container engine, data directory, and repositories for problems")
+    }
+
+    // The output format given on the command line takes priority over the configured one
+    pub fn handle(
+        &self,
+        matches: &ArgMatches,
+        output: &mut Option<OutputFormat>,
+        document: &Rc<RefCell<JsonDocument>>,
+    ) -> SomaResult<()> {
+        let (checks, configured_output) = diagnose(matches);
+        *output = output.or(configured_output);
+
+        match output.unwrap_or(OutputFormat::Text) {
+            OutputFormat::Json => JsonPrinter::new(document.clone())
+                .write_document(&DoctorDocument { checks: &checks }),
+            OutputFormat::Text => print_checks(&mut TerminalPrinter::new(), &checks),
+        }
+
+        if checks.iter().any(|check| check.status == CheckStatus::Fail) {
+            Err(SomaError::EnvironmentCheckFailed)?;
+        }
+        Ok(())
+    }
+}
+
+fn print_checks(printer: &mut impl Printer, checks: &[Check]) {
+    for check in checks {
+        printer.write_line(&format!(
+            "[{}] {}: {}",
+            check.status, check.name, check.message
+        ));
+        if let Some(remedy) = &check.remedy {
+            printer.write_line(&format!("       {}", remedy));
+        }
+    }
+}
+
+// Later checks are skipped when what they depend on is broken
+fn diagnose(matches: &ArgMatches) -> (Vec<Check>, Option<OutputFormat>) {
+    let mut checks = vec![];
+    let mut configured_output = None;
+    let mut configured_username = None;
+    let mut configured_host = None;
+
+    let (check, data_dir) = doctor::check_data_dir();
+    checks.push(check);
+    if let Some(data_dir) = &data_dir {
+        let (check, config_manager) = doctor::check_config(data_dir);
+        checks.push(check);
+        if let Some(config_manager) = config_manager {
+            let config = config_manager.config();
+            configured_output = config.output();
+            configured_username = config.username().cloned();
+            configured_host = config.docker_host().map(str::to_owned);
+        }
+        checks.push(doctor::check_repositories(data_dir));
+    }
+
+    let (check, username) = doctor::check_username(configured_username.as_ref(), &username());
+    checks.push(check);
+
+    let (check, docker_host) =
+        doctor::check_docker_host(matches.value_of("host"), configured_host.as_deref());
+    checks.push(check);
+    if let Some(docker_host) = docker_host {
+        let engine_kind = matches
+            .value_of("engine")
+            .and_then(|engine| EngineKind::from_str(engine).ok());
+        check_docker_host(&mut checks, docker_host, engine_kind, username.as_ref());
+    }
+
+    (checks, configured_output)
+}
+
+fn check_docker_host(
+    checks: &mut Vec<Check>,
+    docker_host: DockerHost,
+    engine_kind: Option<EngineKind>,
+    username: Option<&NameString>,
+) {
+    let connection = match docker_host {
+        #[cfg(unix)]
+        DockerHost::Unix(path) => {
+            let check = doctor::check_socket(&path);
+            let reachable = check.status != CheckStatus::Fail;
+            checks.push(check);
+            if !reachable {
+                return;
+            }
+            docker::connect_unix(&path).map(|connection| {
+                check_engine(
+                    checks,
+                    DockerEngine::with_kind(
+                        connection,
+                        engine_kind.or_else(|| EngineKind::from_socket_path(&path)),
+                    ),
+                    username,
+                )
+            })
+        }
+        #[cfg(windows)]
+        DockerHost::NamedPipe(path) => docker::connect_named_pipe(&path).map(|connection| {
+            check_engine(
+                checks,
+                DockerEngine::with_kind(connection, engine_kind),
+                username,
+            )
+        }),
+        DockerHost::Http(addr) => docker::connect_http(&addr).map(|connection| {
+            check_engine(
+                checks,
+                DockerEngine::with_kind(connection, engine_kind),
+                username,
+            )
+        }),
+        DockerHost::Https { addr, cert_path } => {
+            docker::connect_https(&addr, &cert_path).map(|connection| {
+                check_engine(
+                    checks,
+                    DockerEngine::with_kind(connection, engine_kind),
+                    username,
+                )
+            })
+        }
+    };
+
+    if let Err(error) = connection {
+        checks.push(doctor::connection_failed(error));
+    }
+}
+
+fn check_engine(
+    checks: &mut Vec<Check>,
+    engine: impl ContainerEngine,
+    username: Option<&NameString>,
+) {
+    let mut runtime = default_runtime();
+    let check = doctor::check_engine(&engine, &mut runtime);
+    let reachable = check.status != CheckStatus::Fail;
+    checks.push(check);
+    if let (true, Some(username)) = (reachable, username) {
+        checks.push(doctor::check_resources(&engine, username, &mut runtime));
+    }
+}
@@ -0,0 +1,75 @@
+use clap::{AppSettings, Arg, ArgMatches, SubCommand};
+use serde::Serialize;
+
+use soma::docker::ContainerEngine;
+use soma::ops::exec;
+use soma::prelude::*;
+use soma::{Environment, Printer};
+
+use crate::commands::{default_runtime, instance_arg, selected_instance, App, SomaCommand};
+
+pub struct ExecCommand;
+
+#[derive(Serialize)]
+struct ExecDocument {
+    exit_code: i64,
+    output: String,
+}
+
+impl ExecCommand {
+    pub fn new() -> ExecCommand {
+        ExecCommand {}
+    }
+}
+
+impl SomaCommand for ExecCommand {
+    const NAME: &'static str = "exec";
+
+    fn app(&self) -> App {
+        SubCommand::with_name(Self::NAME)
+            .about("Runs a command in a running problem container")
+            .setting(AppSettings::TrailingVarArg)
+            .arg(
+                Arg::with_name("problem")
+                    .required(true)
+                    .help("problem name with optional repository name prefix"),
+            )
+            .arg(instance_arg(
+                "name of the instance, required when the problem has multiple instances",
+            ))
+            .arg(
+                Arg::with_name("command")
+                    .required(true)
+                    .multiple(true)
+                    .help("command and its arguments to run in the container"),
+            )
+    }
+
+    fn handle_match(
+        &self,
+        env: Environment<impl ContainerEngine, impl Printer>,
+        matches: &ArgMatches,
+    ) -> SomaResult<()> {
+        let instance = selected_instance(matches)?;
+        let exec_output = exec(
+            &env,
+            matches.value_of("problem").unwrap(),
+            instance.as_ref().map(|instance| instance.as_str()),
+            matches.values_of("command").unwrap().collect(),
+            &mut default_runtime(),
+        )?;
+
+        for line in exec_output.output.lines() {
+            env.printer().write_line(line);
+        }
+        env.printer().write_document(&ExecDocument {
+            exit_code: exec_output.exit_code,
+            output: exec_output.output,
+        });
+
+        if exec_output.exit_code != 0 {
+            Err(SomaError::ExecFailed)?;
+        }
+        Ok(())
+    }
+}
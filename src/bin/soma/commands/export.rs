@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+
+use clap::{Arg, ArgMatches, SubCommand};
+
+use soma::docker::ContainerEngine;
+use soma::ops::export;
+use soma::prelude::*;
+use soma::{Environment, Printer};
+
+use crate::commands::{default_runtime, App, SomaCommand};
+
+pub struct ExportCommand;
+
+impl ExportCommand {
+    pub fn new() -> ExportCommand {
+        ExportCommand {}
+    }
+}
+
+impl SomaCommand for ExportCommand {
+    const NAME: &'static str = "export";
+
+    fn app(&self) -> App {
+        SubCommand::with_name(Self::NAME)
+            .about("Exports a built problem image and its public files into an archive")
+            .arg(
+                Arg::with_name("problem")
+                    .required(true)
+                    .help("problem name with optional repository name prefix"),
+            )
+            .arg(
+                Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .help("path of the archive to create")
+                    .value_name("PATH")
+                    .takes_value(true),
+            )
+    }
+
+    fn handle_match(
+        &self,
+        env: Environment<impl ContainerEngine, impl Printer>,
+        matches: &ArgMatches,
+    ) -> SomaResult<()> {
+        let prob_query = matches.value_of("problem").unwrap();
+        let output_path = match matches.value_of("output") {
+            Some(path) => PathBuf::from(path),
+            None => PathBuf::from(format!("{}.tar.gz", prob_query)),
+        };
+
+        export(&env, prob_query, output_path, &mut default_runtime())
+    }
+}
@@ -0,0 +1,157 @@
+use clap::{Arg, ArgMatches, SubCommand};
+use serde::Serialize;
+
+use soma::docker::ContainerEngine;
+use soma::ops::{collect_garbage, find_garbage, GarbageReason};
+use soma::prelude::*;
+use soma::{Environment, Printer};
+
+use crate::commands::{default_runtime, App, SomaCommand};
+
+const SIZE_UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+
+pub struct GcCommand;
+
+#[derive(Serialize)]
+struct GcDocument {
+    dry_run: bool,
+    containers: Vec<GarbageDocument>,
+    images: Vec<GarbageDocument>,
+    networks: Vec<NetworkGarbageDocument>,
+    reclaimed_size: u64,
+}
+
+#[derive(Serialize)]
+struct GarbageDocument {
+    problem: String,
+    id: String,
+    reason: GarbageReason,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct NetworkGarbageDocument {
+    name: String,
+    id: String,
+    reason: GarbageReason,
+}
+
+impl GcCommand {
+    pub fn new() -> GcCommand {
+        GcCommand {}
+    }
+}
+
+// Uses decimal units like docker does
+fn format_size(size: u64) -> String {
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < SIZE_UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, SIZE_UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, SIZE_UNITS[unit])
+    }
+}
+
+impl SomaCommand for GcCommand {
+    const NAME: &'static str = "gc";
+
+    fn app(&self) -> App {
+        SubCommand::with_name(Self::NAME)
+            .about("Removes stopped containers, unused images, and unused networks")
+            .arg(
+                Arg::with_name("dry-run")
+                    .long("dry-run")
+                    .help("Lists what would be removed without removing anything"),
+            )
+    }
+
+    fn handle_match(
+        &self,
+        env: Environment<impl ContainerEngine, impl Printer>,
+        matches: &ArgMatches,
+    ) -> SomaResult<()> {
+        let dry_run = matches.is_present("dry-run");
+        let mut runtime = default_runtime();
+        let garbage = find_garbage(&env, &mut runtime)?;
+
+        let container_documents: Vec<_> = garbage
+            .containers
+            .iter()
+            .map(|(container, reason)| GarbageDocument {
+                problem: format!("{}.{}", container.repo_name(), container.prob_name()),
+                id: container.container().id.clone(),
+                reason: *reason,
+                size: None,
+            })
+            .collect();
+        let image_documents: Vec<_> = garbage
+            .images
+            .iter()
+            .map(|(image, reason)| GarbageDocument {
+                problem: format!("{}.{}", image.repo_name(), image.prob_name()),
+                id: image.image().id.clone(),
+                reason: *reason,
+                size: Some(image.image().size),
+            })
+            .collect();
+        let network_documents: Vec<_> = garbage
+            .networks
+            .iter()
+            .map(|(network, reason)| NetworkGarbageDocument {
+                name: network.name.clone(),
+                id: network.id.clone(),
+                reason: *reason,
+            })
+            .collect();
+
+        if garbage.is_empty() {
+            env.printer().write_line("Nothing to remove.");
+        } else if dry_run {
+            for (document, (_, reason)) in container_documents.iter().zip(&garbage.containers) {
+                env.printer().write_line(&format!(
+                    "Would remove container: '{}' ({})",
+                    document.problem, reason
+                ));
+            }
+            for (document, (_, reason)) in image_documents.iter().zip(&garbage.images) {
+                env.printer().write_line(&format!(
+                    "Would remove image: '{}' ({}, {})",
+                    document.problem,
+                    reason,
+                    format_size(document.size.unwrap_or_default())
+                ));
+            }
+            for document in &network_documents {
+                env.printer().write_line(&format!(
+                    "Would remove network: '{}' ({})",
+                    document.name, document.reason
+                ));
+            }
+            env.printer().write_line(&format!(
+                "Would reclaim up to {} of image space",
+                format_size(garbage.image_size())
+            ));
+        } else {
+            collect_garbage(&env, &garbage, &mut runtime)?;
+            env.printer().write_line(&format!(
+                "Reclaimed up to {} of image space",
+                format_size(garbage.image_size())
+            ));
+        }
+
+        env.printer().write_document(&GcDocument {
+            dry_run,
+            containers: container_documents,
+            images: image_documents,
+            networks: network_documents,
+            reclaimed_size: garbage.image_size(),
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use std::str::FromStr;
+
+use clap::{Arg, ArgMatches, SubCommand};
+
+use soma::docker::ContainerEngine;
+use soma::ops::import;
+use soma::prelude::*;
+use soma::{Environment, NameString, Printer};
+
+use crate::commands::{default_runtime, App, SomaCommand};
+
+pub struct ImportCommand;
+
+impl ImportCommand {
+    pub fn new() -> ImportCommand {
+        ImportCommand {}
+    }
+}
+
+impl SomaCommand for ImportCommand {
+    const NAME: &'static str = "import";
+
+    fn app(&self) -> App {
+        SubCommand::with_name(Self::NAME)
+            .about("Loads a problem archive and registers it as a repository")
+            .arg(
+                Arg::with_name("archive")
+                    .required(true)
+                    .help("path of an archive created by export command"),
+            )
+            .arg(
+                Arg::with_name("name")
+                    .long("name")
+                    .help("customized name for the repository")
+                    .value_name("NAME")
+                    .takes_value(true),
+            )
+    }
+
+    fn handle_match(
+        &self,
+        mut env: Environment<impl ContainerEngine, impl Printer>,
+        matches: &ArgMatches,
+    ) -> SomaResult<()> {
+        import(
+            &mut env,
+            matches.value_of("archive").unwrap(),
+            matches
+                .value_of("name")
+                .map(NameString::from_str)
+                .transpose()?
+                .as_ref(),
+            &mut default_runtime(),
+        )
+    }
+}
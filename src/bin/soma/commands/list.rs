@@ -1,14 +1,38 @@
+use std::collections::HashSet;
+
 use clap::ArgMatches;
 use clap::SubCommand;
-use hyper::client::connect::Connect;
+use serde::Serialize;
 
+use soma::docker::{self, ContainerEngine};
+use soma::ops::outdated_problems;
 use soma::prelude::*;
-use soma::{Environment, Printer};
+use soma::repository::backend::Backend;
+use soma::{Environment, NameString, Printer};
 
-use crate::commands::{App, SomaCommand};
+use crate::commands::{default_runtime, App, SomaCommand};
 
 pub struct ListCommand;
 
+#[derive(Serialize)]
+struct ListDocument<'a> {
+    repositories: Vec<RepositoryDocument<'a>>,
+}
+
+#[derive(Serialize)]
+struct RepositoryDocument<'a> {
+    name: NameString,
+    backend: &'a dyn Backend,
+    problems: Vec<ProblemDocument>,
+}
+
+#[derive(Serialize)]
+struct ProblemDocument {
+    name: NameString,
+    // None when the container engine could not be reached
+    outdated: Option<bool>,
+}
+
 impl ListCommand {
     pub fn new() -> ListCommand {
         ListCommand {}
@@ -24,36 +48,77 @@ impl SomaCommand for ListCommand {
 
     fn handle_match(
         &self,
-        env: Environment<impl Connect, impl Printer>,
+        env: Environment<impl ContainerEngine, impl Printer>,
         _matches: &ArgMatches,
     ) -> SomaResult<()> {
-        let mut repo_iter = env.repo_manager().list_repo().peekable();
+        let repositories: Vec<_> = env.repo_manager().list_repo().collect();
+        let mut document = ListDocument {
+            repositories: Vec::new(),
+        };
 
-        if repo_iter.peek().is_none() {
+        if repositories.is_empty() {
             env.printer().write_line("No repository was added.");
         } else {
-            for repository in repo_iter {
+            // Repositories are still listed without image status when the engine is unreachable
+            let outdated_names: Option<HashSet<_>> =
+                match default_runtime().block_on(docker::list_images(&env)) {
+                    Ok(images) => Some(
+                        outdated_problems(&env, &images)?
+                            .iter()
+                            .map(|problem| problem.fully_qualified_name())
+                            .collect(),
+                    ),
+                    Err(_) => None,
+                };
+
+            for repository in &repositories {
                 env.printer().write_line(&format!(
                     "{} ({})",
                     repository.name(),
                     repository.backend()
                 ));
 
+                let mut problems = Vec::new();
                 let mut peekable = repository.prob_name_iter().peekable();
                 while let Some(name) = peekable.next() {
+                    let outdated = outdated_names.as_ref().map(|outdated_names| {
+                        outdated_names.contains(&format!("{}.{}", repository.name(), name))
+                    });
                     env.printer().write_line(&format!(
-                        "{}─ {}",
+                        "{}─ {}{}",
                         if peekable.peek().is_none() {
                             "└"
                         } else {
                             "├"
                         },
-                        name
-                    ))
+                        name,
+                        if outdated == Some(true) {
+                            " (image outdated)"
+                        } else {
+                            ""
+                        }
+                    ));
+                    problems.push(ProblemDocument {
+                        name: name.clone(),
+                        outdated,
+                    });
                 }
+
+                document.repositories.push(RepositoryDocument {
+                    name: repository.name().clone(),
+                    backend: repository.backend(),
+                    problems,
+                });
+            }
+
+            if outdated_names.is_none() {
+                env.printer().write_line(
+                    "Image status is unknown because the container engine is unreachable.",
+                );
             }
         }
 
+        env.printer().write_document(&document);
         Ok(())
     }
 }
@@ -0,0 +1,59 @@
+use clap::{Arg, ArgMatches, SubCommand};
+use serde::Serialize;
+
+use soma::docker::ContainerEngine;
+use soma::ops::logs;
+use soma::prelude::*;
+use soma::{Environment, Printer};
+
+use crate::commands::{default_runtime, instance_arg, selected_instance, App, SomaCommand};
+
+pub struct LogsCommand;
+
+#[derive(Serialize)]
+struct LogsDocument {
+    logs: String,
+}
+
+impl LogsCommand {
+    pub fn new() -> LogsCommand {
+        LogsCommand {}
+    }
+}
+
+impl SomaCommand for LogsCommand {
+    const NAME: &'static str = "logs";
+
+    fn app(&self) -> App {
+        SubCommand::with_name(Self::NAME)
+            .about("Shows the output of a problem container")
+            .arg(
+                Arg::with_name("problem")
+                    .required(true)
+                    .help("problem name with optional repository name prefix"),
+            )
+            .arg(instance_arg(
+                "name of the instance, required when the problem has multiple instances",
+            ))
+    }
+
+    fn handle_match(
+        &self,
+        env: Environment<impl ContainerEngine, impl Printer>,
+        matches: &ArgMatches,
+    ) -> SomaResult<()> {
+        let instance = selected_instance(matches)?;
+        let output = logs(
+            &env,
+            matches.value_of("problem").unwrap(),
+            instance.as_ref().map(|instance| instance.as_str()),
+            &mut default_runtime(),
+        )?;
+
+        for line in output.lines() {
+            env.printer().write_line(line);
+        }
+        env.printer().write_document(&LogsDocument { logs: output });
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use clap::{value_t, Arg, ArgMatches, SubCommand};
-use hyper::client::connect::Connect;
 
+use soma::docker::ContainerEngine;
 use soma::ops::remove;
 use soma::prelude::*;
 use soma::{Environment, NameString, Printer};
@@ -30,7 +30,7 @@ impl SomaCommand for RemoveCommand {
 
     fn handle_match(
         &self,
-        mut env: Environment<impl Connect, impl Printer>,
+        mut env: Environment<impl ContainerEngine, impl Printer>,
         matches: &ArgMatches,
     ) -> SomaResult<()> {
         remove(
@@ -0,0 +1,54 @@
+use clap::{Arg, ArgMatches, SubCommand};
+
+use soma::docker::ContainerEngine;
+use soma::ops::restart;
+use soma::prelude::*;
+use soma::{Environment, Printer};
+
+use crate::commands::{default_runtime, instance_arg, selected_instance, App, SomaCommand};
+
+pub struct RestartCommand;
+
+impl RestartCommand {
+    pub fn new() -> RestartCommand {
+        RestartCommand {}
+    }
+}
+
+impl SomaCommand for RestartCommand {
+    const NAME: &'static str = "restart";
+
+    fn app(&self) -> App {
+        SubCommand::with_name(Self::NAME)
+            .about("Restarts a problem container on the same port")
+            .arg(
+                Arg::with_name("problem")
+                    .required(true)
+                    .help("problem name with optional repository name prefix"),
+            )
+            .arg(instance_arg(
+                "name of the instance, required when the problem has multiple instances",
+            ))
+            .arg(
+                Arg::with_name("recreate")
+                    .long("recreate")
+                    .help("Replaces the container with a new one from the current problem image"),
+            )
+    }
+
+    fn handle_match(
+        &self,
+        env: Environment<impl ContainerEngine, impl Printer>,
+        matches: &ArgMatches,
+    ) -> SomaResult<()> {
+        let instance = selected_instance(matches)?;
+        restart(
+            &env,
+            matches.value_of("problem").unwrap(),
+            instance.as_ref().map(|instance| instance.as_str()),
+            matches.is_present("recreate"),
+            &mut default_runtime(),
+        )?;
+        Ok(())
+    }
+}
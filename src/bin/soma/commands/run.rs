@@ -1,11 +1,17 @@
+use std::net::IpAddr;
+
 use clap::{value_t, Arg, ArgMatches, SubCommand};
-use hyper::client::connect::Connect;
 
-use soma::ops::run;
+use soma::config::DEFAULT_BIND_ADDRESS;
+use soma::docker::{ContainerEngine, RestartPolicy};
+use soma::ops::{available_ports, run_batch, RunOptions};
 use soma::prelude::*;
 use soma::{Environment, Printer};
 
-use crate::commands::{default_runtime, App, SomaCommand};
+use crate::commands::{
+    all_problems_arg, default_runtime, instance_arg, selected_instance, selected_problems, App,
+    SomaCommand,
+};
 
 pub struct RunCommand;
 
@@ -20,32 +26,84 @@ impl SomaCommand for RunCommand {
 
     fn app(&self) -> App {
         SubCommand::with_name(Self::NAME)
-            .about("Runs problem daemon container")
+            .about("Runs problem daemon containers")
+            .arg(Arg::with_name("problem").required_unless("all").help(
+                "problem name, repository name, or glob pattern such as 'pwn-*'; the port number with --all",
+            ))
+            .arg(Arg::with_name("port").conflicts_with("all").help(
+                "port number to run the problem, which increases by one for each problem; \
+                 free ports in the configured port range by default",
+            ))
+            .arg(
+                Arg::with_name("bind")
+                    .long("bind")
+                    .takes_value(true)
+                    .value_name("ADDRESS")
+                    .help("host address to expose the problem on, such as 0.0.0.0 for every interface; 127.0.0.1 unless configured"),
+            )
             .arg(
-                Arg::with_name("problem")
-                    .required(true)
-                    .help("problem name with optional repository name prefix"),
+                Arg::with_name("internal")
+                    .long("internal")
+                    .help("runs the problem on an internal network even if the manifest does not ask for it, which cuts it off from the internet and the host; a proxy container publishes the port"),
             )
             .arg(
-                Arg::with_name("port")
-                    .required(true)
-                    .help("port number to run the problem"),
+                Arg::with_name("restart")
+                    .long("restart")
+                    .takes_value(true)
+                    .value_name("POLICY")
+                    .possible_values(&["no", "on-failure", "unless-stopped"])
+                    .help("restarts the problem containers when they exit or the host reboots; the manifest decides by default"),
             )
+            .arg(instance_arg(
+                "name of the instance, which runs alongside other instances of the problem",
+            ))
+            .arg(all_problems_arg())
     }
 
     fn handle_match(
         &self,
-        env: Environment<impl Connect, impl Printer>,
+        env: Environment<impl ContainerEngine, impl Printer>,
         matches: &ArgMatches,
     ) -> SomaResult<()> {
-        let port = value_t!(matches, "port", u32)?;
-
-        run(
-            &env,
-            matches.value_of("problem").unwrap(),
-            port,
-            &mut default_runtime(),
-        )?;
-        Ok(())
+        // With --all, the only positional argument is the port number
+        let (query, port_arg) = if matches.is_present("all") {
+            (None, "problem")
+        } else {
+            (matches.value_of("problem"), "port")
+        };
+
+        let bind_address = if matches.is_present("bind") {
+            value_t!(matches, "bind", IpAddr)?
+        } else {
+            env.config_manager()
+                .config()
+                .bind_address()
+                .unwrap_or(DEFAULT_BIND_ADDRESS)
+        };
+        let options = RunOptions {
+            bind_address,
+            internal: matches.is_present("internal"),
+            instance: selected_instance(matches)?,
+            restart_policy: if matches.is_present("restart") {
+                Some(value_t!(matches, "restart", RestartPolicy)?)
+            } else {
+                None
+            },
+        };
+
+        let problems = selected_problems(&env, query)?;
+        let mut runtime = default_runtime();
+        if matches.is_present(port_arg) {
+            let port = value_t!(matches, port_arg, u32)?;
+            run_batch(&env, &problems, port.., &options, &mut runtime)
+        } else {
+            let port_range = env
+                .config_manager()
+                .config()
+                .port_range()
+                .ok_or(SomaError::PortNotSpecified)?;
+            let ports = available_ports(&env, port_range, &mut runtime)?;
+            run_batch(&env, &problems, ports, &options, &mut runtime)
+        }
     }
 }
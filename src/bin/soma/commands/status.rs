@@ -0,0 +1,220 @@
+use std::collections::HashSet;
+
+use clap::ArgMatches;
+use clap::SubCommand;
+use serde::Serialize;
+
+use soma::docker::{self, ContainerEngine, VersionStatus, DEFAULT_INSTANCE};
+use soma::ops::outdated_problems;
+use soma::prelude::*;
+use soma::{Environment, Printer};
+
+use crate::commands::{default_runtime, App, SomaCommand};
+
+pub struct StatusCommand;
+
+#[derive(Serialize)]
+struct StatusDocument {
+    engine: EngineDocument,
+    images: Vec<ImageDocument>,
+    containers: Vec<ContainerDocument>,
+}
+
+#[derive(Serialize)]
+struct EngineDocument {
+    kind: String,
+    version: String,
+    api_version: String,
+}
+
+#[derive(Serialize)]
+struct ImageDocument {
+    problem: String,
+    version_status: &'static str,
+    outdated: bool,
+    base_digest: Option<String>,
+    libc_version: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ContainerDocument {
+    problem: String,
+    instance: String,
+    id: String,
+    state: String,
+    ports: Vec<PortDocument>,
+}
+
+#[derive(Serialize)]
+struct PortDocument {
+    ip: Option<String>,
+    public_port: i64,
+    private_port: i64,
+}
+
+impl StatusCommand {
+    pub fn new() -> StatusCommand {
+        StatusCommand {}
+    }
+}
+
+fn version_status_name(status: VersionStatus) -> &'static str {
+    match status {
+        VersionStatus::Normal => "normal",
+        VersionStatus::VersionMismatch => "version_mismatch",
+        VersionStatus::NoVersionFound => "no_version_found",
+    }
+}
+
+fn version_note(status: VersionStatus) -> &'static str {
+    match status {
+        VersionStatus::Normal => "",
+        VersionStatus::VersionMismatch => " (built by another soma version)",
+        VersionStatus::NoVersionFound => " (no soma version found)",
+    }
+}
+
+impl SomaCommand for StatusCommand {
+    const NAME: &'static str = "status";
+
+    fn app(&self) -> App {
+        SubCommand::with_name(Self::NAME)
+            .about("Shows the container engine, built images, and created containers")
+    }
+
+    fn handle_match(
+        &self,
+        env: Environment<impl ContainerEngine, impl Printer>,
+        _matches: &ArgMatches,
+    ) -> SomaResult<()> {
+        let mut runtime = default_runtime();
+        let engine_info = runtime.block_on(docker::engine_info(&env))?;
+        env.printer().write_line(&format!(
+            "Container engine: {} {} (API {})",
+            engine_info.kind(),
+            engine_info.version(),
+            engine_info.api_version()
+        ));
+
+        let images = runtime.block_on(docker::list_images(&env))?;
+        let containers = runtime.block_on(docker::list_containers(&env))?;
+        let proxies = runtime.block_on(docker::list_proxies(&env))?;
+        let outdated_names: HashSet<_> = outdated_problems(&env, &images)?
+            .iter()
+            .map(|problem| problem.fully_qualified_name())
+            .collect();
+
+        let image_documents: Vec<_> = images
+            .iter()
+            .map(|image| {
+                let prob_name = format!("{}.{}", image.repo_name(), image.prob_name());
+                ImageDocument {
+                    outdated: outdated_names.contains(&prob_name),
+                    problem: prob_name,
+                    version_status: version_status_name(image.status()),
+                    base_digest: image.base_digest().cloned(),
+                    libc_version: image.libc_version().cloned(),
+                }
+            })
+            .collect();
+
+        if image_documents.is_empty() {
+            env.printer().write_line("No image was built.");
+        } else {
+            env.printer().write_line("Images:");
+            for (image, document) in images.iter().zip(&image_documents) {
+                env.printer().write_line(&format!(
+                    "  {}{}{}",
+                    document.problem,
+                    version_note(image.status()),
+                    if document.outdated { " (outdated)" } else { "" }
+                ));
+                env.printer().write_line(&format!(
+                    "    base digest: {}",
+                    document
+                        .base_digest
+                        .as_ref()
+                        .map_or("unknown", String::as_str)
+                ));
+                env.printer().write_line(&format!(
+                    "    libc: {}",
+                    document
+                        .libc_version
+                        .as_ref()
+                        .map_or("unknown", String::as_str)
+                ));
+            }
+        }
+
+        let container_documents: Vec<_> = containers
+            .iter()
+            .map(|container| ContainerDocument {
+                problem: format!("{}.{}", container.repo_name(), container.prob_name()),
+                instance: container.instance().clone(),
+                id: container.container().id.clone(),
+                state: container.container().state.clone(),
+                // Problems on internal networks publish their ports through their proxies
+                ports: docker::find_same_instance(&proxies, container)
+                    .unwrap_or(container)
+                    .container()
+                    .ports
+                    .iter()
+                    .filter_map(|port| {
+                        port.public_port.map(|public_port| PortDocument {
+                            ip: port.ip.clone(),
+                            public_port,
+                            private_port: port.private_port,
+                        })
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        if container_documents.is_empty() {
+            env.printer().write_line("No container was created.");
+        } else {
+            env.printer().write_line("Containers:");
+            for document in &container_documents {
+                let ports = document
+                    .ports
+                    .iter()
+                    .map(|port| {
+                        format!(
+                            "{}:{}->{}",
+                            port.ip.as_ref().map_or("0.0.0.0", String::as_str),
+                            port.public_port,
+                            port.private_port
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let instance = if document.instance == DEFAULT_INSTANCE {
+                    String::new()
+                } else {
+                    format!(" [{}]", document.instance)
+                };
+                env.printer().write_line(&format!(
+                    "  {}{} {} ({}){}{}",
+                    document.problem,
+                    instance,
+                    document.id.get(..12).unwrap_or(&document.id),
+                    document.state,
+                    if ports.is_empty() { "" } else { " " },
+                    ports,
+                ));
+            }
+        }
+
+        env.printer().write_document(&StatusDocument {
+            engine: EngineDocument {
+                kind: engine_info.kind().to_string(),
+                version: engine_info.version().clone(),
+                api_version: engine_info.api_version().clone(),
+            },
+            images: image_documents,
+            containers: container_documents,
+        });
+
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
 use clap::{Arg, ArgMatches, SubCommand};
-use hyper::client::connect::Connect;
 
-use soma::ops::stop;
+use soma::docker::ContainerEngine;
+use soma::ops::{instances, stop_batch};
 use soma::prelude::*;
 use soma::{Environment, Printer};
 
-use crate::commands::{default_runtime, App, SomaCommand};
+use crate::commands::{
+    all_problems_arg, confirm, default_runtime, instance_arg, problem_query_arg, selected_instance,
+    selected_problems, App, SomaCommand,
+};
 
 pub struct StopCommand;
 
@@ -20,23 +23,48 @@ impl SomaCommand for StopCommand {
 
     fn app(&self) -> App {
         SubCommand::with_name(Self::NAME)
-            .about("Stops a running problem")
+            .about("Stops running problems")
+            .arg(problem_query_arg())
+            .arg(all_problems_arg())
+            .arg(instance_arg(
+                "name of the instance to stop; every instance of the problem by default",
+            ))
             .arg(
-                Arg::with_name("problem")
-                    .required(true)
-                    .help("problem name with optional repository name prefix"),
+                Arg::with_name("yes").long("yes").help(
+                    "Stops every instance of problems with multiple instances without asking",
+                ),
             )
     }
 
     fn handle_match(
         &self,
-        env: Environment<impl Connect, impl Printer>,
+        env: Environment<impl ContainerEngine, impl Printer>,
         matches: &ArgMatches,
     ) -> SomaResult<()> {
-        stop(
+        let problems = selected_problems(&env, matches.value_of("problem"))?;
+        let instance = selected_instance(matches)?;
+        let mut runtime = default_runtime();
+
+        if instance.is_none() && !matches.is_present("yes") {
+            let mut multiple_instances = false;
+            for problem in &problems {
+                multiple_instances |= instances(&env, problem, &mut runtime)?.len() > 1;
+            }
+            if multiple_instances
+                && !confirm(
+                    &env,
+                    "Some problems have multiple instances. Stop all of them?",
+                )?
+            {
+                Err(SomaError::InstanceNotSpecified)?;
+            }
+        }
+
+        stop_batch(
             &env,
-            matches.value_of("problem").unwrap(),
-            &mut default_runtime(),
+            &problems,
+            instance.as_ref().map(|instance| instance.as_str()),
+            &mut runtime,
         )
     }
 }
@@ -1,11 +1,11 @@
 use clap::{value_t, Arg, ArgMatches, SubCommand};
-use hyper::client::connect::Connect;
 
-use soma::ops::update;
+use soma::docker::ContainerEngine;
+use soma::ops::{apply_update, prepare_update};
 use soma::prelude::*;
 use soma::{Environment, NameString, Printer};
 
-use crate::commands::{default_runtime, App, SomaCommand};
+use crate::commands::{confirm, default_runtime, App, SomaCommand};
 
 pub struct UpdateCommand;
 
@@ -22,6 +22,9 @@ impl SomaCommand for UpdateCommand {
         // TODO: update all repository when repository is omitted
         SubCommand::with_name(Self::NAME)
             .about("Updates a repository")
+            .arg(Arg::with_name("prune").long("prune").help(
+                "Removes images and containers of removed or renamed problems without asking",
+            ))
             .arg(
                 Arg::with_name("repository")
                     .required(true)
@@ -31,13 +34,37 @@ impl SomaCommand for UpdateCommand {
 
     fn handle_match(
         &self,
-        env: Environment<impl Connect, impl Printer>,
+        mut env: Environment<impl ContainerEngine, impl Printer>,
         matches: &ArgMatches,
     ) -> SomaResult<()> {
-        update(
-            &env,
-            &value_t!(matches, "repository", NameString)?,
-            &mut default_runtime(),
-        )
+        let repo_name = value_t!(matches, "repository", NameString)?;
+        let prune = matches.is_present("prune");
+        let mut runtime = default_runtime();
+
+        let pending = prepare_update(&env, &repo_name, &mut runtime)?;
+        // Images are not carried over to the new names, so renamed problems are built again
+        let renamed: Vec<_> = pending
+            .diff()
+            .renamed()
+            .iter()
+            .filter(|(from, _)| {
+                pending
+                    .problems_in_use()
+                    .iter()
+                    .any(|problem| problem.prob_name() == from)
+            })
+            .map(|(from, to)| format!("{}.{} -> {}.{}", repo_name, from, repo_name, to))
+            .collect();
+        let question = if renamed.is_empty() {
+            "Removed or renamed problems still have images or containers. Remove them?".to_owned()
+        } else {
+            format!(
+                "Removed or renamed problems still have images or containers. \
+                 Renamed problems lose their images and have to be built again ({}). Remove them?",
+                renamed.join(", ")
+            )
+        };
+        let prune = prune || (!pending.problems_in_use().is_empty() && confirm(&env, &question)?);
+        apply_update(&mut env, pending, prune, &mut runtime)
     }
 }
@@ -0,0 +1,99 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use failure::Error;
+use serde::Serialize;
+use serde_json::Value;
+
+use soma::event::Event;
+use soma::prelude::*;
+use soma::Printer;
+
+// Everything a command reported, printed as a single JSON document when the command finishes
+#[derive(Serialize)]
+pub struct JsonDocument {
+    command: Option<String>,
+    result: Value,
+    events: Vec<Event>,
+    error: Option<JsonError>,
+}
+
+#[derive(Serialize)]
+struct JsonError {
+    code: &'static str,
+    message: String,
+}
+
+impl JsonDocument {
+    pub fn new(command: Option<&str>) -> JsonDocument {
+        JsonDocument {
+            command: command.map(str::to_owned),
+            result: Value::Null,
+            events: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn finish(&mut self, result: &SomaResult<()>) -> SomaResult<()> {
+        if let Err(error) = result {
+            self.error = Some(JsonError::new(error));
+        }
+        println!("{}", serde_json::to_string_pretty(self)?);
+        Ok(())
+    }
+}
+
+impl JsonError {
+    // Errors not raised by soma itself, such as I/O errors, share a single code
+    fn new(error: &Error) -> JsonError {
+        let code = match error.downcast_ref::<SomaError>() {
+            Some(soma_error) => soma_error.code(),
+            None => "Other",
+        };
+
+        JsonError {
+            code,
+            message: error.to_string(),
+        }
+    }
+}
+
+pub struct JsonPrinter {
+    document: Rc<RefCell<JsonDocument>>,
+}
+
+impl JsonPrinter {
+    pub fn new(document: Rc<RefCell<JsonDocument>>) -> JsonPrinter {
+        JsonPrinter { document }
+    }
+}
+
+impl Printer for JsonPrinter {
+    // Text lines are not part of the document
+    type Handle = ();
+
+    fn get_current_handle(&mut self) -> Self::Handle {}
+
+    fn write_line_at(&mut self, _handle: &Self::Handle, _message: &str) {}
+
+    fn write_line(&mut self, _message: &str) {}
+
+    fn report(&mut self, event: &Event) {
+        self.document.borrow_mut().events.push(event.clone());
+    }
+
+    fn report_at(&mut self, _handle: &Self::Handle, event: &Event) {
+        self.report(event)
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+
+    fn write_document(&mut self, document: &impl Serialize) {
+        match serde_json::to_value(document) {
+            Ok(value) => self.document.borrow_mut().result = value,
+            Err(e) => eprintln!("Error on JsonPrinter: {}", e),
+        }
+    }
+}
@@ -1,75 +1,238 @@
+use std::cell::RefCell;
 use std::convert::TryFrom;
+use std::rc::Rc;
+use std::str::FromStr;
 use std::string::ToString;
 
-use clap::{App, AppSettings};
-use hyper::client::connect::Connect;
+use clap::{App, AppSettings, Arg, ArgMatches};
 use whoami::username;
 
+use soma::config::{ConfigManager, OutputFormat};
 use soma::data_dir::DataDirectory;
-use soma::docker::connect_default;
+use soma::docker::{self, ContainerEngine, DockerEngine, DockerHost, EngineKind};
 use soma::prelude::*;
-use soma::{Environment, NameString, VERSION};
+use soma::{Environment, NameString, Printer, VERSION};
 
 use crate::commands::*;
+use crate::json_printer::{JsonDocument, JsonPrinter};
 use crate::terminal_printer::TerminalPrinter;
 
 mod commands;
+mod json_printer;
 mod terminal_printer;
 
-fn cli_env(data_dir: &mut DataDirectory) -> SomaResult<Environment<impl Connect, TerminalPrinter>> {
-    Environment::new(
-        NameString::try_from(username().to_lowercase())?,
-        data_dir,
-        connect_default()?,
-        TerminalPrinter::new(),
-    )
+fn cli_env<'a, E: ContainerEngine + 'static, P: Printer + 'static>(
+    data_dir: &'a DataDirectory,
+    config_manager: ConfigManager<'a>,
+    engine: E,
+    printer: P,
+) -> SomaResult<Environment<'a, E, P>> {
+    let username = match config_manager.config().username() {
+        Some(username) => username.clone(),
+        None => NameString::try_from(username().to_lowercase())?,
+    };
+    Environment::with_config(username, data_dir, config_manager, engine, printer)
 }
 
-fn main_result() -> SomaResult<()> {
-    let add_command = AddCommand::new();
-    let build_command = BuildCommand::new();
-    let clean_command = CleanCommand::new();
-    let fetch_command = FetchCommand::new();
-    let list_command = ListCommand::new();
-    let remove_command = RemoveCommand::new();
-    let run_command = RunCommand::new();
-    let stop_command = StopCommand::new();
-    let update_command = UpdateCommand::new();
-
-    let matches = App::new("soma")
+// JSON output collects the whole command into one document instead of printing progress
+fn handle_output(
+    engine: impl ContainerEngine + 'static,
+    data_dir: &DataDirectory,
+    config_manager: ConfigManager,
+    matches: &ArgMatches,
+    output: Option<OutputFormat>,
+    document: &Rc<RefCell<JsonDocument>>,
+) -> SomaResult<()> {
+    match output.unwrap_or(OutputFormat::Text) {
+        OutputFormat::Json => handle_matches(
+            cli_env(
+                data_dir,
+                config_manager,
+                engine,
+                JsonPrinter::new(document.clone()),
+            )?,
+            matches,
+        ),
+        OutputFormat::Text => handle_matches(
+            cli_env(data_dir, config_manager, engine, TerminalPrinter::new())?,
+            matches,
+        ),
+    }
+}
+
+fn handle_matches(
+    env: Environment<impl ContainerEngine, impl Printer>,
+    matches: &ArgMatches,
+) -> SomaResult<()> {
+    match matches.subcommand() {
+        (AddCommand::NAME, Some(matches)) => AddCommand::new().handle_match(env, matches),
+        (BuildCommand::NAME, Some(matches)) => BuildCommand::new().handle_match(env, matches),
+        (BundleCommand::NAME, Some(matches)) => BundleCommand::new().handle_match(env, matches),
+        (CleanCommand::NAME, Some(matches)) => CleanCommand::new().handle_match(env, matches),
+        (ExecCommand::NAME, Some(matches)) => ExecCommand::new().handle_match(env, matches),
+        (ExportCommand::NAME, Some(matches)) => ExportCommand::new().handle_match(env, matches),
+        (FetchCommand::NAME, Some(matches)) => FetchCommand::new().handle_match(env, matches),
+        (GcCommand::NAME, Some(matches)) => GcCommand::new().handle_match(env, matches),
+        (ImportCommand::NAME, Some(matches)) => ImportCommand::new().handle_match(env, matches),
+        (ListCommand::NAME, Some(matches)) => ListCommand::new().handle_match(env, matches),
+        (LogsCommand::NAME, Some(matches)) => LogsCommand::new().handle_match(env, matches),
+        (RemoveCommand::NAME, Some(matches)) => RemoveCommand::new().handle_match(env, matches),
+        (RestartCommand::NAME, Some(matches)) => RestartCommand::new().handle_match(env, matches),
+        (RunCommand::NAME, Some(matches)) => RunCommand::new().handle_match(env, matches),
+        (StatusCommand::NAME, Some(matches)) => StatusCommand::new().handle_match(env, matches),
+        (StopCommand::NAME, Some(matches)) => StopCommand::new().handle_match(env, matches),
+        (UpdateCommand::NAME, Some(matches)) => UpdateCommand::new().handle_match(env, matches),
+        _ => unreachable!(),
+    }
+}
+
+fn app() -> App<'static, 'static> {
+    App::new("soma")
         .version(VERSION)
         .about("Cross-platform CTF problem container manager")
         .setting(AppSettings::SubcommandRequiredElseHelp)
-        .subcommand(add_command.app())
-        .subcommand(build_command.app())
-        .subcommand(clean_command.app())
-        .subcommand(fetch_command.app())
-        .subcommand(list_command.app())
-        .subcommand(remove_command.app())
-        .subcommand(run_command.app())
-        .subcommand(stop_command.app())
-        .subcommand(update_command.app())
-        .get_matches();
-
-    let mut data_dir = DataDirectory::new()?;
-    let env = cli_env(&mut data_dir)?;
+        .arg(
+            Arg::with_name("host")
+                .short("H")
+                .long("host")
+                .help("docker endpoint such as unix:///var/run/docker.sock, tcp://host:2375, or https://host:2376")
+                .value_name("HOST")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("engine")
+                .long("engine")
+                .help("container engine behind the endpoint, detected automatically by default")
+                .value_name("ENGINE")
+                .possible_values(&["docker", "podman"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .help("output format, where json prints a single document with the result, events, and error")
+                .value_name("FORMAT")
+                .possible_values(&["text", "json"])
+                .takes_value(true),
+        )
+        .subcommand(AddCommand::new().app())
+        .subcommand(BuildCommand::new().app())
+        .subcommand(BundleCommand::new().app())
+        .subcommand(CleanCommand::new().app())
+        .subcommand(ConfigCommand::new().app())
+        .subcommand(DoctorCommand::new().app())
+        .subcommand(ExecCommand::new().app())
+        .subcommand(ExportCommand::new().app())
+        .subcommand(FetchCommand::new().app())
+        .subcommand(GcCommand::new().app())
+        .subcommand(ImportCommand::new().app())
+        .subcommand(ListCommand::new().app())
+        .subcommand(LogsCommand::new().app())
+        .subcommand(RemoveCommand::new().app())
+        .subcommand(RestartCommand::new().app())
+        .subcommand(RunCommand::new().app())
+        .subcommand(StatusCommand::new().app())
+        .subcommand(StopCommand::new().app())
+        .subcommand(UpdateCommand::new().app())
+}
 
-    match matches.subcommand() {
-        (AddCommand::NAME, Some(matches)) => add_command.handle_match(env, matches),
-        (BuildCommand::NAME, Some(matches)) => build_command.handle_match(env, matches),
-        (CleanCommand::NAME, Some(matches)) => clean_command.handle_match(env, matches),
-        (FetchCommand::NAME, Some(matches)) => fetch_command.handle_match(env, matches),
-        (ListCommand::NAME, Some(matches)) => list_command.handle_match(env, matches),
-        (RemoveCommand::NAME, Some(matches)) => remove_command.handle_match(env, matches),
-        (RunCommand::NAME, Some(matches)) => run_command.handle_match(env, matches),
-        (StopCommand::NAME, Some(matches)) => stop_command.handle_match(env, matches),
-        (UpdateCommand::NAME, Some(matches)) => update_command.handle_match(env, matches),
-        _ => unreachable!(),
+// Sets the output format as soon as it is known, so that main can report errors in it
+fn main_result(
+    matches: &ArgMatches,
+    output: &mut Option<OutputFormat>,
+    document: &Rc<RefCell<JsonDocument>>,
+) -> SomaResult<()> {
+    // Diagnoses the data directory and the engine instead of requiring them
+    if let (DoctorCommand::NAME, Some(_)) = matches.subcommand() {
+        return DoctorCommand::new().handle(matches, output, document);
+    }
+
+    let engine = matches
+        .value_of("engine")
+        .map(EngineKind::from_str)
+        .transpose()?;
+
+    let data_dir = DataDirectory::new()?;
+    let config_manager = data_dir.register::<ConfigManager>()?;
+    *output = output.or_else(|| config_manager.config().output());
+
+    // Runs before the endpoint is resolved, so that a broken docker_host can be changed
+    if let (ConfigCommand::NAME, Some(matches)) = matches.subcommand() {
+        let mut config_manager = config_manager;
+        return match output.unwrap_or(OutputFormat::Text) {
+            OutputFormat::Json => ConfigCommand::new().handle(
+                &mut config_manager,
+                &mut JsonPrinter::new(document.clone()),
+                matches,
+            ),
+            OutputFormat::Text => ConfigCommand::new().handle(
+                &mut config_manager,
+                &mut TerminalPrinter::new(),
+                matches,
+            ),
+        };
+    }
+    let docker_host = DockerHost::from_env(
+        matches.value_of("host"),
+        config_manager.config().docker_host(),
+    )?;
+
+    // Each kind of endpoint uses a different connector type
+    match docker_host {
+        #[cfg(unix)]
+        DockerHost::Unix(path) => handle_output(
+            DockerEngine::with_kind(
+                docker::connect_unix(&path)?,
+                engine.or_else(|| EngineKind::from_socket_path(&path)),
+            ),
+            &data_dir,
+            config_manager,
+            matches,
+            *output,
+            document,
+        ),
+        #[cfg(windows)]
+        DockerHost::NamedPipe(path) => handle_output(
+            DockerEngine::with_kind(docker::connect_named_pipe(&path)?, engine),
+            &data_dir,
+            config_manager,
+            matches,
+            *output,
+            document,
+        ),
+        DockerHost::Http(addr) => handle_output(
+            DockerEngine::with_kind(docker::connect_http(&addr)?, engine),
+            &data_dir,
+            config_manager,
+            matches,
+            *output,
+            document,
+        ),
+        DockerHost::Https { addr, cert_path } => handle_output(
+            DockerEngine::with_kind(docker::connect_https(&addr, &cert_path)?, engine),
+            &data_dir,
+            config_manager,
+            matches,
+            *output,
+            document,
+        ),
     }
 }
 
 fn main() {
-    if let Err(err) = main_result() {
+    let matches = app().get_matches();
+    let document = Rc::new(RefCell::new(JsonDocument::new(matches.subcommand_name())));
+    // The configured format is unknown until the data directory is loaded
+    let mut output = matches
+        .value_of("output")
+        .and_then(|output| OutputFormat::from_str(output).ok());
+
+    let mut result = main_result(&matches, &mut output, &document);
+    if output == Some(OutputFormat::Json) {
+        result = document.borrow_mut().finish(&result).and(result);
+    }
+
+    if let Err(err) = result {
         eprintln!("{}", err.to_string());
         std::process::exit(1);
     }
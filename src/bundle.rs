@@ -0,0 +1,138 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::export::{self, ExportManifest, EXPORT_MANIFEST_FILE_NAME, ROOTFS_FILE_NAME};
+use crate::prelude::*;
+use crate::{read_file_contents, NameString};
+
+const INDEX_FILE_NAME: &str = "index";
+const REPOSITORY_DIR_NAME: &str = "repositories";
+const IMAGE_DIR_NAME: &str = "images";
+
+// Bundles have the following layout:
+// index, repositories/<repo name>/..., images/<fully qualified name>/{export.toml, rootfs.tar}
+pub struct BundleWriter<W: Write> {
+    tar: tar::Builder<GzEncoder<W>>,
+}
+
+impl<W: Write> BundleWriter<W> {
+    pub fn new(writer: W) -> BundleWriter<W> {
+        BundleWriter {
+            tar: tar::Builder::new(GzEncoder::new(writer, Compression::default())),
+        }
+    }
+
+    pub fn append_index(&mut self, index: &[u8]) -> SomaResult<()> {
+        export::append_bytes(&mut self.tar, INDEX_FILE_NAME, index)
+    }
+
+    pub fn append_repository(
+        &mut self,
+        repo_name: &NameString,
+        repo_path: impl AsRef<Path>,
+    ) -> SomaResult<()> {
+        self.tar
+            .append_dir_all(Path::new(REPOSITORY_DIR_NAME).join(repo_name), repo_path)?;
+        Ok(())
+    }
+
+    pub fn append_image(&mut self, manifest: &ExportManifest, rootfs: File) -> SomaResult<()> {
+        let image_dir = Path::new(IMAGE_DIR_NAME).join(format!(
+            "{}.{}",
+            manifest.repo_name(),
+            manifest.prob_name()
+        ));
+        export::append_bytes(
+            &mut self.tar,
+            image_dir.join(EXPORT_MANIFEST_FILE_NAME),
+            toml::to_string(manifest)?.as_bytes(),
+        )?;
+        export::append_file(&mut self.tar, image_dir.join(ROOTFS_FILE_NAME), rootfs)
+    }
+
+    pub fn finish(self) -> SomaResult<()> {
+        self.tar.into_inner()?.finish()?;
+        Ok(())
+    }
+}
+
+// Bundle contents unpacked into a temporary directory
+pub struct UnpackedBundle {
+    root: PathBuf,
+}
+
+impl UnpackedBundle {
+    pub fn unpack(
+        bundle_path: impl AsRef<Path>,
+        destination: impl AsRef<Path>,
+    ) -> SomaResult<UnpackedBundle> {
+        let mut archive = tar::Archive::new(GzDecoder::new(File::open(bundle_path)?));
+        archive.unpack(&destination)?;
+
+        let root = destination.as_ref().to_owned();
+        if !root.join(INDEX_FILE_NAME).is_file() {
+            Err(SomaError::InvalidBundle)?;
+        }
+
+        Ok(UnpackedBundle { root })
+    }
+
+    pub fn index(&self) -> SomaResult<Vec<u8>> {
+        read_file_contents(self.root.join(INDEX_FILE_NAME))
+    }
+
+    pub fn repository_root(&self) -> PathBuf {
+        self.root.join(REPOSITORY_DIR_NAME)
+    }
+
+    // Filesystems of the images are left in place and returned as paths
+    pub fn images(&self) -> SomaResult<Vec<(ExportManifest, PathBuf)>> {
+        let image_root = self.root.join(IMAGE_DIR_NAME);
+        if !image_root.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        fs::read_dir(image_root)?
+            .map(|entry| {
+                let image_dir = entry?.path();
+                let manifest = toml::from_slice(&read_file_contents(
+                    image_dir.join(EXPORT_MANIFEST_FILE_NAME),
+                )?)?;
+                Ok((manifest, image_dir.join(ROOTFS_FILE_NAME)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let repo_dir = tempdir().unwrap();
+        fs::write(repo_dir.path().join("soma.toml"), "name = \"a\"").unwrap();
+
+        let bundle_dir = tempdir().unwrap();
+        let bundle_path = bundle_dir.path().join("bundle.tar.gz");
+        let mut writer = BundleWriter::new(File::create(&bundle_path).unwrap());
+        writer.append_index(b"index").unwrap();
+        writer
+            .append_repository(&"test".parse().unwrap(), repo_dir.path())
+            .unwrap();
+        writer.finish().unwrap();
+
+        let unpack_dir = tempdir().unwrap();
+        let bundle = UnpackedBundle::unpack(&bundle_path, unpack_dir.path()).unwrap();
+        assert_eq!(bundle.index().unwrap(), b"index");
+        assert!(bundle.repository_root().join("test/soma.toml").is_file());
+        assert!(bundle.images().unwrap().is_empty());
+    }
+}
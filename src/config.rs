@@ -0,0 +1,318 @@
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::data_dir::{DirectoryManager, Registration};
+use crate::docker::DockerHost;
+use crate::prelude::*;
+use crate::NameString;
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+// Problems are only reachable from the local machine unless configured otherwise
+pub const DEFAULT_BIND_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+pub const CONFIG_KEYS: &[&str] = &[
+    "bind_address",
+    "build_jobs",
+    "docker_host",
+    "output",
+    "port_range",
+    "username",
+];
+
+fn config_path<'a>(registration: &Registration<'a, ConfigManager<'a>>) -> PathBuf {
+    registration.root_path().join(CONFIG_FILE_NAME)
+}
+
+// Defaults for options that are otherwise given on every invocation
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct Config {
+    username: Option<NameString>,
+    docker_host: Option<String>,
+    port_range: Option<PortRange>,
+    build_jobs: Option<usize>,
+    bind_address: Option<IpAddr>,
+    output: Option<OutputFormat>,
+}
+
+impl Config {
+    pub fn username(&self) -> Option<&NameString> {
+        self.username.as_ref()
+    }
+
+    pub fn docker_host(&self) -> Option<&str> {
+        self.docker_host.as_deref()
+    }
+
+    pub fn port_range(&self) -> Option<PortRange> {
+        self.port_range
+    }
+
+    pub fn build_jobs(&self) -> Option<usize> {
+        self.build_jobs
+    }
+
+    pub fn bind_address(&self) -> Option<IpAddr> {
+        self.bind_address
+    }
+
+    pub fn output(&self) -> Option<OutputFormat> {
+        self.output
+    }
+
+    pub fn get(&self, key: &str) -> SomaResult<Option<String>> {
+        fn format_value(value: Option<impl ToString>) -> Option<String> {
+            value.map(|value| value.to_string())
+        }
+
+        Ok(match key {
+            "bind_address" => format_value(self.bind_address),
+            "build_jobs" => format_value(self.build_jobs),
+            "docker_host" => format_value(self.docker_host.as_ref()),
+            "output" => format_value(self.output),
+            "port_range" => format_value(self.port_range),
+            "username" => format_value(self.username.as_ref()),
+            _ => Err(SomaError::InvalidConfigKey)?,
+        })
+    }
+
+    // An empty value removes the entry
+    pub fn set(&mut self, key: &str, value: &str) -> SomaResult<()> {
+        fn parse<T: FromStr>(value: &str) -> SomaResult<Option<T>> {
+            if value.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(value.parse().or(Err(SomaError::InvalidConfigValue))?))
+            }
+        }
+
+        match key {
+            "bind_address" => self.bind_address = parse(value)?,
+            "build_jobs" => match parse(value)? {
+                Some(0) => Err(SomaError::InvalidConfigValue)?,
+                build_jobs => self.build_jobs = build_jobs,
+            },
+            // An endpoint that cannot be parsed would make every other command fail
+            "docker_host" => {
+                if !value.is_empty() && DockerHost::from_env(Some(value), None).is_err() {
+                    Err(SomaError::InvalidConfigValue)?;
+                }
+                self.docker_host = parse(value)?
+            }
+            "output" => self.output = parse(value)?,
+            "port_range" => self.port_range = parse(value)?,
+            "username" => self.username = parse(value)?,
+            _ => Err(SomaError::InvalidConfigKey)?,
+        }
+        Ok(())
+    }
+}
+
+pub struct ConfigManager<'a> {
+    registration: Registration<'a, ConfigManager<'a>>,
+    config: Config,
+    dirty: bool,
+}
+
+impl<'a> Deref for ConfigManager<'a> {
+    type Target = Registration<'a, Self>;
+
+    fn deref(&self) -> &<Self as Deref>::Target {
+        &self.registration
+    }
+}
+
+impl<'a> DirectoryManager<'a> for ConfigManager<'a> {
+    const DIR: &'static str = "config";
+
+    fn new(registration: Registration<'a, Self>) -> SomaResult<Self> {
+        let config_path = config_path(&registration);
+        let config = if config_path.exists() {
+            toml::from_str(&fs::read_to_string(config_path)?)?
+        } else {
+            Config::default()
+        };
+
+        Ok(ConfigManager {
+            registration,
+            config,
+            dirty: false,
+        })
+    }
+}
+
+impl<'a> ConfigManager<'a> {
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> SomaResult<()> {
+        self.config.set(key, value)?;
+        self.dirty = true;
+        Ok(())
+    }
+}
+
+impl<'a> Drop for ConfigManager<'a> {
+    fn drop(&mut self) {
+        if self.dirty {
+            let result = toml::to_string(&self.config)
+                .map_err(failure::Error::from)
+                .and_then(|contents| Ok(fs::write(config_path(self), contents)?));
+            if result.is_err() {
+                eprintln!("Failed to save the configuration file");
+            }
+        }
+    }
+}
+
+// Inclusive range of host ports, written as 'start-end'
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PortRange {
+    start: u32,
+    end: u32,
+}
+
+impl PortRange {
+    pub fn new(start: u32, end: u32) -> SomaResult<PortRange> {
+        if start == 0 || start > end || end > u32::from(u16::MAX) {
+            Err(SomaError::InvalidConfigValue)?
+        }
+        Ok(PortRange { start, end })
+    }
+
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    pub fn end(&self) -> u32 {
+        self.end
+    }
+
+    pub fn ports(&self) -> impl Iterator<Item = u32> {
+        self.start..=self.end
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split: Vec<_> = s.splitn(2, '-').collect();
+        match split.as_slice() {
+            [start, end] => PortRange::new(
+                start
+                    .trim()
+                    .parse()
+                    .or(Err(SomaError::InvalidConfigValue))?,
+                end.trim().parse().or(Err(SomaError::InvalidConfigValue))?,
+            ),
+            _ => Err(SomaError::InvalidConfigValue)?,
+        }
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl Serialize for PortRange {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for PortRange {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        value
+            .parse()
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&value), &"start-end"))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = SomaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(SomaError::InvalidConfigValue),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputFormat::Text => write!(f, "text"),
+            OutputFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_get_set() {
+        let mut config = Config::default();
+        assert_eq!(config.get("build_jobs").unwrap(), None);
+
+        config.set("build_jobs", "8").unwrap();
+        config.set("port_range", "30000-30100").unwrap();
+        config.set("output", "json").unwrap();
+        config.set("bind_address", "0.0.0.0").unwrap();
+        config.set("docker_host", "tcp://127.0.0.1:2375").unwrap();
+        assert_eq!(config.build_jobs(), Some(8));
+        assert_eq!(
+            config.get("port_range").unwrap(),
+            Some("30000-30100".to_owned())
+        );
+        assert_eq!(config.output(), Some(OutputFormat::Json));
+
+        assert!(config.set("build_jobs", "0").is_err());
+        assert!(config.set("port_range", "30100-30000").is_err());
+        assert!(config.set("username", "Invalid Name").is_err());
+        assert!(config.set("unknown", "value").is_err());
+        assert!(config.set("docker_host", "127.0.0.1:2375").is_err());
+        assert_eq!(config.docker_host(), Some("tcp://127.0.0.1:2375"));
+
+        let serialized = toml::to_string(&config).unwrap();
+        let config: Config = toml::from_str(&serialized).unwrap();
+        assert_eq!(
+            config.port_range(),
+            Some(PortRange::new(30000, 30100).unwrap())
+        );
+        assert_eq!(config.bind_address().unwrap().to_string(), "0.0.0.0");
+
+        let mut config = config;
+        config.set("build_jobs", "").unwrap();
+        assert_eq!(config.build_jobs(), None);
+    }
+}
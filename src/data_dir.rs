@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::collections::HashSet;
 use std::fs;
 use std::fs::File;
@@ -17,7 +18,7 @@ const LOCK_FILE_NAME: &str = "soma.lock";
 pub struct DataDirectory {
     root_path: PathBuf,
     lock: File,
-    manager_set: HashSet<&'static str>,
+    manager_set: RefCell<HashSet<&'static str>>,
 }
 
 impl DataDirectory {
@@ -34,7 +35,7 @@ impl DataDirectory {
 
         if !path.exists() {
             fs::create_dir_all(&path)?;
-            println!("Created Soma data directory at: {}", path.to_string_lossy());
+            eprintln!("Created Soma data directory at: {}", path.to_string_lossy());
         }
 
         DataDirectory::initialize_and_lock(path)
@@ -53,15 +54,19 @@ impl DataDirectory {
         Ok(DataDirectory {
             root_path: path,
             lock,
-            manager_set: HashSet::new(),
+            manager_set: RefCell::new(HashSet::new()),
         })
     }
 
-    pub fn register<'a, T>(&'a mut self) -> SomaResult<T>
+    pub fn root_path(&self) -> &PathBuf {
+        &self.root_path
+    }
+
+    pub fn register<'a, T>(&'a self) -> SomaResult<T>
     where
         T: DirectoryManager<'a>,
     {
-        if !self.manager_set.insert(T::DIR) {
+        if !self.manager_set.borrow_mut().insert(T::DIR) {
             panic!("A manager should be registered only once");
         }
 
@@ -73,7 +78,8 @@ impl DataDirectory {
 
 impl Drop for DataDirectory {
     fn drop(&mut self) {
-        if self.lock.unlock().is_err() {
+        // The inherent File::unlock of newer toolchains would shadow the fs2 method
+        if FileExt::unlock(&self.lock).is_err() {
             eprintln!("Failed to unlock the data directory");
         }
     }
@@ -1,17 +1,16 @@
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::Path;
 
-use bollard::container::{
-    APIContainers, Config, CreateContainerOptions, HostConfig, ListContainersOptions, PortBinding,
-    PruneContainersOptions, RemoveContainerOptions, StartContainerOptions, StopContainerOptions,
-};
-use bollard::image::{
-    APIImages, BuildImageOptions, ListImagesOptions, PruneImagesOptions, RemoveImageOptions,
-};
-use bollard::Docker;
+use bollard::container::Config;
 use failure::Error;
+use futures::future;
 use futures::{Future, Stream};
 use hyper::client::connect::Connect;
 
+use crate::event::Event;
 use crate::prelude::*;
 use crate::problem::Problem;
 use crate::{Environment, Printer, VERSION};
@@ -20,18 +19,55 @@ const LABEL_KEY_VERSION: &str = "soma.version";
 const LABEL_KEY_USERNAME: &str = "soma.username";
 const LABEL_KEY_REPOSITORY: &str = "soma.repository";
 const LABEL_KEY_PROBLEM: &str = "soma.problem";
+const LABEL_KEY_BASE_DIGEST: &str = "soma.base-digest";
+const LABEL_KEY_LIBC_VERSION: &str = "soma.libc-version";
+const LABEL_KEY_CONTENT_HASH: &str = "soma.content-hash";
+const LABEL_KEY_INSTANCE: &str = "soma.instance";
+const LABEL_KEY_BIND_ADDRESS: &str = "soma.bind-address";
+const LABEL_KEY_PORT: &str = "soma.port";
+const LABEL_KEY_RESTART_POLICY: &str = "soma.restart-policy";
+const LABEL_KEY_ROLE: &str = "soma.role";
+const ROLE_PROXY: &str = "proxy";
+// Docker API 1.39 lists untagged images with this tag instead of an empty list
+const UNTAGGED_REPO_TAG: &str = "<none>:<none>";
+
+// Containers without an instance label were created before instances were introduced
+pub const DEFAULT_INSTANCE: &str = "default";
+// Containers in these states have to be stopped before they are removed or started again
+pub const STATES_TO_STOP: &[&str] = &["paused", "restarting", "running"];
+// Publishes the ports of problems on internal networks, which cannot publish them by themselves
+pub const PROXY_IMAGE: &str = "alpine/socat:latest";
+
+#[cfg(windows)]
+pub use self::connection::connect_named_pipe;
+#[cfg(unix)]
+pub use self::connection::connect_unix;
+pub use self::connection::{connect_http, connect_https, DockerConnection, DockerHost};
+pub use self::daemon::DockerEngine;
+pub use self::engine::{
+    engine_info, BuildOptions, BuildOutput, ContainerEngine, ContainerOptions, ContainerSummary,
+    EngineFuture, EngineInfo, EngineKind, EngineStream, ExecOutput, ImageDetails, ImageSummary,
+    NetworkOptions, NetworkSummary, PortSummary, RestartPolicy,
+};
+pub use self::fake::FakeEngine;
 
-type DockerLabel<'a> = HashMap<&'a str, &'a str>;
-type SomaFilter = HashMap<String, Vec<String>>;
+mod api;
+mod connection;
+mod daemon;
+mod engine;
+mod fake;
+
+pub type DockerLabel<'a> = HashMap<&'a str, &'a str>;
+pub type SomaFilter = HashMap<String, Vec<String>>;
 
 #[cfg(windows)]
-pub fn connect_default() -> SomaResult<Docker<impl Connect>> {
-    Docker::connect_with_named_pipe("npipe:////./pipe/docker_engine", 600)
+pub fn connect_default() -> SomaResult<DockerConnection<impl Connect>> {
+    connect_named_pipe("//./pipe/docker_engine")
 }
 
 #[cfg(unix)]
-pub fn connect_default() -> SomaResult<Docker<impl Connect>> {
-    Docker::connect_with_unix("unix:///var/run/docker.sock", 600)
+pub fn connect_default() -> SomaResult<DockerConnection<impl Connect>> {
+    connect_unix("/var/run/docker.sock")
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -45,7 +81,7 @@ pub enum VersionStatus {
 pub struct SomaImage {
     repo_name: String,
     prob_name: String,
-    image: APIImages,
+    image: ImageSummary,
     status: VersionStatus,
 }
 
@@ -53,7 +89,7 @@ impl SomaImage {
     pub fn new(
         repo_name: String,
         prob_name: String,
-        image: APIImages,
+        image: ImageSummary,
         status: VersionStatus,
     ) -> SomaImage {
         SomaImage {
@@ -72,13 +108,42 @@ impl SomaImage {
         &self.prob_name
     }
 
-    pub fn image(&self) -> &APIImages {
+    pub fn image(&self) -> &ImageSummary {
         &self.image
     }
 
     pub fn status(&self) -> VersionStatus {
         self.status
     }
+
+    // Engines report untagged images either without tags or with a placeholder tag
+    pub fn is_dangling(&self) -> bool {
+        self.image
+            .repo_tags
+            .iter()
+            .all(|tag| tag == UNTAGGED_REPO_TAG)
+    }
+
+    pub fn base_digest(&self) -> Option<&String> {
+        self.label(LABEL_KEY_BASE_DIGEST)
+    }
+
+    pub fn libc_version(&self) -> Option<&String> {
+        self.label(LABEL_KEY_LIBC_VERSION)
+    }
+
+    pub fn content_hash(&self) -> Option<&String> {
+        self.label(LABEL_KEY_CONTENT_HASH)
+    }
+
+    // Images built before content hashes were recorded are considered outdated
+    pub fn is_outdated(&self, content_hash: &str) -> bool {
+        self.content_hash().map(String::as_str) != Some(content_hash)
+    }
+
+    fn label(&self, key: &str) -> Option<&String> {
+        self.image.labels.get(key)
+    }
 }
 
 struct SomaFilterBuilder {
@@ -112,6 +177,15 @@ impl SomaFilterBuilder {
         )
     }
 
+    pub fn append_instance(self, instance: Option<&str>) -> SomaFilterBuilder {
+        match instance {
+            Some(instance) => {
+                self.append_filter(LABEL_KEY_INSTANCE.to_owned(), instance.to_owned())
+            }
+            None => self,
+        }
+    }
+
     pub fn build(self) -> SomaFilter {
         let mut filter = SomaFilter::new();
         filter.insert("label".to_owned(), self.label_filter);
@@ -120,11 +194,16 @@ impl SomaFilterBuilder {
 }
 
 pub fn image_exists(images: &[SomaImage], image_name: &str) -> bool {
-    images.iter().any(|image| match &image.image().repo_tags {
-        Some(tags) => tags
+    find_image(images, image_name).is_some()
+}
+
+pub fn find_image<'a>(images: &'a [SomaImage], image_name: &str) -> Option<&'a SomaImage> {
+    images.iter().find(|image| {
+        image
+            .image()
+            .repo_tags
             .iter()
-            .any(|tag| tag.starts_with(format!("{}:", image_name).as_str())),
-        None => false,
+            .any(|tag| tag.starts_with(format!("{}:", image_name).as_str()))
     })
 }
 
@@ -152,7 +231,8 @@ pub fn image_from_repo_and_prob_exists(
 pub struct SomaContainer {
     repo_name: String,
     prob_name: String,
-    container: APIContainers,
+    instance: String,
+    container: ContainerSummary,
     status: VersionStatus,
 }
 
@@ -160,12 +240,14 @@ impl SomaContainer {
     pub fn new(
         repo_name: String,
         prob_name: String,
-        container: APIContainers,
+        instance: String,
+        container: ContainerSummary,
         status: VersionStatus,
     ) -> SomaContainer {
         SomaContainer {
             repo_name,
             prob_name,
+            instance,
             container,
             status,
         }
@@ -179,13 +261,43 @@ impl SomaContainer {
         &self.prob_name
     }
 
-    pub fn container(&self) -> &APIContainers {
+    pub fn instance(&self) -> &String {
+        &self.instance
+    }
+
+    pub fn container(&self) -> &ContainerSummary {
         &self.container
     }
 
     pub fn status(&self) -> VersionStatus {
         self.status
     }
+
+    // Containers created before bindings were recorded only report ports while running
+    pub fn binding(&self) -> Option<(IpAddr, u32)> {
+        let labels = &self.container.labels;
+        match (
+            labels.get(LABEL_KEY_BIND_ADDRESS),
+            labels.get(LABEL_KEY_PORT),
+        ) {
+            (Some(bind_address), Some(port)) => match (bind_address.parse(), port.parse()) {
+                (Ok(bind_address), Ok(port)) => Some((bind_address, port)),
+                _ => None,
+            },
+            _ => self.container.ports.iter().find_map(|port| {
+                let bind_address = port.ip.as_ref()?.parse().ok()?;
+                Some((bind_address, port.public_port? as u32))
+            }),
+        }
+    }
+
+    pub fn restart_policy(&self) -> RestartPolicy {
+        self.container
+            .labels
+            .get(LABEL_KEY_RESTART_POLICY)
+            .and_then(|restart_policy| restart_policy.parse().ok())
+            .unwrap_or_default()
+    }
 }
 
 pub fn container_exists(containers: &[SomaContainer], container_id: &str) -> bool {
@@ -208,6 +320,19 @@ pub fn container_from_prob_running(containers: &[SomaContainer], problem: &Probl
     })
 }
 
+pub fn container_from_instance_running(
+    containers: &[SomaContainer],
+    problem: &Problem,
+    instance: &str,
+) -> bool {
+    containers.iter().any(|container| {
+        container.repo_name() == problem.repo_name()
+            && container.prob_name() == problem.prob_name()
+            && container.instance() == instance
+            && container.container().state == "running"
+    })
+}
+
 pub fn containers_from_prob(
     containers: Vec<SomaContainer>,
     problem: &Problem,
@@ -221,19 +346,63 @@ pub fn containers_from_prob(
         .collect()
 }
 
-pub fn list_containers(
-    env: &Environment<impl Connect, impl Printer>,
-) -> impl Future<Item = Vec<SomaContainer>, Error = Error> {
-    let soma_filter = SomaFilterBuilder::new().append_user(env.username()).build();
-    env.docker
-        .list_containers(Some(ListContainersOptions::<String> {
-            all: true,
-            filters: soma_filter,
-            ..Default::default()
-        }))
+// Every instance is selected when the instance is omitted
+pub fn containers_from_instance(
+    containers: Vec<SomaContainer>,
+    problem: &Problem,
+    instance: Option<&str>,
+) -> Vec<SomaContainer> {
+    containers_from_prob(containers, problem)
+        .into_iter()
+        .filter(|container| instance.map_or(true, |instance| container.instance() == instance))
+        .collect()
+}
+
+// Finds the proxy of a problem container, or the problem container of a proxy
+pub fn find_same_instance<'a>(
+    containers: &'a [SomaContainer],
+    container: &SomaContainer,
+) -> Option<&'a SomaContainer> {
+    containers.iter().find(|other| {
+        other.repo_name() == container.repo_name()
+            && other.prob_name() == container.prob_name()
+            && other.instance() == container.instance()
+    })
+}
+
+// Problem containers, without the proxies which publish their ports
+pub fn list_containers<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+) -> impl Future<Item = Vec<SomaContainer>, Error = Error> + 'a {
+    list_user_containers(env.engine(), env.username())
+}
+
+pub fn list_proxies<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+) -> impl Future<Item = Vec<SomaContainer>, Error = Error> + 'a {
+    list_role_containers(env.engine(), env.username(), true)
+}
+
+// Lists containers without an environment, such as while diagnosing one
+pub fn list_user_containers<'a>(
+    engine: &'a impl ContainerEngine,
+    username: &str,
+) -> impl Future<Item = Vec<SomaContainer>, Error = Error> + 'a {
+    list_role_containers(engine, username, false)
+}
+
+fn list_role_containers<'a>(
+    engine: &'a impl ContainerEngine,
+    username: &str,
+    proxies: bool,
+) -> impl Future<Item = Vec<SomaContainer>, Error = Error> + 'a {
+    let soma_filter = SomaFilterBuilder::new().append_user(username).build();
+    engine
+        .list_containers(soma_filter)
         .map(move |containers| -> Vec<SomaContainer> {
             containers
                 .into_iter()
+                .filter(|container| container.labels.contains_key(LABEL_KEY_ROLE) == proxies)
                 .filter_map(|container| {
                     let labels = &container.labels;
                     if let (Some(repo_name), Some(prob_name)) = (
@@ -245,9 +414,14 @@ pub fn list_containers(
                             Some(_) => VersionStatus::VersionMismatch,
                             None => VersionStatus::NoVersionFound,
                         };
+                        let instance = labels
+                            .get(LABEL_KEY_INSTANCE)
+                            .map_or(DEFAULT_INSTANCE, String::as_str)
+                            .to_owned();
                         Some(SomaContainer::new(
                             repo_name.to_owned(),
                             prob_name.to_owned(),
+                            instance,
                             container,
                             status,
                         ))
@@ -259,21 +433,24 @@ pub fn list_containers(
         })
 }
 
-pub fn list_images(
-    env: &Environment<impl Connect, impl Printer>,
-) -> impl Future<Item = Vec<SomaImage>, Error = Error> {
-    let soma_filter = SomaFilterBuilder::new().append_user(env.username()).build();
-    env.docker
-        .list_images(Some(ListImagesOptions::<String> {
-            filters: soma_filter,
-            ..Default::default()
-        }))
+pub fn list_images<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+) -> impl Future<Item = Vec<SomaImage>, Error = Error> + 'a {
+    list_user_images(env.engine(), env.username())
+}
+
+pub fn list_user_images<'a>(
+    engine: &'a impl ContainerEngine,
+    username: &str,
+) -> impl Future<Item = Vec<SomaImage>, Error = Error> + 'a {
+    let soma_filter = SomaFilterBuilder::new().append_user(username).build();
+    engine
+        .list_images(soma_filter)
         .map(move |images| -> Vec<SomaImage> {
             images
                 .into_iter()
                 .filter_map(|image| {
-                    // soma_filter guarantees label existence
-                    let labels = image.labels.as_ref().unwrap();
+                    let labels = &image.labels;
                     if let (Some(repo_name), Some(prob_name)) = (
                         labels.get(LABEL_KEY_REPOSITORY),
                         labels.get(LABEL_KEY_PROBLEM),
@@ -298,42 +475,199 @@ pub fn list_images(
 }
 
 pub fn build<'a>(
-    env: &'a Environment<impl Connect, impl Printer>,
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
     labels: DockerLabel<'a>,
     image_name: &'a str,
-    build_context: Vec<u8>,
+    dockerfile: &'a str,
+    pull: bool,
+    build_context: File,
 ) -> impl Future<Item = (), Error = Error> + 'a {
-    let build_options = BuildImageOptions {
-        t: image_name,
-        pull: true,
-        forcerm: true,
+    build_with_output(
+        env,
+        labels,
+        image_name,
+        dockerfile,
+        pull,
+        build_context,
+        move |event| env.report(event),
+    )
+}
+
+// Passes an event for each line of the build output to on_event instead of the printer
+pub fn build_with_output<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    labels: DockerLabel<'a>,
+    image_name: &'a str,
+    dockerfile: &'a str,
+    pull: bool,
+    build_context: File,
+    mut on_event: impl FnMut(Event) + 'a,
+) -> impl Future<Item = (), Error = Error> + 'a {
+    let build_options = BuildOptions {
+        image_name,
+        dockerfile,
+        pull,
         labels,
-        ..Default::default()
     };
 
-    env.docker
-        .build_image(build_options, None, Some(build_context.into()))
-        .fold((), move |_, build_image_result| {
-            use bollard::image::BuildImageResults::*;
-            match build_image_result {
-                BuildImageStream { stream } => {
-                    let message = stream.trim();
-                    if message != "" {
-                        env.printer().write_line(message)
-                    }
-                    Ok(())
-                }
-                BuildImageError { error, .. } => {
-                    env.printer().write_line(error.trim());
-                    Err(SomaError::DockerBuildFailed)
-                }
-                _ => Ok(()),
+    env.engine()
+        .build_image(build_options, build_context)
+        .fold((), move |_, build_output| {
+            report_build_output(build_output, &mut on_event)
+        })
+}
+
+// Pulls the image so that it can be inspected before a build uses it
+pub fn pull<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    image_name: &'a str,
+    mut on_event: impl FnMut(Event) + 'a,
+) -> impl Future<Item = (), Error = Error> + 'a {
+    env.engine()
+        .pull_image(image_name)
+        .fold((), move |_, pull_output| {
+            report_build_output(pull_output, &mut on_event)
+        })
+}
+
+fn report_build_output(
+    build_output: BuildOutput,
+    on_event: &mut impl FnMut(Event),
+) -> Result<(), SomaError> {
+    match build_output {
+        BuildOutput::Stream(stream) => {
+            let message = stream.trim();
+            if message != "" {
+                on_event(Event::from_build_output(message))
             }
+            Ok(())
+        }
+        BuildOutput::Status {
+            id,
+            status,
+            progress,
+        } => {
+            on_event(match id {
+                Some(layer) => Event::Pulling {
+                    layer,
+                    status,
+                    progress,
+                },
+                None => Event::BuildOutput(status),
+            });
+            Ok(())
+        }
+        BuildOutput::Error(error) => {
+            on_event(Event::BuildError(error.trim().to_owned()));
+            Err(SomaError::DockerBuildFailed)
+        }
+    }
+}
+
+pub fn append_build_info<'a>(
+    labels: &mut DockerLabel<'a>,
+    base_digest: Option<&'a str>,
+    libc_version: Option<&'a str>,
+) {
+    if let Some(base_digest) = base_digest {
+        labels.insert(LABEL_KEY_BASE_DIGEST, base_digest);
+    }
+    if let Some(libc_version) = libc_version {
+        labels.insert(LABEL_KEY_LIBC_VERSION, libc_version);
+    }
+}
+
+pub fn append_instance<'a>(labels: &mut DockerLabel<'a>, instance: &'a str) {
+    labels.insert(LABEL_KEY_INSTANCE, instance);
+}
+
+// Records how the container was run, so that it can be recreated the same way
+pub fn append_run_options<'a>(
+    labels: &mut DockerLabel<'a>,
+    bind_address: &'a str,
+    port: &'a str,
+    restart_policy: RestartPolicy,
+) {
+    labels.insert(LABEL_KEY_BIND_ADDRESS, bind_address);
+    labels.insert(LABEL_KEY_PORT, port);
+    labels.insert(LABEL_KEY_RESTART_POLICY, restart_policy.as_str());
+}
+
+pub fn append_content_hash<'a>(labels: &mut DockerLabel<'a>, content_hash: &'a str) {
+    labels.insert(LABEL_KEY_CONTENT_HASH, content_hash);
+}
+
+// Returns the repository digest of a local image, which is absent for locally built images
+pub fn image_digest<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    image_name: &'a str,
+) -> impl Future<Item = Option<String>, Error = Error> + 'a {
+    env.engine()
+        .inspect_image(image_name)
+        .map(|image| image.repo_digests.into_iter().next())
+}
+
+pub fn inspect_config<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    image_name: &'a str,
+) -> impl Future<Item = Config<String>, Error = Error> + 'a {
+    env.engine()
+        .inspect_image(image_name)
+        .map(|image| image.config)
+}
+
+pub fn libc_version<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    image_name: &'a str,
+) -> impl Future<Item = Option<String>, Error = Error> + 'a {
+    // Images without ldd, such as scratch or distroless ones, have no libc version to report
+    run_command(env, image_name, vec!["ldd", "--version"]).then(|result| match result {
+        Ok((0, output)) => Ok(output.lines().next().map(|line| line.trim().to_owned())),
+        _ => Ok(None),
+    })
+}
+
+// Runs a command in a temporary container and returns its exit code with the output
+fn run_command<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    image_name: &'a str,
+    command: Vec<&'a str>,
+) -> impl Future<Item = (i64, String), Error = Error> + 'a {
+    let (entrypoint, cmd) = command.split_at(1);
+    env.engine()
+        .create_container(ContainerOptions {
+            image_name,
+            entrypoint: Some(entrypoint.to_vec()),
+            cmd: Some(cmd.to_vec()),
+            ..Default::default()
+        })
+        .and_then(move |container_id| {
+            let container_run = env
+                .engine()
+                .start_container(&container_id)
+                .and_then({
+                    let container_id = container_id.clone();
+                    move |_| env.engine().wait_container(&container_id)
+                })
+                .and_then({
+                    let container_id = container_id.clone();
+                    move |exit_code| {
+                        env.engine()
+                            .container_logs(&container_id)
+                            .map(move |output| (exit_code, output))
+                    }
+                });
+
+            container_run.then(move |result| {
+                env.engine()
+                    .remove_container(&container_id)
+                    .and_then(|_| result)
+            })
         })
 }
 
 pub fn docker_labels<'a>(
-    env: &'a Environment<impl Connect, impl Printer>,
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
     problem: &'a Problem,
 ) -> DockerLabel<'a> {
     vec![
@@ -347,97 +681,218 @@ pub fn docker_labels<'a>(
 }
 
 pub fn create<'a>(
-    env: &'a Environment<impl Connect, impl Printer>,
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
     labels: DockerLabel<'a>,
     image_name: &'a str,
-    port_str: &'a str,
+    network: Option<&'a str>,
+    port_binding: Option<(&'a str, &'a str, &'a str)>,
+    restart_policy: RestartPolicy,
 ) -> impl Future<Item = String, Error = Error> + 'a {
-    let mut port_bindings = HashMap::new();
-    port_bindings.insert(
-        "1337/tcp",
-        vec![PortBinding {
-            host_ip: "",
-            host_port: port_str,
-        }],
-    );
-
-    let host_config = HostConfig {
-        port_bindings: Some(port_bindings),
+    env.engine().create_container(ContainerOptions {
+        image_name,
+        labels,
+        port_bindings: port_binding.into_iter().collect(),
+        network,
+        restart_policy,
         ..Default::default()
-    };
+    })
+}
 
-    env.docker
-        .create_container(
-            None::<CreateContainerOptions<String>>,
-            Config {
-                image: Some(image_name),
-                labels: Some(labels),
-                host_config: Some(host_config),
-                ..Default::default()
-            },
-        )
-        .map(|container_results| container_results.id)
+// Forwards the port from the default bridge to the problem container on its internal network
+pub fn create_proxy<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    mut labels: DockerLabel<'a>,
+    command: Vec<&'a str>,
+    port_binding: (&'a str, &'a str, &'a str),
+    restart_policy: RestartPolicy,
+) -> impl Future<Item = String, Error = Error> + 'a {
+    labels.insert(LABEL_KEY_ROLE, ROLE_PROXY);
+    env.engine().create_container(ContainerOptions {
+        image_name: PROXY_IMAGE,
+        labels,
+        cmd: Some(command),
+        port_bindings: vec![port_binding],
+        restart_policy,
+        ..Default::default()
+    })
 }
 
-pub fn remove_image(
-    env: &Environment<impl Connect, impl Printer>,
-    image_name: &str,
-) -> impl Future<Item = (), Error = Error> {
-    env.docker
-        .remove_image(image_name, None::<RemoveImageOptions>, None)
-        .map(|_| ())
+pub fn create_network<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    labels: DockerLabel<'a>,
+    network_name: &'a str,
+    internal: bool,
+) -> impl Future<Item = String, Error = Error> + 'a {
+    env.engine().create_network(NetworkOptions {
+        name: network_name,
+        labels,
+        internal,
+    })
 }
 
-pub fn remove_container(
-    env: &Environment<impl Connect, impl Printer>,
+pub fn connect_network<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    network_id: &str,
     container_id: &str,
-) -> impl Future<Item = (), Error = Error> {
-    env.docker
-        .remove_container(container_id, None::<RemoveContainerOptions>)
+) -> impl Future<Item = (), Error = Error> + 'a {
+    env.engine().connect_network(network_id, container_id)
+}
+
+pub fn list_networks<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+) -> impl Future<Item = Vec<NetworkSummary>, Error = Error> + 'a {
+    let soma_filter = SomaFilterBuilder::new().append_user(env.username()).build();
+    env.engine().list_networks(soma_filter)
+}
+
+pub fn remove_network<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    network_id: &'a str,
+) -> impl Future<Item = (), Error = Error> + 'a {
+    env.engine().remove_network(network_id)
 }
 
-pub fn prune_images_from_prob(
-    env: &Environment<impl Connect, impl Printer>,
+pub fn list_networks_from_prob<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
     problem: &Problem,
-) -> impl Future<Item = (), Error = Error> {
+    instance: Option<&str>,
+) -> impl Future<Item = Vec<NetworkSummary>, Error = Error> + 'a {
     let soma_filter = SomaFilterBuilder::new()
         .append_user(env.username())
         .append_prob(problem)
+        .append_instance(instance)
         .build();
-    env.docker
-        .prune_images(Some(PruneImagesOptions {
-            filters: soma_filter,
-        }))
+    env.engine().list_networks(soma_filter)
+}
+
+// Proxies are attached to the networks, so they are removed first
+pub fn remove_networks_from_prob<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    problem: &'a Problem,
+    instance: Option<&'a str>,
+) -> impl Future<Item = (), Error = Error> + 'a {
+    remove_proxies_from_prob(env, problem, instance)
+        .and_then(move |_| list_networks_from_prob(env, problem, instance))
+        .and_then(move |networks| {
+            future::join_all(
+                networks
+                    .into_iter()
+                    .map(move |network| env.engine().remove_network(&network.id)),
+            )
+        })
+        .map(|_| ())
+}
+
+pub fn remove_proxies_from_prob<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    problem: &'a Problem,
+    instance: Option<&'a str>,
+) -> impl Future<Item = (), Error = Error> + 'a {
+    list_proxies(env)
+        .and_then(move |proxies| {
+            let proxies = containers_from_instance(proxies, problem, instance);
+            future::join_all(proxies.into_iter().map(move |proxy| {
+                let proxy = proxy.container;
+                let stopped: EngineFuture<()> = if STATES_TO_STOP.contains(&proxy.state.as_str()) {
+                    env.engine().stop_container(&proxy.id)
+                } else {
+                    Box::new(future::ok(()))
+                };
+                stopped.and_then(move |_| env.engine().remove_container(&proxy.id))
+            }))
+        })
         .map(|_| ())
 }
 
-pub fn prune_containers_from_prob(
-    env: &Environment<impl Connect, impl Printer>,
+// Copies a file out of the image through a temporary container, writing a tar archive to output
+// The container has no soma labels, so that it is never taken for a problem container
+pub fn download_file<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    image_name: &'a str,
+    path: &'a Path,
+    mut output: impl Write + 'a,
+) -> impl Future<Item = (), Error = Error> + 'a {
+    env.engine()
+        .create_container(ContainerOptions {
+            image_name,
+            ..Default::default()
+        })
+        .and_then(move |container_id| {
+            env.engine()
+                .download_from_container(&container_id, path)
+                .for_each(move |chunk| Ok(output.write_all(&chunk)?))
+                // The container is removed even if the download fails, which keeps its error
+                .then(move |downloaded| {
+                    env.engine()
+                        .remove_container(&container_id)
+                        .then(|removed| downloaded.and(removed))
+                })
+        })
+}
+
+pub fn remove_image<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    image_name: &str,
+) -> impl Future<Item = (), Error = Error> + 'a {
+    env.engine().remove_image(image_name)
+}
+
+pub fn remove_container<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    container_id: &str,
+) -> impl Future<Item = (), Error = Error> + 'a {
+    env.engine().remove_container(container_id)
+}
+
+pub fn prune_images_from_prob<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
     problem: &Problem,
-) -> impl Future<Item = (), Error = Error> {
+) -> impl Future<Item = (), Error = Error> + 'a {
     let soma_filter = SomaFilterBuilder::new()
         .append_user(env.username())
         .append_prob(problem)
         .build();
-    env.docker
-        .prune_containers(Some(PruneContainersOptions {
-            filters: soma_filter,
-        }))
-        .map(|_| ())
+    env.engine().prune_images(soma_filter)
+}
+
+pub fn prune_containers_from_prob<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    instance: Option<&str>,
+) -> impl Future<Item = (), Error = Error> + 'a {
+    let soma_filter = SomaFilterBuilder::new()
+        .append_user(env.username())
+        .append_prob(problem)
+        .append_instance(instance)
+        .build();
+    env.engine().prune_containers(soma_filter)
 }
 
-pub fn start(
-    env: &Environment<impl Connect, impl Printer>,
+pub fn start<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
     container_id: &str,
-) -> impl Future<Item = (), Error = Error> {
-    env.docker
-        .start_container(container_id, None::<StartContainerOptions<String>>)
+) -> impl Future<Item = (), Error = Error> + 'a {
+    env.engine().start_container(container_id)
 }
 
-pub fn stop(
-    env: &Environment<impl Connect, impl Printer>,
+pub fn stop<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
     container_id: &str,
-) -> impl Future<Item = (), Error = Error> {
-    env.docker
-        .stop_container(container_id, None::<StopContainerOptions>)
+) -> impl Future<Item = (), Error = Error> + 'a {
+    env.engine().stop_container(container_id)
+}
+
+pub fn logs<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    container_id: &str,
+) -> impl Future<Item = String, Error = Error> + 'a {
+    env.engine().container_logs(container_id)
+}
+
+pub fn exec<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+    container_id: &'a str,
+    cmd: Vec<&'a str>,
+) -> impl Future<Item = ExecOutput, Error = Error> + 'a {
+    env.engine().exec_container(container_id, cmd)
 }
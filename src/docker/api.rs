@@ -0,0 +1,166 @@
+use std::fs;
+use std::path::Path;
+
+use failure::{format_err, Error};
+use futures::future;
+use futures::{Future, Stream};
+use hyper::client::HttpConnector;
+use hyper::{Body, Chunk, Client, Method, Request, Uri};
+use hyper_tls::HttpsConnector;
+#[cfg(unix)]
+use hyperlocal::UnixConnector;
+use native_tls::{Certificate, Identity, TlsConnector};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::engine::EngineFuture;
+use crate::prelude::*;
+
+// Matches the version bollard 0.2 requests, so that both clients see the same API
+const API_VERSION: &str = "v1.39";
+const NUM_THREADS: usize = 1;
+
+// Plain HTTP access to the endpoints that bollard does not wrap, such as networks and the
+// component names of /version. bollard 0.2 keeps its request builder and connectors private,
+// so this client opens its own connection with the same hyper, hyper-tls, and hyperlocal
+// versions that bollard depends on. Only DockerEngine uses it, and it can be removed once
+// bollard exposes these endpoints.
+pub enum ApiClient {
+    #[cfg(unix)]
+    Unix {
+        client: Client<UnixConnector>,
+        path: String,
+    },
+    Http {
+        client: Client<HttpConnector>,
+        addr: String,
+    },
+    Https {
+        client: Client<HttpsConnector<HttpConnector>>,
+        addr: String,
+    },
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+impl ApiClient {
+    #[cfg(unix)]
+    pub fn unix(path: &str) -> ApiClient {
+        let mut client_builder = Client::builder();
+        client_builder.keep_alive(false);
+        ApiClient::Unix {
+            client: client_builder.build(UnixConnector::new()),
+            path: path.to_owned(),
+        }
+    }
+
+    pub fn http(addr: &str) -> ApiClient {
+        ApiClient::Http {
+            client: Client::builder().build(HttpConnector::new(NUM_THREADS)),
+            addr: addr.to_owned(),
+        }
+    }
+
+    pub fn https(addr: &str, identity_path: &Path, ca_path: &Path) -> SomaResult<ApiClient> {
+        let identity = Identity::from_pkcs12(&fs::read(identity_path)?, "")?;
+        let ca = Certificate::from_pem(&fs::read(ca_path)?)?;
+        let tls_connector = TlsConnector::builder()
+            .identity(identity)
+            .add_root_certificate(ca)
+            .build()?;
+
+        let mut http_connector = HttpConnector::new(NUM_THREADS);
+        http_connector.enforce_http(false);
+        Ok(ApiClient::Https {
+            client: Client::builder().build(HttpsConnector::from((http_connector, tls_connector))),
+            addr: addr.to_owned(),
+        })
+    }
+
+    // The path includes the query string
+    fn uri(&self, path: &str) -> SomaResult<Uri> {
+        let path = format!("/{}{}", API_VERSION, path);
+        Ok(match self {
+            #[cfg(unix)]
+            ApiClient::Unix { path: socket, .. } => hyperlocal::Uri::new(socket, &path).into(),
+            ApiClient::Http { addr, .. } => format!("http://{}{}", addr, path).parse()?,
+            ApiClient::Https { addr, .. } => format!("https://{}{}", addr, path).parse()?,
+        })
+    }
+
+    fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> EngineFuture<'static, Chunk> {
+        let request = self.uri(path).and_then(|uri| {
+            let body = match body {
+                Some(body) => Body::from(serde_json::to_vec(&body)?),
+                None => Body::empty(),
+            };
+            Ok(Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Content-Type", "application/json")
+                .body(body)?)
+        });
+        let request = match request {
+            Ok(request) => request,
+            Err(error) => return Box::new(future::err(error)),
+        };
+
+        let response = match self {
+            #[cfg(unix)]
+            ApiClient::Unix { client, .. } => client.request(request),
+            ApiClient::Http { client, .. } => client.request(request),
+            ApiClient::Https { client, .. } => client.request(request),
+        };
+        Box::new(response.from_err::<Error>().and_then(|response| {
+            let status = response.status();
+            response
+                .into_body()
+                .concat2()
+                .from_err::<Error>()
+                .and_then(move |body| {
+                    if status.is_success() {
+                        Ok(body)
+                    } else {
+                        let message = serde_json::from_slice::<ErrorResponse>(&body)
+                            .map(|error| error.message)
+                            .unwrap_or_default();
+                        Err(format_err!(
+                            "Docker responded with status code {}: {}",
+                            status.as_u16(),
+                            message
+                        ))
+                    }
+                })
+        }))
+    }
+
+    pub fn request<T: DeserializeOwned + 'static>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> EngineFuture<'static, T> {
+        Box::new(
+            self.send(method, path, body)
+                .and_then(|body| Ok(serde_json::from_slice(&body)?)),
+        )
+    }
+
+    pub fn request_unit(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> EngineFuture<'static, ()> {
+        Box::new(self.send(method, path, body).map(|_| ()))
+    }
+}
@@ -0,0 +1,212 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use bollard::Docker;
+use hyper::client::connect::Connect;
+
+use super::api::ApiClient;
+use crate::prelude::*;
+
+const DOCKER_HOST_ENV_NAME: &str = "DOCKER_HOST";
+const DOCKER_TLS_VERIFY_ENV_NAME: &str = "DOCKER_TLS_VERIFY";
+const DOCKER_CERT_PATH_ENV_NAME: &str = "DOCKER_CERT_PATH";
+
+const IDENTITY_FILE_NAME: &str = "identity.pfx";
+const CA_FILE_NAME: &str = "ca.pem";
+
+// Timeout in seconds, long enough to pull and build images
+const DOCKER_TIMEOUT: u64 = 600;
+const DOCKER_NUM_THREADS: usize = 1;
+
+#[derive(Debug, PartialEq)]
+pub enum DockerHost {
+    #[cfg(unix)]
+    Unix(String),
+    #[cfg(windows)]
+    NamedPipe(String),
+    // Addresses do not include the scheme
+    Http(String),
+    Https {
+        addr: String,
+        cert_path: PathBuf,
+    },
+}
+
+impl Default for DockerHost {
+    #[cfg(unix)]
+    fn default() -> Self {
+        DockerHost::Unix("/var/run/docker.sock".to_owned())
+    }
+
+    #[cfg(windows)]
+    fn default() -> Self {
+        DockerHost::NamedPipe("//./pipe/docker_engine".to_owned())
+    }
+}
+
+impl DockerHost {
+    // Follows the docker CLI convention of DOCKER_HOST, DOCKER_TLS_VERIFY and DOCKER_CERT_PATH
+    // The configured host is used only when neither the override nor DOCKER_HOST is given
+    pub fn from_env(
+        host_override: Option<&str>,
+        configured_host: Option<&str>,
+    ) -> SomaResult<DockerHost> {
+        let host = match host_override {
+            Some(host) => host.to_owned(),
+            None => match (env::var(DOCKER_HOST_ENV_NAME), configured_host) {
+                (Ok(host), _) if !host.is_empty() => host,
+                (_, Some(host)) => host.to_owned(),
+                _ => return Ok(DockerHost::default()),
+            },
+        };
+
+        let tls_verify = match env::var(DOCKER_TLS_VERIFY_ENV_NAME) {
+            Ok(value) => !value.is_empty() && value != "0",
+            Err(_) => false,
+        };
+        let cert_path = match env::var_os(DOCKER_CERT_PATH_ENV_NAME) {
+            Some(path) => PathBuf::from(path),
+            None => dirs::home_dir()
+                .ok_or(SomaError::InvalidDockerHost)?
+                .join(".docker"),
+        };
+
+        DockerHost::parse(&host, tls_verify, cert_path)
+    }
+
+    pub fn parse(host: &str, tls_verify: bool, cert_path: PathBuf) -> SomaResult<DockerHost> {
+        let split: Vec<_> = host.splitn(2, "://").collect();
+        let docker_host = match split.as_slice() {
+            #[cfg(unix)]
+            ["unix", path] if !path.is_empty() => DockerHost::Unix((*path).to_owned()),
+            #[cfg(windows)]
+            ["npipe", path] if !path.is_empty() => DockerHost::NamedPipe((*path).to_owned()),
+            ["tcp", addr] if !addr.is_empty() && tls_verify => DockerHost::Https {
+                addr: (*addr).to_owned(),
+                cert_path,
+            },
+            ["tcp", addr] | ["http", addr] if !addr.is_empty() => {
+                DockerHost::Http((*addr).to_owned())
+            }
+            ["https", addr] if !addr.is_empty() => DockerHost::Https {
+                addr: (*addr).to_owned(),
+                cert_path,
+            },
+            _ => Err(SomaError::InvalidDockerHost)?,
+        };
+
+        Ok(docker_host)
+    }
+}
+
+// Endpoints that bollard does not wrap are reached through a separate API client
+pub struct DockerConnection<C> {
+    pub(super) docker: Docker<C>,
+    pub(super) api: Option<ApiClient>,
+}
+
+#[cfg(unix)]
+pub fn connect_unix(path: &str) -> SomaResult<DockerConnection<impl Connect>> {
+    Ok(DockerConnection {
+        docker: Docker::connect_with_unix(path, DOCKER_TIMEOUT)?,
+        api: Some(ApiClient::unix(path)),
+    })
+}
+
+// Named pipes are only reachable through bollard, so networks are not available
+#[cfg(windows)]
+pub fn connect_named_pipe(path: &str) -> SomaResult<DockerConnection<impl Connect>> {
+    Ok(DockerConnection {
+        docker: Docker::connect_with_named_pipe(&format!("npipe://{}", path), DOCKER_TIMEOUT)?,
+        api: None,
+    })
+}
+
+pub fn connect_http(addr: &str) -> SomaResult<DockerConnection<impl Connect>> {
+    Ok(DockerConnection {
+        docker: Docker::connect_with_http(addr, DOCKER_NUM_THREADS, DOCKER_TIMEOUT)?,
+        api: Some(ApiClient::http(addr)),
+    })
+}
+
+// Client identity should be converted into PKCS #12 format without password
+pub fn connect_https(addr: &str, cert_path: &Path) -> SomaResult<DockerConnection<impl Connect>> {
+    let identity_path = cert_path.join(IDENTITY_FILE_NAME);
+    let ca_path = cert_path.join(CA_FILE_NAME);
+    // The docker CLI reads cert.pem and key.pem instead, which native-tls cannot load
+    if !identity_path.is_file() {
+        Err(SomaError::TlsIdentityNotFound)?;
+    }
+    if !ca_path.is_file() {
+        Err(SomaError::TlsCaNotFound)?;
+    }
+
+    Ok(DockerConnection {
+        docker: Docker::connect_with_tls(
+            addr,
+            &identity_path,
+            &ca_path,
+            "",
+            DOCKER_NUM_THREADS,
+            DOCKER_TIMEOUT,
+        )?,
+        api: Some(ApiClient::https(addr, &identity_path, &ca_path)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_docker_host() {
+        let cert_path = PathBuf::from("/certs");
+
+        #[cfg(unix)]
+        assert_eq!(
+            DockerHost::parse(
+                "unix:///run/user/1000/docker.sock",
+                false,
+                cert_path.clone()
+            )
+            .unwrap(),
+            DockerHost::Unix("/run/user/1000/docker.sock".to_owned())
+        );
+        assert_eq!(
+            DockerHost::parse("tcp://build-host:2375", false, cert_path.clone()).unwrap(),
+            DockerHost::Http("build-host:2375".to_owned())
+        );
+        assert_eq!(
+            DockerHost::parse("tcp://build-host:2376", true, cert_path.clone()).unwrap(),
+            DockerHost::Https {
+                addr: "build-host:2376".to_owned(),
+                cert_path: cert_path.clone(),
+            }
+        );
+        assert_eq!(
+            DockerHost::parse("https://build-host:2376", false, cert_path.clone()).unwrap(),
+            DockerHost::Https {
+                addr: "build-host:2376".to_owned(),
+                cert_path: cert_path.clone(),
+            }
+        );
+        assert!(DockerHost::parse("build-host:2375", false, cert_path.clone()).is_err());
+        assert!(DockerHost::parse("ssh://build-host", false, cert_path).is_err());
+    }
+
+    #[test]
+    fn test_connect_https_without_identity() {
+        let cert_dir = tempfile::tempdir().unwrap();
+        std::fs::write(cert_dir.path().join("cert.pem"), "").unwrap();
+        std::fs::write(cert_dir.path().join("key.pem"), "").unwrap();
+        std::fs::write(cert_dir.path().join(CA_FILE_NAME), "").unwrap();
+
+        let error = connect_https("build-host:2376", cert_dir.path())
+            .err()
+            .expect("Connecting without identity.pfx should fail");
+        match error.downcast_ref::<SomaError>() {
+            Some(SomaError::TlsIdentityNotFound) => (),
+            _ => panic!("Unexpected error: {}", error),
+        }
+    }
+}
@@ -0,0 +1,622 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use bollard::container::{
+    Config, CreateContainerOptions, DownloadFromContainerOptions, HostConfig,
+    ListContainersOptions, LogsOptions, PortBinding, PruneContainersOptions,
+    RemoveContainerOptions, RestartPolicy as DockerRestartPolicy, StartContainerOptions,
+    StopContainerOptions, WaitContainerOptions,
+};
+use bollard::exec::{CreateExecOptions, StartExecOptions, StartExecResults};
+use bollard::image::{
+    BuildImageOptions, CreateImageOptions, ListImagesOptions, PruneImagesOptions,
+    RemoveImageOptions,
+};
+use bollard::Docker;
+use failure::Error;
+use futures::future::{self, Either};
+use futures::{stream, Future, Stream};
+use hyper::client::connect::Connect;
+use hyper::{Body, Chunk, Method};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::api::ApiClient;
+use super::engine::{
+    BuildOptions, BuildOutput, ContainerEngine, ContainerOptions, ContainerSummary, EngineFuture,
+    EngineInfo, EngineKind, EngineStream, ExecOutput, ImageDetails, ImageSummary, NetworkOptions,
+    NetworkSummary, PortSummary, RestartPolicy,
+};
+use super::{DockerConnection, SomaFilter};
+use crate::prelude::*;
+
+const BUILD_CONTEXT_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct NetworkResponse {
+    id: String,
+    name: String,
+    labels: Option<HashMap<String, String>>,
+    internal: Option<bool>,
+}
+
+// Version response with the fields bollard does not parse
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VersionResponse {
+    version: String,
+    api_version: String,
+    platform: Option<VersionName>,
+    components: Option<Vec<VersionName>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VersionName {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CreateNetworkResponse {
+    id: String,
+}
+
+// The default engine which talks to a docker daemon or a Docker-compatible Podman service
+pub struct DockerEngine<C> {
+    docker: Docker<C>,
+    api: Option<ApiClient>,
+    kind: Cell<Option<EngineKind>>,
+}
+
+impl<C> DockerEngine<C>
+where
+    C: Connect + 'static,
+{
+    pub fn new(connection: DockerConnection<C>) -> DockerEngine<C> {
+        DockerEngine::with_kind(connection, None)
+    }
+
+    // A known kind skips detection of the engine behind the endpoint
+    pub fn with_kind(connection: DockerConnection<C>, kind: Option<EngineKind>) -> DockerEngine<C> {
+        DockerEngine {
+            docker: connection.docker,
+            api: connection.api,
+            kind: Cell::new(kind),
+        }
+    }
+
+    fn api(&self) -> SomaResult<&ApiClient> {
+        Ok(self.api.as_ref().ok_or(SomaError::NetworkUnsupported)?)
+    }
+
+    // The detected kind is cached in the engine
+    fn kind(&self) -> EngineFuture<'_, EngineKind> {
+        match self.kind.get() {
+            Some(kind) => Box::new(future::ok(kind)),
+            None => Box::new(self.info().map(|info| info.kind())),
+        }
+    }
+}
+
+impl<C> ContainerEngine for DockerEngine<C>
+where
+    C: Connect + 'static,
+{
+    // Queries the engine even if its kind is already known, to report the versions
+    // Endpoints without the API client, such as named pipes, are assumed to be Docker
+    fn info(&self) -> EngineFuture<'_, EngineInfo> {
+        let api = match self.api() {
+            Ok(api) => api,
+            Err(_) => {
+                return Box::new(self.docker.version().map(move |version| {
+                    let kind = self.kind.get().unwrap_or(EngineKind::Docker);
+                    self.kind.set(Some(kind));
+                    EngineInfo::new(kind, version.Version, version.ApiVersion)
+                }))
+            }
+        };
+
+        Box::new(
+            api.request::<VersionResponse>(Method::GET, "/version", None)
+                .map(move |version| {
+                    let kind = self.kind.get().unwrap_or_else(|| {
+                        let platform = version.platform.iter();
+                        let components = version.components.iter().flatten();
+                        EngineKind::from_version_names(
+                            platform.chain(components).map(|name| name.name.as_str()),
+                        )
+                    });
+                    self.kind.set(Some(kind));
+                    EngineInfo::new(kind, version.version, version.api_version)
+                }),
+        )
+    }
+
+    fn list_images(&self, filters: SomaFilter) -> EngineFuture<'_, Vec<ImageSummary>> {
+        Box::new(
+            self.docker
+                .list_images(Some(ListImagesOptions::<String> {
+                    filters,
+                    ..Default::default()
+                }))
+                .map(|images| {
+                    images
+                        .into_iter()
+                        .map(|image| ImageSummary {
+                            id: image.id,
+                            repo_tags: image.repo_tags.unwrap_or_default(),
+                            labels: image.labels.unwrap_or_default(),
+                            size: image.size,
+                        })
+                        .collect()
+                }),
+        )
+    }
+
+    fn inspect_image(&self, image_name: &str) -> EngineFuture<'_, ImageDetails> {
+        Box::new(
+            self.docker
+                .inspect_image(image_name)
+                .map(|image| ImageDetails {
+                    repo_digests: image.repo_digests,
+                    config: image.config,
+                }),
+        )
+    }
+
+    fn build_image<'a>(
+        &'a self,
+        options: BuildOptions<'a>,
+        build_context: File,
+    ) -> EngineStream<'a, BuildOutput> {
+        let build_options = BuildImageOptions {
+            dockerfile: options.dockerfile,
+            t: options.image_name,
+            pull: options.pull,
+            forcerm: true,
+            labels: options.labels,
+            ..Default::default()
+        };
+
+        let build_stream = self
+            .docker
+            .build_image(build_options, None, Some(file_body(build_context)))
+            .filter_map(|build_image_result| {
+                use bollard::image::BuildImageResults::*;
+                match build_image_result {
+                    BuildImageStream { stream } => Some(BuildOutput::Stream(stream)),
+                    BuildImageStatus {
+                        id,
+                        status,
+                        progress,
+                        ..
+                    } => Some(BuildOutput::Status {
+                        id,
+                        status,
+                        progress,
+                    }),
+                    BuildImageError { error, .. } => Some(BuildOutput::Error(error)),
+                    _ => None,
+                }
+            });
+
+        // Podman reports build failures in messages that bollard cannot parse
+        Box::new(
+            self.kind()
+                .map(move |kind| {
+                    build_stream.or_else(move |error| {
+                        if kind == EngineKind::Podman && error.downcast_ref::<SomaError>().is_none()
+                        {
+                            Ok(BuildOutput::Error(error.to_string()))
+                        } else {
+                            Err(error)
+                        }
+                    })
+                })
+                .flatten_stream(),
+        )
+    }
+
+    fn pull_image<'a>(&'a self, image_name: &'a str) -> EngineStream<'a, BuildOutput> {
+        let (from_image, tag) = split_image_reference(image_name);
+        let create_options = CreateImageOptions {
+            from_image,
+            tag,
+            ..Default::default()
+        };
+
+        Box::new(
+            self.docker
+                .create_image(Some(create_options), None)
+                .filter_map(|create_image_result| {
+                    use bollard::image::CreateImageResults::*;
+                    match create_image_result {
+                        CreateImageProgressResponse {
+                            id,
+                            status,
+                            progress,
+                            ..
+                        } => Some(BuildOutput::Status {
+                            id,
+                            status,
+                            progress,
+                        }),
+                        CreateImageError { error, .. } => Some(BuildOutput::Error(error)),
+                    }
+                }),
+        )
+    }
+
+    fn remove_image(&self, image_name: &str) -> EngineFuture<'_, ()> {
+        Box::new(
+            self.docker
+                .remove_image(image_name, None::<RemoveImageOptions>, None)
+                .map(|_| ()),
+        )
+    }
+
+    fn prune_images(&self, filters: SomaFilter) -> EngineFuture<'_, ()> {
+        Box::new(self.kind().and_then(move |kind| {
+            match kind {
+                EngineKind::Docker => Either::A(
+                    self.docker
+                        .prune_images(Some(PruneImagesOptions { filters }))
+                        .map(|_| ()),
+                ),
+                // Podman may ignore label filters on prune endpoints and prune unrelated images
+                EngineKind::Podman => {
+                    let mut filters = filters;
+                    filters.insert("dangling".to_owned(), vec!["true".to_owned()]);
+                    Either::B(
+                        self.list_images(filters)
+                            .and_then(move |images| {
+                                future::join_all(images.into_iter().map(move |image| {
+                                    self.docker.remove_image(
+                                        &image.id,
+                                        None::<RemoveImageOptions>,
+                                        None,
+                                    )
+                                }))
+                            })
+                            .map(|_| ()),
+                    )
+                }
+            }
+        }))
+    }
+
+    fn list_containers(&self, filters: SomaFilter) -> EngineFuture<'_, Vec<ContainerSummary>> {
+        Box::new(
+            self.docker
+                .list_containers(Some(ListContainersOptions::<String> {
+                    all: true,
+                    filters,
+                    ..Default::default()
+                }))
+                .map(|containers| {
+                    containers
+                        .into_iter()
+                        .map(|container| ContainerSummary {
+                            id: container.id,
+                            image: container.image,
+                            image_id: container.image_id,
+                            state: container.state,
+                            labels: container.labels,
+                            ports: container
+                                .ports
+                                .into_iter()
+                                .map(|port| PortSummary {
+                                    ip: port.ip,
+                                    private_port: port.private_port,
+                                    public_port: port.public_port,
+                                })
+                                .collect(),
+                            networks: container
+                                .network_settings
+                                .networks
+                                .into_iter()
+                                .map(|(name, _)| name)
+                                .collect(),
+                        })
+                        .collect()
+                }),
+        )
+    }
+
+    fn create_container<'a>(&'a self, options: ContainerOptions<'a>) -> EngineFuture<'a, String> {
+        let port_bindings = if options.port_bindings.is_empty() {
+            None
+        } else {
+            Some(
+                options
+                    .port_bindings
+                    .into_iter()
+                    .map(|(container_port, host_ip, host_port)| {
+                        (container_port, vec![PortBinding { host_ip, host_port }])
+                    })
+                    .collect::<HashMap<_, _>>(),
+            )
+        };
+        let restart_policy = match options.restart_policy {
+            RestartPolicy::No => None,
+            restart_policy => Some(DockerRestartPolicy {
+                name: Some(restart_policy.as_str()),
+                maximum_retry_count: None,
+            }),
+        };
+        let host_config =
+            if port_bindings.is_none() && options.network.is_none() && restart_policy.is_none() {
+                None
+            } else {
+                Some(HostConfig {
+                    port_bindings,
+                    network_mode: options.network,
+                    restart_policy,
+                    ..Default::default()
+                })
+            };
+
+        Box::new(
+            self.docker
+                .create_container(
+                    None::<CreateContainerOptions<String>>,
+                    Config {
+                        image: Some(options.image_name),
+                        labels: Some(options.labels),
+                        entrypoint: options.entrypoint,
+                        cmd: options.cmd,
+                        host_config,
+                        ..Default::default()
+                    },
+                )
+                .map(|container_results| container_results.id),
+        )
+    }
+
+    fn start_container(&self, container_id: &str) -> EngineFuture<'_, ()> {
+        Box::new(
+            self.docker
+                .start_container(container_id, None::<StartContainerOptions<String>>),
+        )
+    }
+
+    fn stop_container(&self, container_id: &str) -> EngineFuture<'_, ()> {
+        Box::new(
+            self.docker
+                .stop_container(container_id, None::<StopContainerOptions>),
+        )
+    }
+
+    fn wait_container(&self, container_id: &str) -> EngineFuture<'_, i64> {
+        Box::new(
+            self.docker
+                .wait_container(container_id, None::<WaitContainerOptions<String>>)
+                .fold(0, |_, wait_result| {
+                    Ok::<_, Error>(wait_result.status_code as i64)
+                }),
+        )
+    }
+
+    fn container_logs(&self, container_id: &str) -> EngineFuture<'_, String> {
+        Box::new(
+            self.docker
+                .logs(
+                    container_id,
+                    Some(LogsOptions {
+                        stdout: true,
+                        stderr: true,
+                        ..Default::default()
+                    }),
+                )
+                .fold(String::new(), |mut output, log| {
+                    output.push_str(&log.to_string());
+                    Ok::<_, Error>(output)
+                }),
+        )
+    }
+
+    fn exec_container<'a>(
+        &'a self,
+        container_id: &'a str,
+        cmd: Vec<&'a str>,
+    ) -> EngineFuture<'a, ExecOutput> {
+        let options = CreateExecOptions {
+            cmd: Some(cmd),
+            attach_stdout: Some(true),
+            attach_stderr: Some(true),
+            ..Default::default()
+        };
+
+        Box::new(
+            self.docker
+                .create_exec(container_id, options)
+                .and_then(move |exec| {
+                    self.docker
+                        .start_exec(&exec.id, None::<StartExecOptions>)
+                        .fold(String::new(), |mut output, result| {
+                            if let StartExecResults::Attached { log } = result {
+                                output.push_str(&log.to_string());
+                            }
+                            Ok::<_, Error>(output)
+                        })
+                        .and_then(move |output| {
+                            self.docker
+                                .inspect_exec(&exec.id)
+                                .map(|inspect| ExecOutput {
+                                    exit_code: inspect.exit_code.unwrap_or_default() as i64,
+                                    output,
+                                })
+                        })
+                }),
+        )
+    }
+
+    fn download_from_container(
+        &self,
+        container_id: &str,
+        path: &Path,
+    ) -> EngineStream<'_, Vec<u8>> {
+        Box::new(
+            self.docker
+                .download_from_container(
+                    container_id,
+                    Some(DownloadFromContainerOptions {
+                        path: path.to_string_lossy().into_owned(),
+                    }),
+                )
+                .map(|chunk| chunk.to_vec()),
+        )
+    }
+
+    fn remove_container(&self, container_id: &str) -> EngineFuture<'_, ()> {
+        Box::new(
+            self.docker
+                .remove_container(container_id, None::<RemoveContainerOptions>),
+        )
+    }
+
+    fn prune_containers(&self, filters: SomaFilter) -> EngineFuture<'_, ()> {
+        Box::new(self.kind().and_then(move |kind| {
+            match kind {
+                EngineKind::Docker => Either::A(
+                    self.docker
+                        .prune_containers(Some(PruneContainersOptions { filters }))
+                        .map(|_| ()),
+                ),
+                // Podman may ignore label filters on prune endpoints and prune unrelated containers
+                EngineKind::Podman => {
+                    let mut filters = filters;
+                    filters.insert(
+                        "status".to_owned(),
+                        vec!["created".to_owned(), "exited".to_owned(), "dead".to_owned()],
+                    );
+                    Either::B(
+                        self.list_containers(filters)
+                            .and_then(move |containers| {
+                                future::join_all(containers.into_iter().map(move |container| {
+                                    self.docker.remove_container(
+                                        &container.id,
+                                        None::<RemoveContainerOptions>,
+                                    )
+                                }))
+                            })
+                            .map(|_| ()),
+                    )
+                }
+            }
+        }))
+    }
+
+    fn list_networks(&self, filters: SomaFilter) -> EngineFuture<'_, Vec<NetworkSummary>> {
+        let path = format!(
+            "/networks?filters={}",
+            url::form_urlencoded::byte_serialize(json!(filters).to_string().as_bytes())
+                .collect::<String>()
+        );
+        // Endpoints without network support cannot have any network to list
+        let networks = match &self.api {
+            Some(api) => api.request::<Vec<NetworkResponse>>(Method::GET, &path, None),
+            None => return Box::new(future::ok(vec![])),
+        };
+
+        Box::new(networks.map(|networks| {
+            networks
+                .into_iter()
+                .map(|network| NetworkSummary {
+                    id: network.id,
+                    name: network.name,
+                    labels: network.labels.unwrap_or_default(),
+                    internal: network.internal.unwrap_or(false),
+                })
+                .collect()
+        }))
+    }
+
+    fn create_network<'a>(&'a self, options: NetworkOptions<'a>) -> EngineFuture<'a, String> {
+        let body = json!({
+            "Name": options.name,
+            "CheckDuplicate": true,
+            "Driver": "bridge",
+            "Internal": options.internal,
+            "Labels": options.labels,
+        });
+
+        match self.api() {
+            Ok(api) => Box::new(
+                api.request::<CreateNetworkResponse>(Method::POST, "/networks/create", Some(body))
+                    .map(|network| network.id),
+            ),
+            Err(error) => Box::new(future::err(error)),
+        }
+    }
+
+    fn connect_network(&self, network_id: &str, container_id: &str) -> EngineFuture<'_, ()> {
+        match self.api() {
+            Ok(api) => api.request_unit(
+                Method::POST,
+                &format!("/networks/{}/connect", network_id),
+                Some(json!({ "Container": container_id })),
+            ),
+            Err(error) => Box::new(future::err(error)),
+        }
+    }
+
+    fn remove_network(&self, network_id: &str) -> EngineFuture<'_, ()> {
+        match self.api() {
+            Ok(api) => api.request_unit(Method::DELETE, &format!("/networks/{}", network_id), None),
+            Err(error) => Box::new(future::err(error)),
+        }
+    }
+}
+
+// Pulling without a tag pulls every tag of the repository, so latest is made explicit
+fn split_image_reference(image_name: &str) -> (&str, &str) {
+    if let Some(at) = image_name.find('@') {
+        return (&image_name[..at], &image_name[at + 1..]);
+    }
+    match image_name.rfind(':') {
+        Some(colon) if !image_name[colon..].contains('/') => {
+            (&image_name[..colon], &image_name[colon + 1..])
+        }
+        _ => (image_name, "latest"),
+    }
+}
+
+// Sends the file in chunks, since build contexts can hold whole problem filesystems
+fn file_body(file: File) -> Body {
+    Body::wrap_stream(stream::unfold(file, |mut file| {
+        let mut buffer = vec![0; BUILD_CONTEXT_CHUNK_SIZE];
+        match file.read(&mut buffer) {
+            Ok(0) => None,
+            Ok(length) => {
+                buffer.truncate(length);
+                Some(Ok((Chunk::from(buffer), file)))
+            }
+            Err(error) => Some(Err(error)),
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_image_reference() {
+        assert_eq!(split_image_reference("ubuntu"), ("ubuntu", "latest"));
+        assert_eq!(split_image_reference("ubuntu:16.04"), ("ubuntu", "16.04"));
+        assert_eq!(
+            split_image_reference("localhost:5000/ubuntu"),
+            ("localhost:5000/ubuntu", "latest")
+        );
+        assert_eq!(
+            split_image_reference("ubuntu@sha256:0123"),
+            ("ubuntu", "sha256:0123")
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+
+use bollard::container::Config;
+use failure::Error;
+use futures::{Future, Stream};
+use serde::{Deserialize, Serialize};
+
+use super::{DockerLabel, SomaFilter};
+use crate::prelude::*;
+use crate::{Environment, Printer};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EngineKind {
+    Docker,
+    Podman,
+}
+
+impl EngineKind {
+    // Podman names itself in the platform or the components of the version response,
+    // while older Docker engines report neither of them
+    pub fn from_version_names<'a>(names: impl IntoIterator<Item = &'a str>) -> EngineKind {
+        if names
+            .into_iter()
+            .any(|name| name.to_lowercase().contains("podman"))
+        {
+            EngineKind::Podman
+        } else {
+            EngineKind::Docker
+        }
+    }
+
+    // Podman sockets are conventionally placed under a podman directory
+    pub fn from_socket_path(path: &str) -> Option<EngineKind> {
+        if path.contains("podman") {
+            Some(EngineKind::Podman)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for EngineKind {
+    type Err = SomaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "docker" => Ok(EngineKind::Docker),
+            "podman" => Ok(EngineKind::Podman),
+            _ => Err(SomaError::UnknownEngine),
+        }
+    }
+}
+
+impl Display for EngineKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineKind::Docker => write!(f, "Docker"),
+            EngineKind::Podman => write!(f, "Podman"),
+        }
+    }
+}
+
+// Whether the engine starts the container again after it exits or the host reboots
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    No,
+    OnFailure,
+    UnlessStopped,
+}
+
+impl FromStr for RestartPolicy {
+    type Err = SomaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "no" => Ok(RestartPolicy::No),
+            "on-failure" => Ok(RestartPolicy::OnFailure),
+            "unless-stopped" => Ok(RestartPolicy::UnlessStopped),
+            _ => Err(SomaError::UnknownRestartPolicy),
+        }
+    }
+}
+
+impl RestartPolicy {
+    // Uses the names docker accepts in the host config
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RestartPolicy::No => "no",
+            RestartPolicy::OnFailure => "on-failure",
+            RestartPolicy::UnlessStopped => "unless-stopped",
+        }
+    }
+}
+
+impl Display for RestartPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+pub type EngineFuture<'a, T> = Box<dyn Future<Item = T, Error = Error> + 'a>;
+pub type EngineStream<'a, T> = Box<dyn Stream<Item = T, Error = Error> + 'a>;
+
+// Operations soma requires from a container engine, described with engine-neutral types
+pub trait ContainerEngine {
+    fn info(&self) -> EngineFuture<'_, EngineInfo>;
+
+    fn list_images(&self, filters: SomaFilter) -> EngineFuture<'_, Vec<ImageSummary>>;
+    fn inspect_image(&self, image_name: &str) -> EngineFuture<'_, ImageDetails>;
+    // Build contexts are read from a file, which is a gzipped tar archive
+    fn build_image<'a>(
+        &'a self,
+        options: BuildOptions<'a>,
+        build_context: File,
+    ) -> EngineStream<'a, BuildOutput>;
+    // Pulls the image from its registry, reporting progress like a build
+    fn pull_image<'a>(&'a self, image_name: &'a str) -> EngineStream<'a, BuildOutput>;
+    fn remove_image(&self, image_name: &str) -> EngineFuture<'_, ()>;
+    // Removes dangling images matching the filters
+    fn prune_images(&self, filters: SomaFilter) -> EngineFuture<'_, ()>;
+
+    fn list_containers(&self, filters: SomaFilter) -> EngineFuture<'_, Vec<ContainerSummary>>;
+    fn create_container<'a>(&'a self, options: ContainerOptions<'a>) -> EngineFuture<'a, String>;
+    fn start_container(&self, container_id: &str) -> EngineFuture<'_, ()>;
+    fn stop_container(&self, container_id: &str) -> EngineFuture<'_, ()>;
+    // Waits until the container exits and returns its exit code
+    fn wait_container(&self, container_id: &str) -> EngineFuture<'_, i64>;
+    fn container_logs(&self, container_id: &str) -> EngineFuture<'_, String>;
+    // Runs a command in the running container until it exits
+    fn exec_container<'a>(
+        &'a self,
+        container_id: &'a str,
+        cmd: Vec<&'a str>,
+    ) -> EngineFuture<'a, ExecOutput>;
+    // Streams a tar archive of the path inside of the container in chunks
+    fn download_from_container(&self, container_id: &str, path: &Path)
+        -> EngineStream<'_, Vec<u8>>;
+    fn remove_container(&self, container_id: &str) -> EngineFuture<'_, ()>;
+    // Removes stopped containers matching the filters
+    fn prune_containers(&self, filters: SomaFilter) -> EngineFuture<'_, ()>;
+
+    fn list_networks(&self, filters: SomaFilter) -> EngineFuture<'_, Vec<NetworkSummary>>;
+    fn create_network<'a>(&'a self, options: NetworkOptions<'a>) -> EngineFuture<'a, String>;
+    // Attaches the container to the network in addition to the networks it is already on
+    fn connect_network(&self, network_id: &str, container_id: &str) -> EngineFuture<'_, ()>;
+    fn remove_network(&self, network_id: &str) -> EngineFuture<'_, ()>;
+}
+
+#[derive(Clone, Debug)]
+pub struct ImageSummary {
+    pub id: String,
+    pub repo_tags: Vec<String>,
+    pub labels: HashMap<String, String>,
+    // Bytes on disk, including layers shared with other images
+    pub size: u64,
+}
+
+#[derive(Debug)]
+pub struct ImageDetails {
+    pub repo_digests: Vec<String>,
+    pub config: Config<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ContainerSummary {
+    pub id: String,
+    pub image: String,
+    pub image_id: String,
+    pub state: String,
+    pub labels: HashMap<String, String>,
+    pub ports: Vec<PortSummary>,
+    // Names of the networks the container is attached to
+    pub networks: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PortSummary {
+    pub ip: Option<String>,
+    pub private_port: i64,
+    pub public_port: Option<i64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExecOutput {
+    pub exit_code: i64,
+    // Standard output and standard error in the order they were written
+    pub output: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct NetworkSummary {
+    pub id: String,
+    pub name: String,
+    pub labels: HashMap<String, String>,
+    // Internal networks have no route outside of the host
+    pub internal: bool,
+}
+
+pub struct BuildOptions<'a> {
+    pub image_name: &'a str,
+    pub dockerfile: &'a str,
+    pub pull: bool,
+    pub labels: DockerLabel<'a>,
+}
+
+#[derive(Debug)]
+pub enum BuildOutput {
+    Stream(String),
+    // Progress of pulling a base image layer
+    Status {
+        id: Option<String>,
+        status: String,
+        progress: Option<String>,
+    },
+    Error(String),
+}
+
+#[derive(Default)]
+pub struct ContainerOptions<'a> {
+    pub image_name: &'a str,
+    pub labels: DockerLabel<'a>,
+    pub entrypoint: Option<Vec<&'a str>>,
+    pub cmd: Option<Vec<&'a str>>,
+    // Triples of a container port such as 1337/tcp, a host address, and a host port
+    pub port_bindings: Vec<(&'a str, &'a str, &'a str)>,
+    // Attaches the container to the network instead of the default bridge
+    pub network: Option<&'a str>,
+    pub restart_policy: RestartPolicy,
+}
+
+pub struct NetworkOptions<'a> {
+    pub name: &'a str,
+    pub labels: DockerLabel<'a>,
+    pub internal: bool,
+}
+
+#[derive(Debug)]
+pub struct EngineInfo {
+    kind: EngineKind,
+    version: String,
+    api_version: String,
+}
+
+impl EngineInfo {
+    pub fn new(kind: EngineKind, version: String, api_version: String) -> EngineInfo {
+        EngineInfo {
+            kind,
+            version,
+            api_version,
+        }
+    }
+
+    pub fn kind(&self) -> EngineKind {
+        self.kind
+    }
+
+    pub fn version(&self) -> &String {
+        &self.version
+    }
+
+    pub fn api_version(&self) -> &String {
+        &self.api_version
+    }
+}
+
+pub fn engine_info<'a>(
+    env: &'a Environment<impl ContainerEngine, impl Printer>,
+) -> impl Future<Item = EngineInfo, Error = Error> + 'a {
+    env.engine().info()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_policy_names() {
+        for policy in &[
+            RestartPolicy::No,
+            RestartPolicy::OnFailure,
+            RestartPolicy::UnlessStopped,
+        ] {
+            assert_eq!(
+                policy.to_string().parse::<RestartPolicy>().unwrap(),
+                *policy
+            );
+        }
+        assert!("always".parse::<RestartPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_engine_kind_detection() {
+        assert_eq!(
+            EngineKind::from_version_names(vec!["Docker Engine - Community", "Engine"]),
+            EngineKind::Docker
+        );
+        assert_eq!(
+            EngineKind::from_version_names(vec!["linux/amd64/fedora-38", "Podman Engine"]),
+            EngineKind::Podman
+        );
+        // Docker 1.13 reports neither a platform nor components
+        assert_eq!(EngineKind::from_version_names(vec![]), EngineKind::Docker);
+        assert_eq!(
+            EngineKind::from_socket_path("/run/user/1000/podman/podman.sock"),
+            Some(EngineKind::Podman)
+        );
+        assert_eq!(EngineKind::from_socket_path("/var/run/docker.sock"), None);
+        assert_eq!("Podman".parse::<EngineKind>().unwrap(), EngineKind::Podman);
+    }
+}
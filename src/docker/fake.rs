@@ -0,0 +1,748 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use bollard::container::Config;
+use flate2::read::GzDecoder;
+use futures::{future, stream};
+
+use super::engine::{
+    BuildOptions, BuildOutput, ContainerEngine, ContainerOptions, ContainerSummary, EngineFuture,
+    EngineInfo, EngineKind, EngineStream, ExecOutput, ImageDetails, ImageSummary, NetworkOptions,
+    NetworkSummary, PortSummary,
+};
+use super::{SomaFilter, UNTAGGED_REPO_TAG};
+use crate::prelude::*;
+
+// An in-memory engine that keeps track of images, containers, and networks
+// without running anything, so that operations can be tested without a docker daemon
+//
+// Base images are pulled instantly, containers exit with code 0 without any output,
+// and commands in them succeed unless they end with `false`. Containers of images whose
+// CMD ends with `false` exit as soon as they start. Container filesystems are
+// always empty, pulled images take no space, and built images are as large as their
+// build context.
+#[derive(Default)]
+pub struct FakeEngine {
+    state: RefCell<FakeState>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    next_id: u64,
+    images: Vec<FakeImage>,
+    containers: Vec<FakeContainer>,
+    networks: Vec<NetworkSummary>,
+}
+
+struct FakeImage {
+    id: String,
+    parent_id: Option<String>,
+    repo_tags: Vec<String>,
+    repo_digests: Vec<String>,
+    labels: HashMap<String, String>,
+    size: u64,
+    crashes: bool,
+}
+
+struct FakeContainer {
+    id: String,
+    image_id: String,
+    state: String,
+    labels: HashMap<String, String>,
+    ports: Vec<PortSummary>,
+    networks: Vec<String>,
+}
+
+impl FakeEngine {
+    pub fn new() -> FakeEngine {
+        FakeEngine::default()
+    }
+}
+
+impl FakeState {
+    // Spreads sequential numbers over the digits so that shortened ids stay distinct
+    fn next_id(&mut self) -> String {
+        self.next_id += 1;
+        format!("{:016x}", self.next_id.wrapping_mul(0x9e37_79b9_7f4a_7c15)).repeat(4)
+    }
+
+    fn find_image(&self, image_name: &str) -> Option<usize> {
+        let tag = normalize_tag(image_name);
+        self.images
+            .iter()
+            .position(|image| image.id == image_name || image.repo_tags.contains(&tag))
+    }
+
+    fn find_container(&self, container_id: &str) -> SomaResult<usize> {
+        match self
+            .containers
+            .iter()
+            .position(|container| container.id == container_id)
+        {
+            Some(index) => Ok(index),
+            None => Err(SomaError::EngineRequestFailed)?,
+        }
+    }
+
+    fn find_network(&self, network_id: &str) -> SomaResult<usize> {
+        match self
+            .networks
+            .iter()
+            .position(|network| network.id == network_id || network.name == network_id)
+        {
+            Some(index) => Ok(index),
+            None => Err(SomaError::EngineRequestFailed)?,
+        }
+    }
+
+    // Pulling an image which already exists is a no-op
+    fn pull(&mut self, image_name: &str) -> usize {
+        if let Some(index) = self.find_image(image_name) {
+            return index;
+        }
+
+        let id = self.next_id();
+        let repository = image_name.split('@').next().unwrap_or(image_name);
+        let repository = match repository.rfind(':') {
+            Some(colon) if !repository[colon..].contains('/') => &repository[..colon],
+            _ => repository,
+        };
+        let repo_digests = if image_name.contains('@') {
+            vec![image_name.to_owned()]
+        } else {
+            vec![format!("{}@sha256:{}", repository, id)]
+        };
+        let repo_tags = if image_name.contains('@') {
+            vec![]
+        } else {
+            vec![normalize_tag(image_name)]
+        };
+
+        self.images.push(FakeImage {
+            id: format!("sha256:{}", id),
+            parent_id: None,
+            repo_tags,
+            repo_digests,
+            labels: HashMap::new(),
+            size: 0,
+            crashes: false,
+        });
+        self.images.len() - 1
+    }
+
+    fn build(&mut self, options: BuildOptions, build_context: &[u8]) -> SomaResult<Vec<String>> {
+        let dockerfile = read_dockerfile(build_context, options.dockerfile)?;
+        let mut output = vec![];
+
+        // Only the base of the last stage becomes the parent of the result
+        let mut stages = HashSet::new();
+        let mut base = None;
+        let instructions = parse_instructions(&dockerfile);
+        for (step, instruction) in instructions.iter().enumerate() {
+            output.push(format!(
+                "Step {}/{} : {}",
+                step + 1,
+                instructions.len(),
+                instruction
+            ));
+
+            let words: Vec<_> = instruction.split_whitespace().collect();
+            if words[0].eq_ignore_ascii_case("FROM") && words.len() >= 2 {
+                base = if stages.contains(words[1]) {
+                    Some((None, HashMap::new(), false))
+                } else {
+                    let index = self.pull(words[1]);
+                    let image = &self.images[index];
+                    Some((Some(image.id.clone()), image.labels.clone(), image.crashes))
+                };
+                if words.len() >= 4 && words[2].eq_ignore_ascii_case("AS") {
+                    stages.insert(words[3].to_owned());
+                }
+            } else if words[0].eq_ignore_ascii_case("CMD") {
+                if let Some((_, _, crashes)) = &mut base {
+                    *crashes = instruction
+                        .trim_end_matches(&['"', ']'][..])
+                        .ends_with("false");
+                }
+            }
+        }
+        let (parent_id, mut labels, crashes) = base.ok_or(SomaError::EngineRequestFailed)?;
+
+        // The previous image with the same tag becomes dangling
+        let tag = normalize_tag(options.image_name);
+        for image in &mut self.images {
+            image.repo_tags.retain(|repo_tag| repo_tag != &tag);
+        }
+
+        let id = format!("sha256:{}", self.next_id());
+        output.push(format!("Successfully built {}", &id[7..19]));
+        output.push(format!("Successfully tagged {}", tag));
+
+        labels.extend(
+            options
+                .labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string())),
+        );
+        self.images.push(FakeImage {
+            id,
+            parent_id,
+            repo_tags: vec![tag],
+            repo_digests: vec![],
+            labels,
+            size: build_context.len() as u64,
+            crashes,
+        });
+        Ok(output)
+    }
+
+    fn create_container(&mut self, options: ContainerOptions) -> SomaResult<String> {
+        let image_id = match self.find_image(options.image_name) {
+            Some(index) => self.images[index].id.clone(),
+            None => Err(SomaError::EngineRequestFailed)?,
+        };
+
+        let ports = options
+            .port_bindings
+            .iter()
+            .map(|(container_port, host_ip, host_port)| {
+                let private_port = container_port.split('/').next().unwrap_or(container_port);
+                let host_ip = if host_ip.is_empty() {
+                    "0.0.0.0"
+                } else {
+                    host_ip
+                };
+                match (private_port.parse(), host_port.parse()) {
+                    (Ok(private_port), Ok(public_port)) => Ok(PortSummary {
+                        ip: Some(host_ip.to_owned()),
+                        private_port,
+                        public_port: Some(public_port),
+                    }),
+                    _ => Err(SomaError::EngineRequestFailed),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let network = match options.network {
+            Some(network) => {
+                let index = self.find_network(network)?;
+                // Ports published from internal networks are unreachable
+                if self.networks[index].internal && !ports.is_empty() {
+                    Err(SomaError::EngineRequestFailed)?;
+                }
+                self.networks[index].name.clone()
+            }
+            None => "bridge".to_owned(),
+        };
+
+        let id = self.next_id();
+        self.containers.push(FakeContainer {
+            id: id.clone(),
+            image_id,
+            state: "created".to_owned(),
+            labels: options
+                .labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            ports,
+            networks: vec![network],
+        });
+        Ok(id)
+    }
+
+    fn create_network(&mut self, options: NetworkOptions) -> SomaResult<String> {
+        if self.find_network(options.name).is_ok() {
+            Err(SomaError::EngineRequestFailed)?;
+        }
+
+        let id = self.next_id();
+        self.networks.push(NetworkSummary {
+            id: id.clone(),
+            name: options.name.to_owned(),
+            labels: options
+                .labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            internal: options.internal,
+        });
+        Ok(id)
+    }
+
+    fn connect_network(&mut self, network_id: &str, container_id: &str) -> SomaResult<()> {
+        let network_index = self.find_network(network_id)?;
+        let container_index = self.find_container(container_id)?;
+        let name = self.networks[network_index].name.clone();
+        let networks = &mut self.containers[container_index].networks;
+        if networks.contains(&name) {
+            Err(SomaError::EngineRequestFailed)?;
+        }
+        networks.push(name);
+        Ok(())
+    }
+
+    // Networks with running containers have active endpoints and cannot be removed
+    fn remove_network(&mut self, network_id: &str) -> SomaResult<()> {
+        let index = self.find_network(network_id)?;
+        let name = &self.networks[index].name;
+        if self
+            .containers
+            .iter()
+            .any(|container| container.networks.contains(name) && container.state == "running")
+        {
+            Err(SomaError::EngineRequestFailed)?;
+        }
+        self.networks.remove(index);
+        Ok(())
+    }
+
+    // Host ports cannot be shared among running containers
+    fn start_container(&mut self, container_id: &str) -> SomaResult<()> {
+        let index = self.find_container(container_id)?;
+        let public_ports: Vec<_> = self.containers[index]
+            .ports
+            .iter()
+            .filter_map(|port| port.public_port)
+            .collect();
+        let port_in_use = self.containers.iter().any(|container| {
+            container.id != container_id
+                && container.state == "running"
+                && container.ports.iter().any(|port| {
+                    port.public_port
+                        .map_or(false, |p| public_ports.contains(&p))
+                })
+        });
+        if port_in_use {
+            Err(SomaError::EngineRequestFailed)?;
+        }
+
+        let crashes = self
+            .images
+            .iter()
+            .any(|image| image.id == self.containers[index].image_id && image.crashes);
+        self.containers[index].state = if crashes { "exited" } else { "running" }.to_owned();
+        Ok(())
+    }
+
+    // Docker rejects stopping a container which is not running
+    fn stop_container(&mut self, container_id: &str) -> SomaResult<()> {
+        let index = self.find_container(container_id)?;
+        if self.containers[index].state != "running" {
+            Err(SomaError::EngineRequestFailed)?;
+        }
+        self.exit_container(container_id)
+    }
+
+    fn exit_container(&mut self, container_id: &str) -> SomaResult<()> {
+        let index = self.find_container(container_id)?;
+        self.containers[index].state = "exited".to_owned();
+        Ok(())
+    }
+
+    fn has_children(&self, image_id: &str) -> bool {
+        self.images
+            .iter()
+            .any(|image| image.parent_id.as_deref() == Some(image_id))
+    }
+
+    // Intermediate images are untagged parents, which are hidden from listings like docker does
+    fn is_intermediate(&self, image: &FakeImage) -> bool {
+        image.repo_tags.is_empty() && self.has_children(&image.id)
+    }
+
+    // Tagged references only remove the tag while other tags or child images remain,
+    // and untagged parents are removed along with their last child
+    fn remove_image(&mut self, image_name: &str) -> SomaResult<()> {
+        let index = match self.find_image(image_name) {
+            Some(index) => index,
+            None => Err(SomaError::EngineRequestFailed)?,
+        };
+
+        let tag = normalize_tag(image_name);
+        let image_id = self.images[index].id.clone();
+        let has_children = self.has_children(&image_id);
+        let image = &mut self.images[index];
+        if image.repo_tags.contains(&tag) && (image.repo_tags.len() > 1 || has_children) {
+            image.repo_tags.retain(|repo_tag| repo_tag != &tag);
+            return Ok(());
+        }
+
+        if has_children
+            || self
+                .containers
+                .iter()
+                .any(|container| container.image_id == image_id)
+        {
+            Err(SomaError::EngineRequestFailed)?;
+        }
+        let image = self.images.remove(index);
+
+        if let Some(parent_id) = image.parent_id {
+            let parent_unused = self
+                .find_image(&parent_id)
+                .map(|parent| self.images[parent].repo_tags.is_empty())
+                .unwrap_or(false)
+                && !self.has_children(&parent_id);
+            if parent_unused {
+                self.remove_image(&parent_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_container(&mut self, container_id: &str) -> SomaResult<()> {
+        let index = self.find_container(container_id)?;
+        if self.containers[index].state == "running" {
+            Err(SomaError::EngineRequestFailed)?;
+        }
+        self.containers.remove(index);
+        Ok(())
+    }
+
+    fn image_summaries(&self, filters: &SomaFilter) -> Vec<ImageSummary> {
+        let dangling = filters
+            .get("dangling")
+            .map(|values| values.iter().any(|value| value == "true"));
+        self.images
+            .iter()
+            .filter(|image| !self.is_intermediate(image))
+            .filter(|image| matches_labels(&image.labels, filters))
+            .filter(|image| {
+                dangling.map_or(true, |dangling| dangling == image.repo_tags.is_empty())
+            })
+            .map(|image| ImageSummary {
+                id: image.id.clone(),
+                // Untagged images are listed with a placeholder tag like docker does
+                repo_tags: if image.repo_tags.is_empty() {
+                    vec![UNTAGGED_REPO_TAG.to_owned()]
+                } else {
+                    image.repo_tags.clone()
+                },
+                labels: image.labels.clone(),
+                size: image.size,
+            })
+            .collect()
+    }
+
+    fn container_summaries(&self, filters: &SomaFilter) -> Vec<ContainerSummary> {
+        let status = filters.get("status");
+        self.containers
+            .iter()
+            .filter(|container| matches_labels(&container.labels, filters))
+            .filter(|container| status.map_or(true, |status| status.contains(&container.state)))
+            .map(|container| {
+                let image = self
+                    .images
+                    .iter()
+                    .find(|image| image.id == container.image_id)
+                    .and_then(|image| image.repo_tags.first())
+                    .unwrap_or(&container.image_id);
+                ContainerSummary {
+                    id: container.id.clone(),
+                    image: image.clone(),
+                    image_id: container.image_id.clone(),
+                    state: container.state.clone(),
+                    labels: container.labels.clone(),
+                    ports: container.ports.clone(),
+                    networks: container.networks.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl ContainerEngine for FakeEngine {
+    fn info(&self) -> EngineFuture<'_, EngineInfo> {
+        Box::new(future::ok(EngineInfo::new(
+            EngineKind::Docker,
+            "fake".to_owned(),
+            "1.39".to_owned(),
+        )))
+    }
+
+    fn list_images(&self, filters: SomaFilter) -> EngineFuture<'_, Vec<ImageSummary>> {
+        Box::new(future::ok(self.state.borrow().image_summaries(&filters)))
+    }
+
+    fn inspect_image(&self, image_name: &str) -> EngineFuture<'_, ImageDetails> {
+        let state = self.state.borrow();
+        let result = match state.find_image(image_name) {
+            Some(index) => Ok(ImageDetails {
+                repo_digests: state.images[index].repo_digests.clone(),
+                config: Config {
+                    labels: Some(state.images[index].labels.clone()),
+                    ..Default::default()
+                },
+            }),
+            None => Err(SomaError::EngineRequestFailed.into()),
+        };
+        Box::new(future::result(result))
+    }
+
+    fn build_image<'a>(
+        &'a self,
+        options: BuildOptions<'a>,
+        mut build_context: File,
+    ) -> EngineStream<'a, BuildOutput> {
+        let mut contents = Vec::new();
+        let output = match build_context
+            .read_to_end(&mut contents)
+            .map_err(failure::Error::from)
+            .and_then(|_| self.state.borrow_mut().build(options, &contents))
+        {
+            Ok(output) => output.into_iter().map(BuildOutput::Stream).collect(),
+            Err(error) => vec![BuildOutput::Error(error.to_string())],
+        };
+        Box::new(stream::iter_ok(output))
+    }
+
+    fn pull_image<'a>(&'a self, image_name: &'a str) -> EngineStream<'a, BuildOutput> {
+        self.state.borrow_mut().pull(image_name);
+        Box::new(stream::empty())
+    }
+
+    fn remove_image(&self, image_name: &str) -> EngineFuture<'_, ()> {
+        Box::new(future::result(
+            self.state.borrow_mut().remove_image(image_name),
+        ))
+    }
+
+    fn prune_images(&self, filters: SomaFilter) -> EngineFuture<'_, ()> {
+        let mut filters = filters;
+        filters.insert("dangling".to_owned(), vec!["true".to_owned()]);
+
+        let mut state = self.state.borrow_mut();
+        let result = state
+            .image_summaries(&filters)
+            .into_iter()
+            // Images used by containers are kept
+            .filter(|image| {
+                !state
+                    .containers
+                    .iter()
+                    .any(|container| container.image_id == image.id)
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .try_for_each(|image| state.remove_image(&image.id));
+        Box::new(future::result(result))
+    }
+
+    fn list_containers(&self, filters: SomaFilter) -> EngineFuture<'_, Vec<ContainerSummary>> {
+        Box::new(future::ok(
+            self.state.borrow().container_summaries(&filters),
+        ))
+    }
+
+    fn create_container<'a>(&'a self, options: ContainerOptions<'a>) -> EngineFuture<'a, String> {
+        Box::new(future::result(
+            self.state.borrow_mut().create_container(options),
+        ))
+    }
+
+    fn start_container(&self, container_id: &str) -> EngineFuture<'_, ()> {
+        Box::new(future::result(
+            self.state.borrow_mut().start_container(container_id),
+        ))
+    }
+
+    fn stop_container(&self, container_id: &str) -> EngineFuture<'_, ()> {
+        Box::new(future::result(
+            self.state.borrow_mut().stop_container(container_id),
+        ))
+    }
+
+    fn wait_container(&self, container_id: &str) -> EngineFuture<'_, i64> {
+        Box::new(future::result(
+            self.state
+                .borrow_mut()
+                .exit_container(container_id)
+                .map(|_| 0),
+        ))
+    }
+
+    fn container_logs(&self, container_id: &str) -> EngineFuture<'_, String> {
+        Box::new(future::result(
+            self.state
+                .borrow()
+                .find_container(container_id)
+                .map(|_| String::new()),
+        ))
+    }
+
+    fn exec_container<'a>(
+        &'a self,
+        container_id: &'a str,
+        cmd: Vec<&'a str>,
+    ) -> EngineFuture<'a, ExecOutput> {
+        let state = self.state.borrow();
+        let result = state.find_container(container_id).and_then(|index| {
+            if state.containers[index].state != "running" {
+                Err(SomaError::EngineRequestFailed)?;
+            }
+            Ok(ExecOutput {
+                exit_code: if cmd.last() == Some(&"false") { 1 } else { 0 },
+                output: String::new(),
+            })
+        });
+        Box::new(future::result(result))
+    }
+
+    fn download_from_container(
+        &self,
+        container_id: &str,
+        _path: &Path,
+    ) -> EngineStream<'_, Vec<u8>> {
+        let result = self
+            .state
+            .borrow()
+            .find_container(container_id)
+            .and_then(|_| Ok(tar::Builder::new(Vec::new()).into_inner()?));
+        Box::new(stream::once(result))
+    }
+
+    fn remove_container(&self, container_id: &str) -> EngineFuture<'_, ()> {
+        Box::new(future::result(
+            self.state.borrow_mut().remove_container(container_id),
+        ))
+    }
+
+    fn prune_containers(&self, filters: SomaFilter) -> EngineFuture<'_, ()> {
+        let mut state = self.state.borrow_mut();
+        let result = state
+            .container_summaries(&filters)
+            .into_iter()
+            .filter(|container| container.state != "running")
+            .try_for_each(|container| state.remove_container(&container.id));
+        Box::new(future::result(result))
+    }
+
+    fn list_networks(&self, filters: SomaFilter) -> EngineFuture<'_, Vec<NetworkSummary>> {
+        Box::new(future::ok(
+            self.state
+                .borrow()
+                .networks
+                .iter()
+                .filter(|network| matches_labels(&network.labels, &filters))
+                .cloned()
+                .collect(),
+        ))
+    }
+
+    fn create_network<'a>(&'a self, options: NetworkOptions<'a>) -> EngineFuture<'a, String> {
+        Box::new(future::result(
+            self.state.borrow_mut().create_network(options),
+        ))
+    }
+
+    fn connect_network(&self, network_id: &str, container_id: &str) -> EngineFuture<'_, ()> {
+        Box::new(future::result(
+            self.state
+                .borrow_mut()
+                .connect_network(network_id, container_id),
+        ))
+    }
+
+    fn remove_network(&self, network_id: &str) -> EngineFuture<'_, ()> {
+        Box::new(future::result(
+            self.state.borrow_mut().remove_network(network_id),
+        ))
+    }
+}
+
+// Untagged references refer to the latest tag
+fn normalize_tag(image_name: &str) -> String {
+    let last_component = image_name.rsplit('/').next().unwrap_or(image_name);
+    if last_component.contains(':') || image_name.contains('@') {
+        image_name.to_owned()
+    } else {
+        format!("{}:latest", image_name)
+    }
+}
+
+fn matches_labels(labels: &HashMap<String, String>, filters: &SomaFilter) -> bool {
+    filters.get("label").map_or(true, |label_filters| {
+        label_filters.iter().all(|filter| {
+            let mut split = filter.splitn(2, '=');
+            match (split.next(), split.next()) {
+                (Some(key), Some(value)) => labels.get(key).map(String::as_str) == Some(value),
+                (Some(key), None) => labels.contains_key(key),
+                _ => false,
+            }
+        })
+    })
+}
+
+// Joins lines continued with backslashes, skipping comments and empty lines
+fn parse_instructions(dockerfile: &str) -> Vec<String> {
+    let mut instructions = vec![];
+    let mut current = String::new();
+    for line in dockerfile.lines().map(str::trim) {
+        if current.is_empty() && (line.is_empty() || line.starts_with('#')) {
+            continue;
+        }
+        if let Some(continued) = line.strip_suffix('\\') {
+            current.push_str(continued);
+        } else {
+            current.push_str(line);
+            instructions.push(current.trim().to_owned());
+            current = String::new();
+        }
+    }
+    if !current.is_empty() {
+        instructions.push(current.trim().to_owned());
+    }
+    instructions
+}
+
+// Build contexts are either plain or gzipped tar archives
+fn read_dockerfile(build_context: &[u8], dockerfile: &str) -> SomaResult<String> {
+    let reader: Box<dyn Read> = if build_context.starts_with(&[0x1f, 0x8b]) {
+        Box::new(GzDecoder::new(build_context))
+    } else {
+        Box::new(build_context)
+    };
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()? == Path::new(dockerfile) {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            return Ok(contents);
+        }
+    }
+    Err(SomaError::EngineRequestFailed)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_instructions() {
+        let instructions = parse_instructions(
+            "# comment\nFROM ubuntu:16.04 AS base\n\nRUN apt-get update \\\n    && apt-get install -y socat\n",
+        );
+        assert_eq!(
+            instructions,
+            vec![
+                "FROM ubuntu:16.04 AS base",
+                "RUN apt-get update && apt-get install -y socat",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_tag() {
+        assert_eq!(normalize_tag("ubuntu"), "ubuntu:latest");
+        assert_eq!(normalize_tag("ubuntu:16.04"), "ubuntu:16.04");
+        assert_eq!(
+            normalize_tag("localhost:5000/ubuntu"),
+            "localhost:5000/ubuntu:latest"
+        );
+    }
+}
@@ -0,0 +1,391 @@
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+#[cfg(unix)]
+use std::io::ErrorKind;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use failure::Error;
+use serde::Serialize;
+use tokio::runtime::current_thread::Runtime;
+
+use crate::config::ConfigManager;
+use crate::data_dir::DataDirectory;
+use crate::docker::{self, ContainerEngine, DockerHost, VersionStatus};
+use crate::prelude::*;
+use crate::repository::backend::BackendExt;
+use crate::repository::RepositoryManager;
+use crate::NameString;
+
+// Both bollard and the API client request this version
+const REQUIRED_API_VERSION: (u32, u32) = (1, 39);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+// Result of a single diagnostic, with the remedy when it did not pass
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    pub remedy: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: impl Into<String>) -> Check {
+        Check {
+            name,
+            status: CheckStatus::Pass,
+            message: message.into(),
+            remedy: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, remedy: impl Into<String>) -> Check {
+        Check {
+            name,
+            status: CheckStatus::Warn,
+            message: message.into(),
+            remedy: Some(remedy.into()),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, remedy: impl Into<String>) -> Check {
+        Check {
+            name,
+            status: CheckStatus::Fail,
+            message: message.into(),
+            remedy: Some(remedy.into()),
+        }
+    }
+}
+
+impl Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "ok"),
+            CheckStatus::Warn => write!(f, "warn"),
+            CheckStatus::Fail => write!(f, "fail"),
+        }
+    }
+}
+
+pub fn check_data_dir() -> (Check, Option<DataDirectory>) {
+    const NAME: &str = "data directory";
+    match DataDirectory::new() {
+        Ok(data_dir) => (
+            Check::pass(
+                NAME,
+                format!("'{}' is accessible", data_dir.root_path().display()),
+            ),
+            Some(data_dir),
+        ),
+        Err(error) => {
+            let remedy = match error.downcast_ref::<SomaError>() {
+                Some(SomaError::DataDirectoryLockFailed) => {
+                    "Wait for the other soma command to finish, or end it if it is stuck"
+                }
+                _ => "Check the permissions of the data directory, or set SOMA_DATA_DIR to another directory",
+            };
+            (Check::fail(NAME, error.to_string(), remedy), None)
+        }
+    }
+}
+
+pub fn check_config(data_dir: &DataDirectory) -> (Check, Option<ConfigManager<'_>>) {
+    const NAME: &str = "configuration";
+    match data_dir.register::<ConfigManager>() {
+        Ok(config_manager) => (Check::pass(NAME, "loaded"), Some(config_manager)),
+        Err(error) => (
+            Check::fail(
+                NAME,
+                format!("Failed to load the configuration: {}", error),
+                "Fix or remove config/config.toml in the data directory",
+            ),
+            None,
+        ),
+    }
+}
+
+// Problem containers are labeled with the username, so it follows the name rules
+pub fn check_username(
+    configured: Option<&NameString>,
+    system_username: &str,
+) -> (Check, Option<NameString>) {
+    const NAME: &str = "username";
+    if let Some(username) = configured {
+        return (
+            Check::pass(NAME, format!("'{}' from the configuration", username)),
+            Some(username.clone()),
+        );
+    }
+
+    match NameString::try_from(system_username.to_lowercase()) {
+        Ok(username) => (Check::pass(NAME, format!("'{}'", username)), Some(username)),
+        Err(_) => (
+            Check::fail(
+                NAME,
+                format!("'{}' does not satisfy the name rules", system_username),
+                "Set a valid username with 'soma config set username NAME'",
+            ),
+            None,
+        ),
+    }
+}
+
+// Repositories are checked only as far as possible without fetching anything
+pub fn check_repositories(data_dir: &DataDirectory) -> Check {
+    const NAME: &str = "repositories";
+    let repo_manager = match data_dir.register::<RepositoryManager>() {
+        Ok(repo_manager) => repo_manager,
+        Err(error) => {
+            return Check::fail(
+                NAME,
+                format!("Failed to load the repository index: {}", error),
+                "Restore the repository index in the data directory from a backup, or remove it and add the repositories again",
+            )
+        }
+    };
+    check_repository_manager(&repo_manager)
+}
+
+pub fn check_repository_manager(repo_manager: &RepositoryManager) -> Check {
+    const NAME: &str = "repositories";
+    let mut broken_backends = vec![];
+    let mut broken_manifests = vec![];
+    for repository in repo_manager.list_repo() {
+        if repository.backend().check_at(repository.path()).is_err() {
+            broken_backends.push(format!("{} ({})", repository.name(), repository.backend()));
+        }
+    }
+    for problem in repo_manager.list_prob() {
+        if problem.load_manifest().is_err() {
+            broken_manifests.push(problem.fully_qualified_name());
+        }
+    }
+
+    if !broken_backends.is_empty() {
+        Check::fail(
+            NAME,
+            format!(
+                "Cannot update from the origin of: {}",
+                broken_backends.join(", ")
+            ),
+            "Remove the repositories with 'soma remove' and add them again",
+        )
+    } else if !broken_manifests.is_empty() {
+        Check::warn(
+            NAME,
+            format!("Invalid manifests: {}", broken_manifests.join(", ")),
+            "Fix soma.toml of the problems and run 'soma update'",
+        )
+    } else {
+        Check::pass(
+            NAME,
+            format!(
+                "{} repositories are intact",
+                repo_manager.list_repo().count()
+            ),
+        )
+    }
+}
+
+fn describe_host(docker_host: &DockerHost) -> String {
+    match docker_host {
+        #[cfg(unix)]
+        DockerHost::Unix(path) => format!("unix://{}", path),
+        #[cfg(windows)]
+        DockerHost::NamedPipe(path) => format!("npipe://{}", path),
+        DockerHost::Http(addr) => format!("tcp://{}", addr),
+        DockerHost::Https { addr, cert_path } => {
+            format!(
+                "https://{} with certificates in '{}'",
+                addr,
+                cert_path.display()
+            )
+        }
+    }
+}
+
+pub fn check_docker_host(
+    host_override: Option<&str>,
+    configured_host: Option<&str>,
+) -> (Check, Option<DockerHost>) {
+    const NAME: &str = "docker host";
+    match DockerHost::from_env(host_override, configured_host) {
+        Ok(docker_host) => (
+            Check::pass(NAME, describe_host(&docker_host)),
+            Some(docker_host),
+        ),
+        Err(error) => (
+            Check::fail(
+                NAME,
+                error.to_string(),
+                "Fix --host, DOCKER_HOST, or the docker_host configuration",
+            ),
+            None,
+        ),
+    }
+}
+
+#[cfg(unix)]
+pub fn check_socket(path: &str) -> Check {
+    const NAME: &str = "docker socket";
+    match UnixStream::connect(path) {
+        Ok(_) => Check::pass(NAME, format!("'{}' is accessible", path)),
+        Err(ref error) if error.kind() == ErrorKind::PermissionDenied => Check::fail(
+            NAME,
+            format!("Permission denied for '{}'", path),
+            "Add your user to the docker group with 'sudo usermod -aG docker $USER' and log in again",
+        ),
+        Err(ref error) if error.kind() == ErrorKind::NotFound => Check::fail(
+            NAME,
+            format!("'{}' does not exist", path),
+            "Start the Docker daemon, or point --host or DOCKER_HOST at the running engine",
+        ),
+        Err(error) => Check::fail(
+            NAME,
+            format!("Failed to connect to '{}': {}", path, error),
+            "Start the Docker daemon",
+        ),
+    }
+}
+
+// Connectors fail early only on invalid addresses or certificates
+pub fn connection_failed(error: Error) -> Check {
+    Check::fail(
+        "container engine",
+        format!("Failed to connect to the engine: {}", error),
+        "Check the docker host address, and DOCKER_CERT_PATH for https endpoints",
+    )
+}
+
+fn parse_api_version(api_version: &str) -> Option<(u32, u32)> {
+    let mut split = api_version.trim().splitn(2, '.');
+    let major = split.next()?.parse().ok()?;
+    let minor = split.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+pub fn check_engine(engine: &impl ContainerEngine, runtime: &mut Runtime) -> Check {
+    const NAME: &str = "container engine";
+    let engine_info = match runtime.block_on(engine.info()) {
+        Ok(engine_info) => engine_info,
+        Err(error) => {
+            return Check::fail(
+                NAME,
+                format!("The engine is not reachable: {}", error),
+                "Start the Docker daemon, and check that the docker host points at it",
+            )
+        }
+    };
+
+    let message = format!(
+        "{} {} (API {})",
+        engine_info.kind(),
+        engine_info.version(),
+        engine_info.api_version()
+    );
+    match parse_api_version(engine_info.api_version()) {
+        Some(api_version) if api_version >= REQUIRED_API_VERSION => Check::pass(NAME, message),
+        _ => Check::fail(
+            NAME,
+            message,
+            format!(
+                "Upgrade the engine to support API {}.{} or later",
+                REQUIRED_API_VERSION.0, REQUIRED_API_VERSION.1
+            ),
+        ),
+    }
+}
+
+// Resources created by other soma versions may not work with this one
+pub fn check_resources(
+    engine: &impl ContainerEngine,
+    username: &str,
+    runtime: &mut Runtime,
+) -> Check {
+    const NAME: &str = "resources";
+    let resources = runtime
+        .block_on(docker::list_user_images(engine, username))
+        .and_then(|images| {
+            runtime
+                .block_on(docker::list_user_containers(engine, username))
+                .map(|containers| (images, containers))
+        });
+    let (images, containers) = match resources {
+        Ok(resources) => resources,
+        Err(error) => {
+            return Check::fail(
+                NAME,
+                format!("Failed to list images and containers: {}", error),
+                "Check that the container engine is reachable",
+            )
+        }
+    };
+
+    let mismatched_images = images
+        .iter()
+        .filter(|image| !matches!(image.status(), VersionStatus::Normal))
+        .count();
+    let mismatched_containers = containers
+        .iter()
+        .filter(|container| !matches!(container.status(), VersionStatus::Normal))
+        .count();
+    if mismatched_images + mismatched_containers == 0 {
+        Check::pass(
+            NAME,
+            format!(
+                "{} images and {} containers",
+                images.len(),
+                containers.len()
+            ),
+        )
+    } else {
+        Check::warn(
+            NAME,
+            format!(
+                "{} images and {} containers were created by another soma version",
+                mismatched_images, mismatched_containers
+            ),
+            "Stop the problems, run 'soma gc' to remove them, and build the problems again",
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_api_version() {
+        assert_eq!(parse_api_version("1.39"), Some((1, 39)));
+        assert_eq!(parse_api_version("1.41\n"), Some((1, 41)));
+        assert!(parse_api_version("1.40") > Some(REQUIRED_API_VERSION));
+        assert!(parse_api_version("1.9") < Some(REQUIRED_API_VERSION));
+        assert_eq!(parse_api_version("fake"), None);
+    }
+
+    #[test]
+    fn test_check_username() {
+        let (check, username) = check_username(None, "Alice");
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert_eq!(username.unwrap().as_str(), "alice");
+
+        let (check, username) = check_username(None, "alice smith");
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.remedy.is_some());
+        assert!(username.is_none());
+
+        let configured = NameString::try_from("bob").unwrap();
+        let (check, username) = check_username(Some(&configured), "alice smith");
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert_eq!(username, Some(configured));
+    }
+}
@@ -2,6 +2,14 @@ use failure::Fail;
 
 #[derive(Debug, Fail)]
 pub enum Error {
+    #[fail(display = "The operation failed for some problems")]
+    BatchOperationFailed,
+    #[fail(
+        display = "The command needs confirmation, which requires a terminal and text output; Pass --yes to stop or --prune to update without asking"
+    )]
+    ConfirmationUnavailable,
+    #[fail(display = "The problem container did not become healthy")]
+    ContainerUnhealthy,
     #[fail(display = "Failed to access the data directory")]
     DataDirectoryAccessDenied,
     #[fail(display = "Another Soma instance is using the data directory")]
@@ -10,12 +18,32 @@ pub enum Error {
     DockerBuildFailed,
     #[fail(display = "A repository with the same name already exists")]
     DuplicateRepository,
+    #[fail(display = "The container engine failed to process the request")]
+    EngineRequestFailed,
+    #[fail(display = "Some environment checks failed")]
+    EnvironmentCheckFailed,
+    #[fail(display = "The command in the problem container exited with a non-zero status")]
+    ExecFailed,
     #[fail(display = "Failed to detect filename from the path")]
     FileNameNotFound,
     #[fail(
         display = "The specified file does not exist, or you don't have enough permission to access it"
     )]
     FileUnreachable,
+    #[fail(display = "The problem image is not built yet")]
+    ImageNotFound,
+    #[fail(display = "The problem has multiple instances; specify one with --instance")]
+    InstanceNotSpecified,
+    #[fail(display = "The archive is not a valid problem export")]
+    InvalidArchive,
+    #[fail(display = "The bundle is corrupted or incomplete")]
+    InvalidBundle,
+    #[fail(display = "The configuration key is not one of the supported keys")]
+    InvalidConfigKey,
+    #[fail(display = "The value is not valid for the configuration key")]
+    InvalidConfigValue,
+    #[fail(display = "DOCKER_HOST or the host option is not a supported docker endpoint")]
+    InvalidDockerHost,
     #[fail(display = "Some entry in the manifest is invalid")]
     InvalidManifest,
     #[fail(display = "The provided repository does not contain 'soma.toml' or 'soma-list.toml'")]
@@ -26,8 +54,20 @@ pub enum Error {
         display = "The name doesn't satisfy docker name component rules, which allows lower case alphanumerics with non-boundary '_', '__', or (multiple) '-'(s)"
     )]
     InvalidName,
+    #[fail(display = "A template provided by the repository is invalid or inaccessible")]
+    InvalidTemplate,
     #[fail(display = "The specified file's path contains unsupported characters")]
     InvalidUnicode,
+    #[fail(display = "The docker endpoint does not support managing networks")]
+    NetworkUnsupported,
+    #[fail(display = "No port is available in the configured port range")]
+    NoAvailablePort,
+    #[fail(
+        display = "The port binding of the problem container is unknown; run the problem again"
+    )]
+    PortBindingUnknown,
+    #[fail(display = "The port number is required unless a port range is configured")]
+    PortNotSpecified,
     #[fail(display = "There is a container already running for the specified problem")]
     ProblemAlreadyRunning,
     #[fail(display = "The specified problem is not found")]
@@ -41,9 +81,71 @@ pub enum Error {
     #[fail(display = "The specified repository is not found")]
     RepositoryNotFound,
     #[fail(
-        display = "The repository contains changes that cannot be handled by update command; Please remove and add the repository manually"
+        display = "ca.pem of the docker endpoint is not found in DOCKER_CERT_PATH or ~/.docker"
+    )]
+    TlsCaNotFound,
+    #[fail(
+        display = "identity.pfx is not found in DOCKER_CERT_PATH or ~/.docker; Create it from the client certificate with 'openssl pkcs12 -export -out identity.pfx -inkey key.pem -in cert.pem -passout pass:'"
+    )]
+    TlsIdentityNotFound,
+    #[fail(display = "The container engine should be either 'docker' or 'podman'")]
+    UnknownEngine,
+    #[fail(
+        display = "The restart policy should be one of 'no', 'on-failure', or 'unless-stopped'"
+    )]
+    UnknownRestartPolicy,
+    #[fail(
+        display = "Removed or renamed problems still have images or containers; Update with --prune to remove them"
     )]
     UnsupportedUpdate,
 }
 
+impl Error {
+    // Stable identifiers for machine-readable output, which must not change with variant names
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::BatchOperationFailed => "BatchOperationFailed",
+            Error::ConfirmationUnavailable => "ConfirmationUnavailable",
+            Error::ContainerUnhealthy => "ContainerUnhealthy",
+            Error::DataDirectoryAccessDenied => "DataDirectoryAccessDenied",
+            Error::DataDirectoryLockFailed => "DataDirectoryLockFailed",
+            Error::DockerBuildFailed => "DockerBuildFailed",
+            Error::DuplicateRepository => "DuplicateRepository",
+            Error::EngineRequestFailed => "EngineRequestFailed",
+            Error::EnvironmentCheckFailed => "EnvironmentCheckFailed",
+            Error::ExecFailed => "ExecFailed",
+            Error::FileNameNotFound => "FileNameNotFound",
+            Error::FileUnreachable => "FileUnreachable",
+            Error::ImageNotFound => "ImageNotFound",
+            Error::InstanceNotSpecified => "InstanceNotSpecified",
+            Error::InvalidArchive => "InvalidArchive",
+            Error::InvalidBundle => "InvalidBundle",
+            Error::InvalidConfigKey => "InvalidConfigKey",
+            Error::InvalidConfigValue => "InvalidConfigValue",
+            Error::InvalidDockerHost => "InvalidDockerHost",
+            Error::InvalidManifest => "InvalidManifest",
+            Error::InvalidRepository => "InvalidRepository",
+            Error::InvalidSomaList => "InvalidSomaList",
+            Error::InvalidName => "InvalidName",
+            Error::InvalidTemplate => "InvalidTemplate",
+            Error::InvalidUnicode => "InvalidUnicode",
+            Error::NetworkUnsupported => "NetworkUnsupported",
+            Error::NoAvailablePort => "NoAvailablePort",
+            Error::PortBindingUnknown => "PortBindingUnknown",
+            Error::PortNotSpecified => "PortNotSpecified",
+            Error::ProblemAlreadyRunning => "ProblemAlreadyRunning",
+            Error::ProblemNotFound => "ProblemNotFound",
+            Error::ProblemNotRunning => "ProblemNotRunning",
+            Error::ProblemQueryAmbiguous => "ProblemQueryAmbiguous",
+            Error::RepositoryInUse => "RepositoryInUse",
+            Error::RepositoryNotFound => "RepositoryNotFound",
+            Error::TlsCaNotFound => "TlsCaNotFound",
+            Error::TlsIdentityNotFound => "TlsIdentityNotFound",
+            Error::UnknownEngine => "UnknownEngine",
+            Error::UnknownRestartPolicy => "UnknownRestartPolicy",
+            Error::UnsupportedUpdate => "UnsupportedUpdate",
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, failure::Error>;
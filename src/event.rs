@@ -0,0 +1,353 @@
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::NameString;
+
+lazy_static! {
+    static ref BUILD_STEP_REGEX: Regex = Regex::new(r"^Step (\d+)/(\d+) : (.*)$").unwrap();
+}
+
+// Progress of an operation, reported by ops to the printer
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum Event {
+    StageStarted(Stage),
+    // Line of the container engine output during a build
+    BuildOutput(String),
+    BuildStep {
+        step: u32,
+        total: u32,
+        instruction: String,
+    },
+    BuildError(String),
+    BuildFinished {
+        succeeded: bool,
+    },
+    Pulling {
+        layer: String,
+        status: String,
+        progress: Option<String>,
+    },
+    Warning(String),
+    FileFetching(String),
+    LibcDetected(String),
+    ImageBuilt(String),
+    ImageCleaned(String),
+    ImageRemoved {
+        problem: String,
+        id: String,
+    },
+    ContainerRemoved {
+        problem: String,
+        id: String,
+    },
+    ImageOutdated(String),
+    ImagePacking(String),
+    ImageLoaded(String),
+    NoOutdatedImage,
+    RebuildSuggested,
+    ContainerCreating(String),
+    NetworkCreated {
+        name: String,
+        internal: bool,
+    },
+    NetworkRemoved(String),
+    ContainerStarted {
+        problem: String,
+        instance: String,
+        id: String,
+        bind_address: IpAddr,
+        port: u32,
+    },
+    ContainerRestarted {
+        problem: String,
+        instance: String,
+        id: String,
+    },
+    // Carries the last log lines of the container, which usually explain the failure
+    ContainerUnhealthy {
+        problem: String,
+        logs: Vec<String>,
+    },
+    ProblemAdded(String),
+    ProblemRemoved(String),
+    ProblemRenamed {
+        from: String,
+        to: String,
+    },
+    ProblemChanged(String),
+    ProblemExported(String),
+    ProblemImported(String),
+    ProblemPurged(String),
+    ProblemStopped(String),
+    NoProblemChanged,
+    RepositoryAdded(NameString),
+    RepositoryLoaded(NameString),
+    RepositoryPacking(NameString),
+    RepositoryRemoved(NameString),
+    RepositoryUpdated(NameString),
+    BundleCreated,
+    // Event of a single problem among concurrently processed problems
+    ProblemProgress {
+        problem: String,
+        event: Box<Event>,
+    },
+    SummaryStarted,
+    ProblemSucceeded {
+        problem: String,
+        outcome: String,
+    },
+    ProblemFailed {
+        problem: String,
+        error: String,
+    },
+    SummaryFinished {
+        succeeded: usize,
+        failed: usize,
+    },
+}
+
+impl Event {
+    // Recognizes build steps in a line of the engine output
+    pub fn from_build_output(message: &str) -> Event {
+        match BUILD_STEP_REGEX.captures(message) {
+            Some(captures) => match (captures[1].parse(), captures[2].parse()) {
+                (Ok(step), Ok(total)) => Event::BuildStep {
+                    step,
+                    total,
+                    instruction: captures[3].to_owned(),
+                },
+                _ => Event::BuildOutput(message.to_owned()),
+            },
+            None => Event::BuildOutput(message.to_owned()),
+        }
+    }
+
+    pub fn in_progress_of(self, problem: impl Into<String>) -> Event {
+        Event::ProblemProgress {
+            problem: problem.into(),
+            event: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::StageStarted(stage) => write!(f, "{}", stage),
+            Event::BuildOutput(message) | Event::BuildError(message) => write!(f, "{}", message),
+            Event::BuildStep {
+                step,
+                total,
+                instruction,
+            } => write!(f, "Step {}/{} : {}", step, total, instruction),
+            Event::BuildFinished { succeeded: true } => write!(f, "built"),
+            Event::BuildFinished { succeeded: false } => write!(f, "failed"),
+            Event::Pulling {
+                layer,
+                status,
+                progress: Some(progress),
+            } => write!(f, "{}: {} {}", layer, status, progress),
+            Event::Pulling { layer, status, .. } => write!(f, "{}: {}", layer, status),
+            Event::Warning(message) => write!(f, "Warning: {}", message),
+            Event::FileFetching(file_name) => write!(f, "Fetching '{}'...", file_name),
+            Event::LibcDetected(version) => write!(f, "Detected libc: '{}'", version),
+            Event::ImageBuilt(problem) => write!(f, "Built image for problem: '{}'", problem),
+            Event::ImageCleaned(problem) => write!(f, "Problem image cleaned: '{}'", problem),
+            Event::ImageRemoved { problem, id } => {
+                write!(f, "Image removed: '{}' ({})", problem, short_id(id))
+            }
+            Event::ContainerRemoved { problem, id } => {
+                write!(f, "Container removed: '{}' ({})", problem, short_id(id))
+            }
+            Event::ImageOutdated(problem) => write!(f, "Problem image outdated: '{}'", problem),
+            Event::ImagePacking(problem) => write!(f, "Packing image: '{}'", problem),
+            Event::ImageLoaded(problem) => write!(f, "Image loaded: '{}'", problem),
+            Event::NoOutdatedImage => write!(f, "No outdated problem image was found."),
+            Event::RebuildSuggested => {
+                write!(f, "Run 'soma build --outdated' to rebuild outdated images")
+            }
+            Event::ContainerCreating(problem) => {
+                write!(f, "Creating container for problem: '{}'", problem)
+            }
+            Event::NetworkCreated {
+                name,
+                internal: false,
+            } => write!(f, "Network created: '{}'", name),
+            Event::NetworkCreated {
+                name,
+                internal: true,
+            } => write!(f, "Internal network created: '{}'", name),
+            Event::NetworkRemoved(name) => write!(f, "Network removed: '{}'", name),
+            Event::ContainerStarted {
+                id,
+                bind_address,
+                port,
+                ..
+            } => write!(
+                f,
+                "Container started: '{}' ({})",
+                id,
+                SocketAddr::new(*bind_address, *port as u16)
+            ),
+            Event::ContainerRestarted { id, .. } => write!(f, "Container restarted: '{}'", id),
+            Event::ContainerUnhealthy { problem, logs } => {
+                write!(f, "Container did not become healthy: '{}'", problem)?;
+                for line in logs {
+                    write!(f, "\n  {}", line)?;
+                }
+                Ok(())
+            }
+            Event::ProblemAdded(problem) => write!(f, "Problem added: '{}'", problem),
+            Event::ProblemRemoved(problem) => write!(f, "Problem removed: '{}'", problem),
+            Event::ProblemRenamed { from, to } => {
+                write!(f, "Problem renamed: '{}' -> '{}'", from, to)
+            }
+            Event::ProblemChanged(problem) => write!(f, "Problem changed: '{}'", problem),
+            Event::ProblemExported(problem) => write!(f, "Problem exported: '{}'", problem),
+            Event::ProblemImported(problem) => write!(f, "Problem imported: '{}'", problem),
+            Event::ProblemPurged(problem) => write!(f, "Problem purged: '{}'", problem),
+            Event::ProblemStopped(problem) => write!(f, "Problem stopped: '{}'", problem),
+            Event::NoProblemChanged => write!(f, "No problem was changed"),
+            Event::RepositoryAdded(repo_name) => write!(f, "Repository added: '{}'", repo_name),
+            Event::RepositoryLoaded(repo_name) => write!(f, "Repository loaded: '{}'", repo_name),
+            Event::RepositoryPacking(repo_name) => {
+                write!(f, "Packing repository: '{}'", repo_name)
+            }
+            Event::RepositoryRemoved(repo_name) => {
+                write!(f, "Repository removed: '{}'", repo_name)
+            }
+            Event::RepositoryUpdated(repo_name) => {
+                write!(f, "Repository updated: '{}'", repo_name)
+            }
+            Event::BundleCreated => write!(f, "Bundle created"),
+            Event::ProblemProgress { problem, event } => write!(f, "{}: {}", problem, event),
+            Event::SummaryStarted => write!(f, "Summary:"),
+            Event::ProblemSucceeded { problem, outcome } => {
+                write!(f, "  {}: {}", problem, outcome)
+            }
+            Event::ProblemFailed { problem, error } => {
+                write!(f, "  {}: failed ({})", problem, error)
+            }
+            Event::SummaryFinished { succeeded, failed } => {
+                write!(f, "{} succeeded, {} failed", succeeded, failed)
+            }
+        }
+    }
+}
+
+// Shortens ids like docker does, dropping the digest algorithm of image ids
+pub(crate) fn short_id(id: &str) -> &str {
+    let id = id.strip_prefix("sha256:").unwrap_or(id);
+    &id[..id.len().min(12)]
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Queued,
+    LoadingManifest,
+    PreparingBuildContext,
+    ConstructingImageRoot,
+    CopyingBuildSource,
+    RenderingBuildFiles,
+    EncodingBuildContext,
+    WaitingForEngine,
+    BuildingImage,
+    InspectingImage,
+    ExportingImageFilesystem,
+    WritingArchive,
+    ReadingArchive,
+    LoadingImage,
+    UnpackingBundle,
+    StartingContainer,
+    StartingProxy,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            Stage::Queued => "waiting",
+            Stage::LoadingManifest => "Loading manifest...",
+            Stage::PreparingBuildContext => "Preparing build context...",
+            Stage::ConstructingImageRoot => "Constructing image root...",
+            Stage::CopyingBuildSource => "Copying build source...",
+            Stage::RenderingBuildFiles => "Rendering build files...",
+            Stage::EncodingBuildContext => "Encoding build context...",
+            Stage::WaitingForEngine => "Waiting for the engine...",
+            Stage::BuildingImage => "Building image...",
+            Stage::InspectingImage => "Inspecting image...",
+            Stage::ExportingImageFilesystem => "Exporting image filesystem...",
+            Stage::WritingArchive => "Writing archive...",
+            Stage::ReadingArchive => "Reading archive...",
+            Stage::LoadingImage => "Loading image...",
+            Stage::UnpackingBundle => "Unpacking bundle...",
+            Stage::StartingContainer => "Starting container...",
+            Stage::StartingProxy => "Starting port proxy...",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_from_build_output() {
+        assert_eq!(
+            Event::from_build_output("Step 2/7 : RUN apt-get update"),
+            Event::BuildStep {
+                step: 2,
+                total: 7,
+                instruction: "RUN apt-get update".to_owned(),
+            }
+        );
+        assert_eq!(
+            Event::from_build_output("Successfully built 0123456789ab"),
+            Event::BuildOutput("Successfully built 0123456789ab".to_owned())
+        );
+        assert_eq!(
+            Event::from_build_output("Step 2/7 : RUN echo").to_string(),
+            "Step 2/7 : RUN echo"
+        );
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        assert_eq!(
+            serde_json::to_value(Event::ContainerStarted {
+                problem: "repo.prob".to_owned(),
+                instance: "default".to_owned(),
+                id: "0123456789ab".to_owned(),
+                bind_address: IpAddr::from([127, 0, 0, 1]),
+                port: 31337,
+            })
+            .unwrap(),
+            json!({
+                "type": "container_started",
+                "data": {
+                    "problem": "repo.prob",
+                    "instance": "default",
+                    "id": "0123456789ab",
+                    "bind_address": "127.0.0.1",
+                    "port": 31337
+                }
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(Event::StageStarted(Stage::LoadingManifest)).unwrap(),
+            json!({ "type": "stage_started", "data": "loading_manifest" })
+        );
+        assert_eq!(
+            serde_json::to_value(Event::BundleCreated).unwrap(),
+            json!({ "type": "bundle_created" })
+        );
+    }
+}
@@ -0,0 +1,363 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+
+use bollard::container::Config;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+use crate::problem::configs::{is_contained, is_valid_env_key};
+use crate::problem::MANIFEST_FILE_NAME;
+use crate::NameString;
+
+pub const ROOTFS_FILE_NAME: &str = "rootfs.tar";
+pub(crate) const EXPORT_MANIFEST_FILE_NAME: &str = "export.toml";
+const PROBLEM_DIR_NAME: &str = "problem";
+
+lazy_static! {
+    static ref PORT_REGEX: Regex = Regex::new(r"^[0-9]+(/(tcp|udp|sctp))?$").unwrap();
+}
+
+// Describes an exported problem archive with the following layout:
+// export.toml, rootfs.tar, problem/soma.toml, problem/<public files>
+#[derive(Deserialize, Serialize)]
+pub struct ExportManifest {
+    repo_name: NameString,
+    prob_name: NameString,
+    image: ImageConfig,
+}
+
+// Runtime configuration of the problem image which is lost when its filesystem is flattened
+#[derive(Deserialize, Serialize)]
+pub struct ImageConfig {
+    user: Option<String>,
+    working_dir: Option<String>,
+    entrypoint: Vec<String>,
+    cmd: Vec<String>,
+    env: Vec<String>,
+    exposed_ports: Vec<String>,
+    base_digest: Option<String>,
+    libc_version: Option<String>,
+}
+
+// Strings are escaped to be rendered inside double quotes of Dockerfile instructions
+#[derive(Serialize)]
+pub struct SolidImageConfig {
+    user: Option<String>,
+    working_dir: Option<String>,
+    entrypoint: Vec<String>,
+    cmd: Vec<String>,
+    env: BTreeMap<String, String>,
+    exposed_ports: Vec<String>,
+}
+
+impl ExportManifest {
+    pub fn new(repo_name: NameString, prob_name: NameString, image: ImageConfig) -> ExportManifest {
+        ExportManifest {
+            repo_name,
+            prob_name,
+            image,
+        }
+    }
+
+    pub fn repo_name(&self) -> &NameString {
+        &self.repo_name
+    }
+
+    pub fn prob_name(&self) -> &NameString {
+        &self.prob_name
+    }
+
+    pub fn image(&self) -> &ImageConfig {
+        &self.image
+    }
+}
+
+impl ImageConfig {
+    pub fn new(
+        config: Config<String>,
+        base_digest: Option<&String>,
+        libc_version: Option<&String>,
+    ) -> ImageConfig {
+        let mut exposed_ports: Vec<_> = config
+            .exposed_ports
+            .map(|ports| ports.into_iter().map(|(port, _)| port).collect())
+            .unwrap_or_default();
+        exposed_ports.sort();
+
+        ImageConfig {
+            user: config.user.filter(|user| !user.is_empty()),
+            working_dir: config.working_dir.filter(|dir| !dir.is_empty()),
+            entrypoint: config.entrypoint.unwrap_or_default(),
+            cmd: config.cmd.unwrap_or_default(),
+            env: config.env.unwrap_or_default(),
+            exposed_ports,
+            base_digest: base_digest.cloned(),
+            libc_version: libc_version.cloned(),
+        }
+    }
+
+    pub fn base_digest(&self) -> Option<&String> {
+        self.base_digest.as_ref()
+    }
+
+    pub fn libc_version(&self) -> Option<&String> {
+        self.libc_version.as_ref()
+    }
+
+    pub fn solidify(&self) -> SomaResult<SolidImageConfig> {
+        // TODO: More descriptive error
+        let single_line = |value: &String| !value.contains('\n');
+        if !self.user.iter().all(single_line)
+            || !self.working_dir.iter().all(single_line)
+            || !self.entrypoint.iter().all(single_line)
+            || !self.cmd.iter().all(single_line)
+            || !self.env.iter().all(single_line)
+            || !self
+                .exposed_ports
+                .iter()
+                .all(|port| PORT_REGEX.is_match(port))
+        {
+            Err(SomaError::InvalidArchive)?;
+        }
+
+        let env = self
+            .env
+            .iter()
+            .map(|entry| {
+                let mut split = entry.splitn(2, '=');
+                match (split.next(), split.next()) {
+                    (Some(key), Some(value)) if is_valid_env_key(key) => {
+                        Ok((key.to_owned(), escape_quoted(value)))
+                    }
+                    _ => Err(SomaError::InvalidArchive)?,
+                }
+            })
+            .collect::<SomaResult<BTreeMap<_, _>>>()?;
+
+        Ok(SolidImageConfig {
+            user: self.user.clone(),
+            working_dir: self.working_dir.clone(),
+            entrypoint: self
+                .entrypoint
+                .iter()
+                .map(|arg| escape_quoted(arg))
+                .collect(),
+            cmd: self.cmd.iter().map(|arg| escape_quoted(arg)).collect(),
+            env,
+            exposed_ports: self.exposed_ports.clone(),
+        })
+    }
+}
+
+fn escape_quoted(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Public files are stored relative to the problem directory
+pub fn write_archive(
+    writer: impl Write,
+    manifest: &ExportManifest,
+    problem_dir: impl AsRef<Path>,
+    public_files: &[&PathBuf],
+    rootfs: File,
+) -> SomaResult<()> {
+    let compressor = GzEncoder::new(writer, Compression::default());
+    let mut tar = tar::Builder::new(compressor);
+
+    append_bytes(
+        &mut tar,
+        EXPORT_MANIFEST_FILE_NAME,
+        toml::to_string(manifest)?.as_bytes(),
+    )?;
+    append_file(&mut tar, ROOTFS_FILE_NAME, rootfs)?;
+
+    let problem_dir = problem_dir.as_ref();
+    let manifest_file = PathBuf::from(MANIFEST_FILE_NAME);
+    for file_path in public_files
+        .iter()
+        .cloned()
+        .chain(iter::once(&manifest_file))
+    {
+        if !is_contained(file_path) {
+            Err(SomaError::InvalidManifest)?;
+        }
+        tar.append_path_with_name(
+            problem_dir.join(file_path),
+            Path::new(PROBLEM_DIR_NAME).join(file_path),
+        )?;
+    }
+
+    tar.into_inner()?.finish()?;
+    Ok(())
+}
+
+pub(crate) fn append_bytes(
+    tar: &mut tar::Builder<impl Write>,
+    path: impl AsRef<Path>,
+    data: &[u8],
+) -> SomaResult<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    tar.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+// Files are streamed into the archive, since a filesystem can be larger than memory
+pub(crate) fn append_file(
+    tar: &mut tar::Builder<impl Write>,
+    path: impl AsRef<Path>,
+    file: File,
+) -> SomaResult<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(file.metadata()?.len());
+    header.set_mode(0o644);
+    header.set_cksum();
+    tar.append_data(&mut header, path, file)?;
+    Ok(())
+}
+
+// Copies the filesystem into rootfs while reading the archive, returning the manifest
+pub fn read_archive(
+    archive_path: impl AsRef<Path>,
+    mut rootfs: impl Write,
+) -> SomaResult<ExportManifest> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(archive_path)?));
+
+    let mut manifest = None;
+    let mut rootfs_found = false;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        if entry_path == Path::new(EXPORT_MANIFEST_FILE_NAME) {
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents)?;
+            manifest = Some(toml::from_slice(&contents)?);
+        } else if entry_path == Path::new(ROOTFS_FILE_NAME) {
+            io::copy(&mut entry, &mut rootfs)?;
+            rootfs_found = true;
+        }
+    }
+
+    match manifest {
+        Some(manifest) if rootfs_found => Ok(manifest),
+        _ => Err(SomaError::InvalidArchive)?,
+    }
+}
+
+// Unpacks problem files of the archive so that the destination becomes a repository
+pub fn unpack_problem(
+    archive_path: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+) -> SomaResult<()> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(archive_path)?));
+    let destination = destination.as_ref();
+    fs::create_dir_all(destination)?;
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        if let Ok(relative_path) = entry_path.strip_prefix(PROBLEM_DIR_NAME) {
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            if !is_contained(relative_path) {
+                Err(SomaError::InvalidArchive)?;
+            }
+
+            let file_path = destination.join(relative_path);
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            entry.unpack(file_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::io::Write;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn test_image_config() -> ImageConfig {
+        ImageConfig {
+            user: Some("simple-bof".to_owned()),
+            working_dir: Some("/home/simple-bof".to_owned()),
+            entrypoint: vec![],
+            cmd: vec!["/.soma/start.sh".to_owned()],
+            env: vec!["PROB=simple-bof".to_owned(), "BANNER=say \"hi\"".to_owned()],
+            exposed_ports: vec!["1337/tcp".to_owned()],
+            base_digest: None,
+            libc_version: Some("ldd (Ubuntu GLIBC 2.23-0ubuntu11) 2.23".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_image_config_solidify() {
+        let solid_config = test_image_config().solidify().unwrap();
+        assert_eq!(solid_config.env.get("BANNER").unwrap(), r#"say \"hi\""#);
+
+        let mut invalid_config = test_image_config();
+        invalid_config.env.push("NO_VALUE".to_owned());
+        assert!(invalid_config.solidify().is_err());
+    }
+
+    #[test]
+    fn test_archive_round_trip() {
+        let problem_dir = tempdir().unwrap();
+        fs::write(problem_dir.path().join(MANIFEST_FILE_NAME), "name = \"a\"").unwrap();
+        fs::create_dir(problem_dir.path().join("public")).unwrap();
+        File::create(problem_dir.path().join("public/simple-bof"))
+            .unwrap()
+            .write_all(b"binary")
+            .unwrap();
+
+        let manifest = ExportManifest::new(
+            NameString::try_from("test").unwrap(),
+            NameString::try_from("a").unwrap(),
+            test_image_config(),
+        );
+        let archive_dir = tempdir().unwrap();
+        let archive_path = archive_dir.path().join("a.tar.gz");
+        let rootfs_path = archive_dir.path().join(ROOTFS_FILE_NAME);
+        fs::write(&rootfs_path, "rootfs").unwrap();
+        write_archive(
+            File::create(&archive_path).unwrap(),
+            &manifest,
+            problem_dir.path(),
+            &[&PathBuf::from("public/simple-bof")],
+            File::open(&rootfs_path).unwrap(),
+        )
+        .unwrap();
+
+        let mut rootfs = Vec::new();
+        let read_manifest = read_archive(&archive_path, &mut rootfs).unwrap();
+        assert_eq!(read_manifest.prob_name(), manifest.prob_name());
+        assert_eq!(
+            read_manifest.image().libc_version(),
+            manifest.image().libc_version()
+        );
+        assert_eq!(rootfs, b"rootfs");
+
+        let repo_dir = tempdir().unwrap();
+        unpack_problem(&archive_path, repo_dir.path()).unwrap();
+        assert!(repo_dir.path().join(MANIFEST_FILE_NAME).is_file());
+        assert!(repo_dir.path().join("public/simple-bof").is_file());
+        assert!(!repo_dir.path().join(ROOTFS_FILE_NAME).exists());
+    }
+}
@@ -1,28 +1,35 @@
 use std::cell::{RefCell, RefMut};
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Read;
 use std::ops::Deref;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use bollard::Docker;
 use clap::crate_version;
-use hyper::client::connect::Connect;
 use lazy_static::lazy_static;
 use regex::Regex;
 use serde::de::{self, Deserializer, Unexpected, Visitor};
 use serde::ser::Serializer;
 use serde::{Deserialize, Serialize};
 
+use crate::config::ConfigManager;
 use crate::data_dir::DataDirectory;
+use crate::docker::ContainerEngine;
+use crate::event::Event;
 use crate::prelude::*;
 use crate::repository::RepositoryManager;
 
+pub mod bundle;
+pub mod config;
 pub mod data_dir;
 pub mod docker;
+pub mod doctor;
 pub mod error;
+pub mod event;
+pub mod export;
 pub mod ops;
 pub mod prelude;
 pub mod problem;
@@ -37,32 +44,63 @@ pub trait Printer {
     fn get_current_handle(&mut self) -> Self::Handle;
     fn write_line_at(&mut self, handle: &Self::Handle, message: &str);
     fn write_line(&mut self, message: &str);
+
+    // Printers render events as text lines unless they override these
+    fn report(&mut self, event: &Event) {
+        self.write_line(&event.to_string());
+    }
+
+    fn report_at(&mut self, handle: &Self::Handle, event: &Event) {
+        self.write_line_at(handle, &event.to_string());
+    }
+
+    // Structured result of a command, which only machine-readable printers render
+    fn write_document(&mut self, _document: &impl Serialize) {}
+
+    // Machine-readable printers are read by scripts, which cannot answer questions
+    fn is_interactive(&self) -> bool {
+        true
+    }
 }
 
-pub struct Environment<'a, C: 'static, P: Printer + 'static> {
+pub struct Environment<'a, E: 'static, P: Printer + 'static> {
     username: NameString,
     repo_manager: RepositoryManager<'a>,
-    docker: Docker<C>,
+    config_manager: ConfigManager<'a>,
+    engine: E,
     printer: RefCell<P>,
 }
 
-impl<'a, C, P> Environment<'a, C, P>
+impl<'a, E, P> Environment<'a, E, P>
 where
-    C: Connect,
+    E: ContainerEngine,
     P: Printer,
 {
     pub fn new(
         username: NameString,
-        data_dir: &'a mut DataDirectory,
-        docker: Docker<C>,
+        data_dir: &'a DataDirectory,
+        engine: E,
         printer: P,
-    ) -> SomaResult<Environment<'a, C, P>> {
+    ) -> SomaResult<Environment<'a, E, P>> {
+        let config_manager = data_dir.register::<ConfigManager>()?;
+        Environment::with_config(username, data_dir, config_manager, engine, printer)
+    }
+
+    // The configuration is registered separately when it is needed to set up the engine
+    pub fn with_config(
+        username: NameString,
+        data_dir: &'a DataDirectory,
+        config_manager: ConfigManager<'a>,
+        engine: E,
+        printer: P,
+    ) -> SomaResult<Environment<'a, E, P>> {
         let repo_manager = data_dir.register::<RepositoryManager>()?;
 
         Ok(Environment {
             username,
             repo_manager,
-            docker,
+            config_manager,
+            engine,
             printer: RefCell::new(printer),
         })
     }
@@ -71,10 +109,18 @@ where
         &self.username
     }
 
+    pub fn engine(&self) -> &E {
+        &self.engine
+    }
+
     pub fn printer(&self) -> RefMut<P> {
         self.printer.borrow_mut()
     }
 
+    pub fn report(&self, event: Event) {
+        self.printer().report(&event);
+    }
+
     pub fn repo_manager(&self) -> &RepositoryManager<'a> {
         &self.repo_manager
     }
@@ -82,6 +128,14 @@ where
     pub fn repo_manager_mut(&mut self) -> &mut RepositoryManager<'a> {
         &mut self.repo_manager
     }
+
+    pub fn config_manager(&self) -> &ConfigManager<'a> {
+        &self.config_manager
+    }
+
+    pub fn config_manager_mut(&mut self) -> &mut ConfigManager<'a> {
+        &mut self.config_manager
+    }
 }
 
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -219,6 +273,29 @@ fn read_file_contents(path: impl AsRef<Path>) -> SomaResult<Vec<u8>> {
     Ok(contents)
 }
 
+// Maps relative paths of files under the directory to their contents, skipping version control metadata
+fn read_dir_contents(root: impl AsRef<Path>) -> SomaResult<BTreeMap<PathBuf, Vec<u8>>> {
+    let mut contents = BTreeMap::new();
+    let mut pending = vec![root.as_ref().to_owned()];
+    while let Some(dir_path) = pending.pop() {
+        for entry in fs::read_dir(&dir_path)? {
+            let entry = entry?;
+            if entry.file_name() == ".git" {
+                continue;
+            }
+
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                let relative_path = path.strip_prefix(&root)?.to_owned();
+                contents.insert(relative_path, read_file_contents(&path)?);
+            }
+        }
+    }
+    Ok(contents)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,26 +1,49 @@
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
-use std::fs;
-use std::path::Path;
+use std::fmt::{self, Display};
+use std::fs::{self, File};
+use std::io::{Seek, SeekFrom, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use failure::{format_err, Error};
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use fs_extra::{dir, file};
-use futures::Future;
+use futures::{future, stream, Future, Stream};
 use handlebars::Handlebars;
-use hyper::client::connect::Connect;
-use tempfile::tempdir;
+use serde::Serialize;
+use tempfile::{tempdir, tempdir_in, tempfile, TempDir};
 use tokio::runtime::current_thread::Runtime;
 
-use crate::docker;
+use crate::bundle::{BundleWriter, UnpackedBundle};
+use crate::config::{PortRange, DEFAULT_BIND_ADDRESS};
+use crate::docker::{
+    self, ContainerEngine, DockerLabel, ExecOutput, NetworkSummary, RestartPolicy, SomaContainer,
+    SomaImage, VersionStatus, STATES_TO_STOP,
+};
+use crate::event::{short_id, Event, Stage};
+use crate::export::{self, ExportManifest, ImageConfig, ROOTFS_FILE_NAME};
 use crate::prelude::*;
-use crate::problem::configs::SolidBinaryConfig;
-use crate::problem::Problem;
-use crate::repository::backend;
-use crate::template::{HandleBarsExt, Templates};
+use crate::problem::configs::{Healthcheck, SolidBinaryConfig, SolidDockerConfig};
+use crate::problem::{is_pinned, Problem, SolidManifest};
+use crate::repository::backend::{self, ArchiveBackend};
+use crate::repository::{FetchedUpdate, UpdateDiff};
+use crate::template::{HandleBarsExt, TemplateOverrides, Templates};
 use crate::{Environment, NameString, Printer};
 
+const DOCKERFILE_NAME: &str = "Dockerfile";
+const BUILD_LOG_DIR_NAME: &str = "build-logs";
+const HEALTHCHECK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+// Containers without a healthcheck command have to keep running this long to be healthy
+const STARTUP_GRACE_PERIOD: Duration = Duration::from_secs(3);
+// Number of log lines reported when a problem container fails to start
+const UNHEALTHY_LOG_LINES: usize = 10;
+
 pub fn add(
-    env: &mut Environment<impl Connect, impl Printer>,
+    env: &mut Environment<impl ContainerEngine, impl Printer>,
     repo_location: &str,
     repo_name: Option<&NameString>,
 ) -> SomaResult<()> {
@@ -37,50 +60,317 @@ pub fn add(
     let mut repository = env.repo_manager().get_repo(&repo_name)?;
     repository.update(&[])?;
 
-    env.printer()
-        .write_line(&format!("Repository added: '{}'", &repo_name));
+    env.report(Event::RepositoryAdded(repo_name.clone()));
 
     Ok(())
 }
 
 pub fn fetch(
-    env: &Environment<impl Connect, impl Printer>,
+    env: &Environment<impl ContainerEngine, impl Printer>,
     prob_query: &str,
     cwd: impl AsRef<Path>,
+    runtime: &mut Runtime,
 ) -> SomaResult<()> {
     let problem = env.repo_manager().search_prob(prob_query)?;
     let manifest = problem.load_manifest()?;
+    // Solidifying validates that public files stay inside the problem directory
+    let solid_manifest = manifest.solidify()?;
 
     manifest
         .public_files()
         .into_iter()
-        .try_for_each(|public_file_path| {
+        .try_for_each(|public_file_path| -> SomaResult<()> {
             let file_path = problem.path().join(public_file_path);
             let file_name = file_path.file_name().ok_or(SomaError::FileNameNotFound)?;
 
-            env.printer()
-                .write_line(&format!("Fetching '{}'...", file_name.to_string_lossy()));
+            env.report(Event::FileFetching(
+                file_name.to_string_lossy().into_owned(),
+            ));
             fs::copy(&file_path, cwd.as_ref().join(file_name))?;
             Ok(())
-        })
+        })?;
+
+    // Compiled artifacts only exist inside of the problem image
+    if let Some(build_config) = solid_manifest.build() {
+        let image_name = problem.docker_image_name(env.username());
+        let mut public_artifacts = build_config.public_artifacts().peekable();
+        if public_artifacts.peek().is_some() {
+            let images = runtime.block_on(docker::list_images(env))?;
+            if !docker::image_exists(&images, &image_name) {
+                Err(SomaError::ImageNotFound)?;
+            }
+        }
+
+        for artifact_path in public_artifacts {
+            let file_name = artifact_path
+                .file_name()
+                .ok_or(SomaError::FileNameNotFound)?;
+
+            env.report(Event::FileFetching(
+                file_name.to_string_lossy().into_owned(),
+            ));
+            let mut archive = Vec::new();
+            runtime.block_on(docker::download_file(
+                env,
+                &image_name,
+                artifact_path,
+                &mut archive,
+            ))?;
+            tar::Archive::new(archive.as_slice()).unpack(cwd.as_ref())?;
+        }
+    }
+
+    Ok(())
 }
 
 pub fn build(
-    env: &Environment<impl Connect, impl Printer>,
+    env: &Environment<impl ContainerEngine, impl Printer>,
     prob_query: &str,
+    pull: bool,
     runtime: &mut Runtime,
 ) -> SomaResult<()> {
     let problem = env.repo_manager().search_prob(prob_query)?;
+    build_problem(env, &problem, pull, runtime)
+}
 
-    runtime.block_on(docker::prune_images_from_prob(&env, &problem))?;
-    build_image(&env, &problem, runtime)?;
-    env.printer().write_line(&format!(
-        "Built image for problem: '{}'",
-        problem.fully_qualified_name()
-    ));
+// Builds up to jobs problems concurrently, keeping a progress line and a log file for each problem
+pub fn build_batch(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problems: &[Problem],
+    pull: bool,
+    jobs: usize,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    if let [problem] = problems {
+        return build_problem(env, problem, pull, runtime);
+    }
+
+    let log_dir = build_log_dir(env);
+    fs::create_dir_all(&log_dir)?;
+    let mut progresses = problems
+        .iter()
+        .map(|problem| BuildProgress::new(env, problem, &log_dir))
+        .collect::<SomaResult<Vec<_>>>()?;
+
+    // Build contexts are prepared one by one, and only the engine builds run concurrently
+    let mut prepared_jobs: Vec<_> = progresses
+        .iter_mut()
+        .zip(problems)
+        .map(|(progress, problem)| {
+            runtime.block_on(docker::prune_images_from_prob(env, problem))?;
+            prepare_build(
+                env,
+                problem,
+                pull,
+                &mut |event| progress.report(env, event),
+                runtime,
+            )
+        })
+        .collect();
+
+    let builds: Vec<_> = progresses
+        .iter_mut()
+        .zip(problems)
+        .zip(prepared_jobs.iter_mut())
+        .enumerate()
+        .filter_map(|(index, ((progress, problem), job))| match job {
+            Ok(job) => Some((index, progress, problem, job)),
+            Err(_) => None,
+        })
+        .map(|(index, progress, problem, job)| {
+            progress.report(env, Event::StageStarted(Stage::WaitingForEngine));
+            let job: &BuildJob = job;
+            future::result(job.build_context.try_clone())
+                .from_err()
+                .and_then(move |build_context| {
+                    docker::build_with_output(
+                        env,
+                        job.labels(env, problem),
+                        &job.image_name,
+                        &job.dockerfile,
+                        job.pull,
+                        build_context,
+                        move |event| progress.log_output(env, event),
+                    )
+                })
+                .then(move |result| Ok::<_, Error>((index, result)))
+        })
+        .collect();
+    let mut build_errors: HashMap<_, _> = runtime
+        .block_on(
+            stream::iter_ok(builds)
+                .buffer_unordered(jobs.max(1))
+                .collect(),
+        )?
+        .into_iter()
+        .filter_map(|(index, result)| result.err().map(|error| (index, error)))
+        .collect();
+
+    let results = progresses
+        .iter_mut()
+        .zip(problems)
+        .zip(prepared_jobs)
+        .enumerate()
+        .map(|(index, ((progress, problem), job))| {
+            let result = job.and_then(|_| match build_errors.remove(&index) {
+                Some(error) => Err(error),
+                None => Ok(()),
+            });
+
+            let outcome = match result {
+                Ok(_) => Ok("built".to_owned()),
+                Err(error) => {
+                    progress.log(&error.to_string());
+                    Err(format_err!(
+                        "{}; See '{}' for the build log",
+                        error,
+                        progress.log_path.display()
+                    ))
+                }
+            };
+            progress.report(
+                env,
+                Event::BuildFinished {
+                    succeeded: outcome.is_ok(),
+                },
+            );
+            (problem.fully_qualified_name(), outcome)
+        })
+        .collect();
+
+    print_summary(env, results)
+}
+
+fn build_log_dir(env: &Environment<impl ContainerEngine, impl Printer>) -> PathBuf {
+    env.repo_manager()
+        .data_dir()
+        .root_path()
+        .join(BUILD_LOG_DIR_NAME)
+}
+
+// A printer line showing the current step of a build and a log file with the full output
+struct BuildProgress<H> {
+    prob_name: String,
+    handle: H,
+    log: File,
+    log_path: PathBuf,
+}
+
+impl<H> BuildProgress<H> {
+    fn new<E, P>(
+        env: &Environment<E, P>,
+        problem: &Problem,
+        log_dir: &Path,
+    ) -> SomaResult<BuildProgress<H>>
+    where
+        E: ContainerEngine,
+        P: Printer<Handle = H>,
+    {
+        let prob_name = problem.fully_qualified_name();
+        let log_path = log_dir.join(format!("{}.log", prob_name));
+        let mut printer = env.printer();
+        let handle = printer.get_current_handle();
+        printer.report_at(
+            &handle,
+            &Event::StageStarted(Stage::Queued).in_progress_of(prob_name.as_str()),
+        );
+
+        Ok(BuildProgress {
+            prob_name,
+            handle,
+            log: File::create(&log_path)?,
+            log_path,
+        })
+    }
+
+    fn log(&mut self, message: &str) {
+        if writeln!(self.log, "{}", message).is_err() {
+            eprintln!("Failed to write the build log of '{}'", self.prob_name);
+        }
+    }
+
+    fn report(
+        &mut self,
+        env: &Environment<impl ContainerEngine, impl Printer<Handle = H>>,
+        event: Event,
+    ) {
+        self.log(&event.to_string());
+        env.printer()
+            .report_at(&self.handle, &event.in_progress_of(self.prob_name.as_str()));
+    }
+
+    // Only build steps replace the printer line, while every line goes to the log
+    fn log_output(
+        &mut self,
+        env: &Environment<impl ContainerEngine, impl Printer<Handle = H>>,
+        event: Event,
+    ) {
+        if let Event::BuildStep { .. } = event {
+            self.report(env, event);
+        } else {
+            self.log(&event.to_string());
+        }
+    }
+}
+
+fn build_problem(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    pull: bool,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    runtime.block_on(docker::prune_images_from_prob(env, problem))?;
+    build_image(env, problem, pull, runtime)?;
+    env.report(Event::ImageBuilt(problem.fully_qualified_name()));
     Ok(())
 }
 
+// Rebuilds every problem whose image does not match the current problem contents
+pub fn build_outdated(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    pull: bool,
+    jobs: usize,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    let images = runtime.block_on(docker::list_images(env))?;
+    let problems = outdated_problems(env, &images)?;
+    if problems.is_empty() {
+        env.report(Event::NoOutdatedImage);
+        return Ok(());
+    }
+
+    build_batch(env, &problems, pull, jobs, runtime)
+}
+
+// Problems with a built image that is out of date with the repository
+pub fn outdated_problems(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    images: &[SomaImage],
+) -> SomaResult<Vec<Problem>> {
+    let mut problems = Vec::new();
+    for problem in env.repo_manager().list_prob() {
+        if let Some(image) = docker::find_image(images, &problem.docker_image_name(env.username()))
+        {
+            if image.is_outdated(&content_hash(env, &problem)?) {
+                problems.push(problem);
+            }
+        }
+    }
+    Ok(problems)
+}
+
+// Repository templates are part of the build, so changing them outdates the images
+fn content_hash(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+) -> SomaResult<String> {
+    let overrides = env
+        .repo_manager()
+        .get_repo(problem.repo_name())?
+        .template_overrides()?;
+    problem.content_hash(&overrides)
+}
+
 fn construct_image_root(
     image_root: impl AsRef<Path>,
     problem_dir: impl AsRef<Path>,
@@ -112,87 +402,651 @@ fn construct_image_root(
     Ok(())
 }
 
-fn encode_context(path: impl AsRef<Path>) -> SomaResult<Vec<u8>> {
-    let compressor = GzEncoder::new(Vec::new(), Compression::default());
+// Build contexts are kept in a temporary file, since they can hold whole problem filesystems
+fn encode_context(path: impl AsRef<Path>) -> SomaResult<File> {
+    let compressor = GzEncoder::new(tempfile()?, Compression::default());
     let mut tar = tar::Builder::new(compressor);
     tar.append_dir_all("", path)?;
     tar.finish()?;
-    let compressor = tar.into_inner()?;
-    Ok(compressor.finish()?)
+    let mut build_context = tar.into_inner()?.finish()?;
+    build_context.seek(SeekFrom::Start(0))?;
+    Ok(build_context)
+}
+
+// Everything needed to send a problem to the container engine
+struct BuildJob {
+    image_name: String,
+    dockerfile: String,
+    // Base images of binary problems are pulled beforehand, and pulling again could move the tag
+    pull: bool,
+    build_context: File,
+    build_info: BuildInfo,
+}
+
+// Labels describing how the image is built, which are resolved before the build
+struct BuildInfo {
+    base_digest: Option<String>,
+    libc_version: Option<String>,
+    content_hash: String,
+}
+
+impl BuildJob {
+    fn labels<'a>(
+        &'a self,
+        env: &'a Environment<impl ContainerEngine, impl Printer>,
+        problem: &'a Problem,
+    ) -> DockerLabel<'a> {
+        let mut labels = docker::docker_labels(env, problem);
+        docker::append_build_info(
+            &mut labels,
+            self.build_info.base_digest.as_deref(),
+            self.build_info.libc_version.as_deref(),
+        );
+        docker::append_content_hash(&mut labels, &self.build_info.content_hash);
+        labels
+    }
 }
 
 fn build_image(
-    env: &Environment<impl Connect, impl Printer>,
+    env: &Environment<impl ContainerEngine, impl Printer>,
     problem: &Problem,
+    pull: bool,
     runtime: &mut Runtime,
 ) -> SomaResult<()> {
-    let image_name = problem.docker_image_name(env.username());
+    let mut report = |event| env.report(event);
+    let job = prepare_build(env, problem, pull, &mut report, runtime)?;
 
-    env.printer().write_line("Preparing build context...");
-    let context = tempdir()?;
-    let context_path = context.path();
+    report(Event::StageStarted(Stage::BuildingImage));
+    runtime.block_on(docker::build(
+        env,
+        job.labels(env, problem),
+        &job.image_name,
+        &job.dockerfile,
+        job.pull,
+        job.build_context.try_clone()?,
+    ))
+}
 
-    env.printer().write_line("Loading manifest...");
+fn prepare_build(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    pull: bool,
+    report: &mut dyn FnMut(Event),
+    runtime: &mut Runtime,
+) -> SomaResult<BuildJob> {
+    report(Event::StageStarted(Stage::LoadingManifest));
     let manifest = problem.load_manifest()?.solidify()?;
 
-    env.printer().write_line("Constructing image root...");
+    let (dockerfile, build_context) = match manifest.docker() {
+        Some(docker_config) => (
+            docker_config.dockerfile().to_owned(),
+            prepare_custom_context(problem, docker_config, report)?,
+        ),
+        None => (
+            DOCKERFILE_NAME.to_owned(),
+            prepare_binary_context(env, problem, &manifest, report)?,
+        ),
+    };
+
+    let base_image = manifest.binary().map(|binary| binary.os());
+    let build_info = resolve_build_info(env, problem, base_image, pull, report, runtime)?;
+    Ok(BuildJob {
+        image_name: problem.docker_image_name(env.username()),
+        dockerfile,
+        pull: pull && base_image.is_none(),
+        build_context,
+        build_info,
+    })
+}
+
+// Resolves the digest and the libc version of the base image for reproducibility
+// The base image is pulled beforehand and the build does not pull it again,
+// so that the build uses the inspected one
+fn resolve_build_info(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    base_image: Option<&str>,
+    pull: bool,
+    report: &mut dyn FnMut(Event),
+    runtime: &mut Runtime,
+) -> SomaResult<BuildInfo> {
+    report(Event::StageStarted(Stage::InspectingImage));
+    let (base_digest, libc_version) = match base_image {
+        Some(base_image) => {
+            if pull
+                || runtime
+                    .block_on(env.engine().inspect_image(base_image))
+                    .is_err()
+            {
+                runtime.block_on(docker::pull(env, base_image, &mut *report))?;
+            }
+            (
+                runtime.block_on(docker::image_digest(env, base_image))?,
+                runtime.block_on(docker::libc_version(env, base_image))?,
+            )
+        }
+        None => (None, None),
+    };
+
+    match (base_image, &base_digest) {
+        (Some(base_image), _) if is_pinned(base_image) => (),
+        (Some(base_image), Some(base_digest)) => report(Event::Warning(format!(
+            "base image '{}' is not pinned by digest; resolved to '{}'",
+            base_image, base_digest
+        ))),
+        _ => (),
+    }
+    if let Some(libc_version) = &libc_version {
+        report(Event::LibcDetected(libc_version.to_owned()));
+    }
+
+    Ok(BuildInfo {
+        base_digest,
+        libc_version,
+        content_hash: content_hash(env, problem)?,
+    })
+}
+
+fn prepare_binary_context(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    manifest: &SolidManifest,
+    report: &mut dyn FnMut(Event),
+) -> SomaResult<File> {
+    report(Event::StageStarted(Stage::PreparingBuildContext));
+    let context = tempdir()?;
+    let context_path = context.path();
+
+    report(Event::StageStarted(Stage::ConstructingImageRoot));
     let image_root = context_path.join("image-root");
     let problem_dir = problem.path();
     fs::create_dir(&image_root)?;
-    let binary_config = manifest.binary();
+    let binary_config = manifest.binary().ok_or(SomaError::InvalidManifest)?;
     construct_image_root(image_root, problem_dir, binary_config)?;
 
-    env.printer().write_line("Rendering build files...");
+    if let Some(build_config) = manifest.build() {
+        report(Event::StageStarted(Stage::CopyingBuildSource));
+        let source_path = problem_dir.join(build_config.source());
+        if !source_path.is_dir() {
+            Err(SomaError::FileUnreachable)?;
+        }
+        let mut dir_copy_options = dir::CopyOptions::new();
+        dir_copy_options.copy_inside = true;
+        dir::copy(
+            source_path,
+            context_path.join("build-source"),
+            &dir_copy_options,
+        )?;
+    }
+
+    report(Event::StageStarted(Stage::RenderingBuildFiles));
     fs::create_dir(context_path.join(".soma"))?;
-    Handlebars::new().render_templates(Templates::Binary, &manifest, context_path)?;
+    let overrides = env
+        .repo_manager()
+        .get_repo(problem.repo_name())?
+        .template_overrides()?;
+    Handlebars::new().render_templates(Templates::Binary, &overrides, manifest, context_path)?;
 
-    env.printer().write_line("Encoding build context...");
+    report(Event::StageStarted(Stage::EncodingBuildContext));
     let build_context = encode_context(context_path)?;
 
     context.close()?;
-    env.printer().write_line("Building image...");
-    let labels = docker::docker_labels(&env, &problem);
-    runtime.block_on(docker::build(&env, labels, &image_name, build_context))?;
+    Ok(build_context)
+}
+
+fn prepare_custom_context(
+    problem: &Problem,
+    docker_config: &SolidDockerConfig,
+    report: &mut dyn FnMut(Event),
+) -> SomaResult<File> {
+    report(Event::StageStarted(Stage::EncodingBuildContext));
+    let context_path = problem.path().join(docker_config.context());
+    if !context_path.join(docker_config.dockerfile()).is_file() {
+        Err(SomaError::FileUnreachable)?;
+    }
+    encode_context(context_path)
+}
+
+pub fn export(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    prob_query: &str,
+    output_path: impl AsRef<Path>,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    let problem = env.repo_manager().search_prob(prob_query)?;
+    let manifest = problem.load_manifest()?;
+    manifest.solidify()?;
+    let image_name = problem.docker_image_name(env.username());
+
+    let images = runtime.block_on(docker::list_images(env))?;
+    let image = docker::find_image(&images, &image_name).ok_or(SomaError::ImageNotFound)?;
+    let (export_manifest, rootfs) = export_image(env, &problem, image, runtime)?;
+
+    env.report(Event::StageStarted(Stage::WritingArchive));
+    export::write_archive(
+        File::create(output_path)?,
+        &export_manifest,
+        problem.path(),
+        &manifest.public_files(),
+        rootfs,
+    )?;
+    env.report(Event::ProblemExported(problem.fully_qualified_name()));
+
+    Ok(())
+}
+
+fn export_image(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    image: &SomaImage,
+    runtime: &mut Runtime,
+) -> SomaResult<(ExportManifest, File)> {
+    let image_name = problem.docker_image_name(env.username());
+
+    env.report(Event::StageStarted(Stage::InspectingImage));
+    let config = runtime.block_on(docker::inspect_config(env, &image_name))?;
+    let export_manifest = ExportManifest::new(
+        problem.repo_name().clone(),
+        problem.prob_name().clone(),
+        ImageConfig::new(config, image.base_digest(), image.libc_version()),
+    );
+
+    // The filesystem is written to a temporary file instead of memory
+    env.report(Event::StageStarted(Stage::ExportingImageFilesystem));
+    let mut rootfs = tempfile()?;
+    runtime.block_on(docker::download_file(
+        env,
+        &image_name,
+        Path::new("/"),
+        &rootfs,
+    ))?;
+    rootfs.seek(SeekFrom::Start(0))?;
+
+    Ok((export_manifest, rootfs))
+}
+
+pub fn import(
+    env: &mut Environment<impl ContainerEngine, impl Printer>,
+    archive_path: impl AsRef<Path>,
+    repo_name: Option<&NameString>,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    env.report(Event::StageStarted(Stage::ReadingArchive));
+    let context = tempdir()?;
+    let export_manifest = export::read_archive(
+        &archive_path,
+        File::create(context.path().join(ROOTFS_FILE_NAME))?,
+    )?;
+    let repo_name = repo_name.unwrap_or_else(|| export_manifest.repo_name());
+
+    let backend = ArchiveBackend::new(archive_path.as_ref().canonicalize()?);
+    env.repo_manager_mut()
+        .add_repo(repo_name, Box::new(backend))?;
+    let mut repository = env.repo_manager().get_repo(repo_name)?;
+    repository.update(&[])?;
+
+    // The repository is not usable without the problem image
+    let prob_query = format!("{}.{}", repo_name, export_manifest.prob_name());
+    if let Err(error) = import_image(env, &prob_query, &export_manifest, context, runtime) {
+        env.repo_manager_mut().remove_repo(repo_name)?;
+        return Err(error);
+    }
+
+    env.report(Event::ProblemImported(prob_query));
+
+    Ok(())
+}
+
+// The context directory already contains the exported filesystem
+fn import_image(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    prob_query: &str,
+    export_manifest: &ExportManifest,
+    context: TempDir,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    let problem = env.repo_manager().search_prob(prob_query)?;
+    let image_name = problem.docker_image_name(env.username());
+    let image_config = export_manifest.image();
+
+    env.report(Event::StageStarted(Stage::PreparingBuildContext));
+    let context_path = context.path();
+    Handlebars::new().render_templates(
+        Templates::Import,
+        &TemplateOverrides::new(),
+        &image_config.solidify()?,
+        context_path,
+    )?;
+
+    env.report(Event::StageStarted(Stage::EncodingBuildContext));
+    let build_context = encode_context(context_path)?;
+
+    context.close()?;
+    env.report(Event::StageStarted(Stage::LoadingImage));
+    let content_hash = content_hash(env, &problem)?;
+    let mut labels = docker::docker_labels(env, &problem);
+    docker::append_build_info(
+        &mut labels,
+        image_config.base_digest().map(String::as_str),
+        image_config.libc_version().map(String::as_str),
+    );
+    docker::append_content_hash(&mut labels, &content_hash);
+    runtime.block_on(docker::build(
+        env,
+        labels,
+        &image_name,
+        DOCKERFILE_NAME,
+        false,
+        build_context,
+    ))?;
     Ok(())
 }
 
+pub fn bundle_create(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    repo_names: &[NameString],
+    output_path: impl AsRef<Path>,
+    include_images: bool,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    let mut writer = BundleWriter::new(File::create(output_path)?);
+    writer.append_index(&env.repo_manager().bundle_index(repo_names)?)?;
+
+    for repo_name in repo_names {
+        env.report(Event::RepositoryPacking(repo_name.clone()));
+        let repository = env.repo_manager().get_repo(repo_name)?;
+        writer.append_repository(repo_name, repository.path())?;
+    }
+
+    if include_images {
+        let images = runtime.block_on(docker::list_images(env))?;
+        let problems = env
+            .repo_manager()
+            .list_prob()
+            .filter(|problem| repo_names.contains(problem.repo_name()));
+        for problem in problems {
+            let image_name = problem.docker_image_name(env.username());
+            if let Some(image) = docker::find_image(&images, &image_name) {
+                env.report(Event::ImagePacking(problem.fully_qualified_name()));
+                let (export_manifest, rootfs) = export_image(env, &problem, image, runtime)?;
+                writer.append_image(&export_manifest, rootfs)?;
+            }
+        }
+    }
+
+    writer.finish()?;
+    env.report(Event::BundleCreated);
+
+    Ok(())
+}
+
+pub fn bundle_load(
+    env: &mut Environment<impl ContainerEngine, impl Printer>,
+    bundle_path: impl AsRef<Path>,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    env.report(Event::StageStarted(Stage::UnpackingBundle));
+    let temp_dir = tempdir()?;
+    let bundle = UnpackedBundle::unpack(bundle_path, temp_dir.path())?;
+
+    let repo_names = env
+        .repo_manager_mut()
+        .restore_repos(&bundle.index()?, bundle.repository_root())?;
+    for repo_name in &repo_names {
+        env.report(Event::RepositoryLoaded(repo_name.clone()));
+    }
+
+    for (export_manifest, rootfs_path) in bundle.images()? {
+        let prob_query = format!(
+            "{}.{}",
+            export_manifest.repo_name(),
+            export_manifest.prob_name()
+        );
+        // Moving the filesystem within the unpacked bundle avoids copying it
+        let context = tempdir_in(temp_dir.path())?;
+        fs::rename(rootfs_path, context.path().join(ROOTFS_FILE_NAME))?;
+        import_image(env, &prob_query, &export_manifest, context, runtime)?;
+        env.report(Event::ImageLoaded(prob_query));
+    }
+
+    Ok(())
+}
+
+pub struct RunOptions {
+    pub bind_address: IpAddr,
+    // Runs the problem on an internal network even if the manifest does not ask for it
+    pub internal: bool,
+    // Named instances of a problem run alongside each other
+    pub instance: Option<NameString>,
+    // Overrides the restart policy of the manifest
+    pub restart_policy: Option<RestartPolicy>,
+}
+
+impl RunOptions {
+    pub fn instance(&self) -> &str {
+        self.instance
+            .as_ref()
+            .map_or(docker::DEFAULT_INSTANCE, |instance| instance.as_str())
+    }
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            bind_address: DEFAULT_BIND_ADDRESS,
+            internal: false,
+            instance: None,
+            restart_policy: None,
+        }
+    }
+}
+
 pub fn run(
-    env: &Environment<impl Connect, impl Printer>,
+    env: &Environment<impl ContainerEngine, impl Printer>,
     prob_query: &str,
     port: u32,
+    options: &RunOptions,
     runtime: &mut Runtime,
 ) -> SomaResult<String> {
     let problem = env.repo_manager().search_prob(prob_query)?;
+    run_problem(env, &problem, port, options, runtime)
+}
+
+// Runs each problem on the next port from ports, such as consecutive ports from 31337..
+pub fn run_batch(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problems: &[Problem],
+    ports: impl IntoIterator<Item = u32>,
+    options: &RunOptions,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    let mut ports = ports.into_iter();
+    for_each_problem(env, problems, |problem| {
+        let port = ports.next().ok_or(SomaError::NoAvailablePort)?;
+        run_problem(env, problem, port, options, runtime).map(|_| match &options.instance {
+            Some(instance) => format!("running instance '{}' on port {}", instance, port),
+            None => format!("running on port {}", port),
+        })
+    })
+}
+
+// Ports in the range that are not published by any problem container
+pub fn available_ports(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    port_range: PortRange,
+    runtime: &mut Runtime,
+) -> SomaResult<Vec<u32>> {
+    // Proxies publish the ports of problems on internal networks
+    let containers = runtime.block_on(docker::list_containers(env))?;
+    let proxies = runtime.block_on(docker::list_proxies(env))?;
+    let used_ports: HashSet<_> = containers
+        .iter()
+        .chain(&proxies)
+        .flat_map(|container| &container.container().ports)
+        .filter_map(|port| port.public_port)
+        .collect();
+
+    Ok(port_range
+        .ports()
+        .filter(|&port| !used_ports.contains(&i64::from(port)))
+        .collect())
+}
+
+fn run_problem(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    port: u32,
+    options: &RunOptions,
+    runtime: &mut Runtime,
+) -> SomaResult<String> {
+    let instance = options.instance();
     let image_name = problem.docker_image_name(env.username());
+    let network_name = problem.docker_network_name(env.username(), instance);
+    let manifest = problem.load_manifest()?;
+    let container_port = &format!("{}/tcp", manifest.container_port());
+    let internal = manifest.internal() || options.internal;
+    let healthcheck = manifest.healthcheck();
+    let restart_policy = options
+        .restart_policy
+        .unwrap_or_else(|| manifest.restart_policy());
+    let host_ip = &options.bind_address.to_string();
     let port_str = &port.to_string();
 
-    let containers = runtime.block_on(docker::list_containers(&env))?;
-    if docker::container_from_prob_running(&containers, &problem) {
+    let containers = runtime.block_on(docker::list_containers(env))?;
+    if docker::container_from_instance_running(&containers, problem, instance) {
         Err(SomaError::ProblemAlreadyRunning)?
     }
 
-    runtime.block_on(docker::prune_containers_from_prob(&env, &problem))?;
-
-    let labels = docker::docker_labels(env, &problem);
-    let container_run =
-        docker::create(env, labels, &image_name, port_str).and_then(|container_name| {
-            env.printer().write_line("Starting container...");
-            docker::start(env, &container_name).map(|_| container_name)
-        });
+    runtime.block_on(docker::prune_containers_from_prob(
+        env,
+        problem,
+        Some(instance),
+    ))?;
+    // Networks are left behind by containers that exited without being stopped
+    runtime.block_on(docker::remove_networks_from_prob(
+        env,
+        problem,
+        Some(instance),
+    ))?;
 
-    env.printer().write_line(&format!(
-        "Creating container for problem: '{}'",
-        problem.fully_qualified_name()
+    let mut labels = docker::docker_labels(env, problem);
+    docker::append_instance(&mut labels, instance);
+    docker::append_run_options(&mut labels, host_ip, port_str, restart_policy);
+    let network_created = runtime.block_on(docker::create_network(
+        env,
+        labels.clone(),
+        &network_name,
+        internal,
     ));
+    let network = match network_created {
+        Ok(_) => {
+            env.report(Event::NetworkCreated {
+                name: network_name.clone(),
+                internal,
+            });
+            Some(network_name.as_str())
+        }
+        // Problems which are not internal can still run on the default network
+        Err(ref error)
+            if !internal
+                && matches!(
+                    error.downcast_ref::<SomaError>(),
+                    Some(SomaError::NetworkUnsupported)
+                ) =>
+        {
+            env.report(Event::Warning(format!(
+                "{}, so the problem runs on the default network",
+                error
+            )));
+            None
+        }
+        Err(error) => Err(error)?,
+    };
+
+    // Ports published from internal networks are unreachable, so the proxy publishes it instead
+    let port_binding = (container_port.as_str(), host_ip.as_str(), port_str.as_str());
+    let container_run = docker::create(
+        env,
+        labels.clone(),
+        &image_name,
+        network,
+        if internal { None } else { Some(port_binding) },
+        restart_policy,
+    )
+    .and_then(|container_name| {
+        env.report(Event::StageStarted(Stage::StartingContainer));
+        docker::start(env, &container_name).map(|_| container_name)
+    });
+
+    env.report(Event::ContainerCreating(problem.fully_qualified_name()));
     let container_name = runtime.block_on(container_run)?;
-    env.printer()
-        .write_line(&format!("Container started: '{}'", &container_name));
+    let started = if internal {
+        start_proxy(
+            env,
+            labels,
+            &network_name,
+            &container_name,
+            port_binding,
+            restart_policy,
+            runtime,
+        )
+    } else {
+        Ok(())
+    }
+    .and_then(|_| wait_until_healthy(env, problem, &container_name, &healthcheck, runtime));
+    if let Err(error) = started {
+        remove_unhealthy(env, problem, instance, &container_name, runtime);
+        return Err(error);
+    }
+    env.report(Event::ContainerStarted {
+        problem: problem.fully_qualified_name(),
+        instance: instance.to_owned(),
+        id: container_name.clone(),
+        bind_address: options.bind_address,
+        port,
+    });
 
     Ok(container_name)
 }
 
+// Forwards the port from the default bridge to the problem container on its internal network
+fn start_proxy(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    labels: DockerLabel,
+    network_name: &str,
+    container_id: &str,
+    port_binding: (&str, &str, &str),
+    restart_policy: RestartPolicy,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    env.report(Event::StageStarted(Stage::StartingProxy));
+    if runtime
+        .block_on(env.engine().inspect_image(docker::PROXY_IMAGE))
+        .is_err()
+    {
+        runtime.block_on(docker::pull(env, docker::PROXY_IMAGE, |event| {
+            env.report(event)
+        }))?;
+    }
+
+    let container_port = port_binding.0.trim_end_matches("/tcp");
+    let listen = format!("TCP-LISTEN:{},fork,reuseaddr", container_port);
+    // Engines resolve containers by their short ids on user-defined networks
+    let target = format!("TCP:{}:{}", short_id(container_id), container_port);
+    let proxy_run = docker::create_proxy(
+        env,
+        labels,
+        vec![&listen, &target],
+        port_binding,
+        restart_policy,
+    )
+    .and_then(|proxy_id| docker::connect_network(env, network_name, &proxy_id).map(|_| proxy_id))
+    .and_then(|proxy_id| docker::start(env, &proxy_id));
+    runtime.block_on(proxy_run)
+}
+
 pub fn remove(
-    env: &mut Environment<impl Connect, impl Printer>,
+    env: &mut Environment<impl ContainerEngine, impl Printer>,
     repo_name: &NameString,
     runtime: &mut Runtime,
 ) -> SomaResult<()> {
@@ -202,21 +1056,37 @@ pub fn remove(
     }
 
     env.repo_manager_mut().remove_repo(repo_name)?;
-    env.printer()
-        .write_line(&format!("Repository removed: '{}'", &repo_name));
+    env.report(Event::RepositoryRemoved(repo_name.clone()));
 
     Ok(())
 }
 
 pub fn clean(
-    env: &Environment<impl Connect, impl Printer>,
+    env: &Environment<impl ContainerEngine, impl Printer>,
     prob_query: &str,
     runtime: &mut Runtime,
 ) -> SomaResult<()> {
     let problem = env.repo_manager().search_prob(prob_query)?;
+    clean_problem(env, &problem, runtime)
+}
 
+pub fn clean_batch(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problems: &[Problem],
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    for_each_problem(env, problems, |problem| {
+        clean_problem(env, problem, runtime).map(|_| "cleaned".to_owned())
+    })
+}
+
+fn clean_problem(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
     let container_list = runtime.block_on(docker::list_containers(env))?;
-    if docker::container_from_prob_exists(&container_list, &problem) {
+    if docker::container_from_prob_exists(&container_list, problem) {
         Err(SomaError::RepositoryInUse)?;
     }
 
@@ -224,32 +1094,48 @@ pub fn clean(
         env,
         &problem.docker_image_name(env.username()),
     ))?;
-    env.printer().write_line(&format!(
-        "Problem image cleaned: '{}'",
-        problem.fully_qualified_name()
-    ));
+    env.report(Event::ImageCleaned(problem.fully_qualified_name()));
 
     Ok(())
 }
 
+// Every instance of the problem is stopped when the instance is omitted
 pub fn stop(
-    env: &Environment<impl Connect, impl Printer>,
+    env: &Environment<impl ContainerEngine, impl Printer>,
     prob_query: &str,
+    instance: Option<&str>,
     runtime: &mut Runtime,
 ) -> SomaResult<()> {
     let problem = env.repo_manager().search_prob(prob_query)?;
+    stop_problem(env, &problem, instance, runtime)
+}
 
+pub fn stop_batch(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problems: &[Problem],
+    instance: Option<&str>,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    for_each_problem(env, problems, |problem| {
+        stop_problem(env, problem, instance, runtime).map(|_| "stopped".to_owned())
+    })
+}
+
+fn stop_problem(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    instance: Option<&str>,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
     let container_list = runtime.block_on(docker::list_containers(env))?;
-    if !docker::container_from_prob_exists(&container_list, &problem) {
+    let container_list = docker::containers_from_instance(container_list, problem, instance);
+    if container_list.is_empty() {
         Err(SomaError::ProblemNotRunning)?;
     }
 
-    let container_list = docker::containers_from_prob(container_list, &problem);
-    let states_to_stop = &["paused", "restarting", "running"];
-
     let containers_to_stop = container_list
         .iter()
-        .filter(|container| states_to_stop.contains(&container.container().state.as_str()));
+        .filter(|container| STATES_TO_STOP.contains(&container.container().state.as_str()));
 
     for container in containers_to_stop {
         runtime.block_on(docker::stop(env, &container.container().id))?;
@@ -258,24 +1144,596 @@ pub fn stop(
     for container in container_list {
         runtime.block_on(docker::remove_container(env, &container.container().id))?;
     }
+    runtime.block_on(docker::remove_networks_from_prob(env, problem, instance))?;
 
-    env.printer().write_line(&format!(
-        "Problem stopped: '{}'",
-        problem.fully_qualified_name()
-    ));
+    env.report(Event::ProblemStopped(problem.fully_qualified_name()));
 
     Ok(())
 }
 
+// Names of the instances of the problem which have containers
+pub fn instances(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    runtime: &mut Runtime,
+) -> SomaResult<Vec<String>> {
+    let container_list = runtime.block_on(docker::list_containers(env))?;
+    let mut instances: Vec<_> = docker::containers_from_prob(container_list, problem)
+        .into_iter()
+        .map(|container| container.instance().clone())
+        .collect();
+    instances.sort();
+    instances.dedup();
+    Ok(instances)
+}
+
+// The instance can be omitted when the problem has only one instance
+fn instance_container(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    instance: Option<&str>,
+    runtime: &mut Runtime,
+) -> SomaResult<SomaContainer> {
+    let container_list = runtime.block_on(docker::list_containers(env))?;
+    let mut container_list = docker::containers_from_instance(container_list, problem, instance);
+    match container_list.len() {
+        0 => Err(SomaError::ProblemNotRunning)?,
+        1 => Ok(container_list.remove(0)),
+        _ => Err(SomaError::InstanceNotSpecified)?,
+    }
+}
+
+// Polls until the container passes the healthcheck, or until it has kept running for the grace
+// period without one, since a container that crashes on startup is running on the first poll
+fn wait_until_healthy(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    container_id: &str,
+    healthcheck: &Healthcheck,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    let started = Instant::now();
+    let deadline = started + healthcheck.timeout();
+    let grace_end = started + STARTUP_GRACE_PERIOD.min(healthcheck.timeout());
+    loop {
+        let container_list = runtime.block_on(docker::list_containers(env))?;
+        let running = container_list.iter().any(|container| {
+            container.container().id == container_id && container.container().state == "running"
+        });
+        if !running {
+            break;
+        }
+
+        let healthy = match healthcheck.cmd() {
+            Some(cmd) => {
+                runtime
+                    .block_on(docker::exec(env, container_id, vec!["sh", "-c", cmd]))?
+                    .exit_code
+                    == 0
+            }
+            None => Instant::now() >= grace_end,
+        };
+        if healthy {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            break;
+        }
+        thread::sleep(HEALTHCHECK_POLL_INTERVAL);
+    }
+
+    let logs = runtime.block_on(docker::logs(env, container_id))?;
+    let lines: Vec<_> = logs.lines().collect();
+    let last_lines = &lines[lines.len().saturating_sub(UNHEALTHY_LOG_LINES)..];
+    env.report(Event::ContainerUnhealthy {
+        problem: problem.fully_qualified_name(),
+        logs: last_lines.iter().map(|line| (*line).to_owned()).collect(),
+    });
+    Err(SomaError::ContainerUnhealthy)?
+}
+
+// Leaves nothing behind, so that the problem can run again after a fix
+// Each step is best-effort, so that a failed cleanup does not hide why the container failed
+fn remove_unhealthy(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    instance: &str,
+    container_id: &str,
+    runtime: &mut Runtime,
+) {
+    // Docker refuses to stop a container which already exited
+    let running = runtime
+        .block_on(docker::list_containers(env))
+        .map(|containers| {
+            containers.iter().any(|container| {
+                container.container().id == container_id
+                    && STATES_TO_STOP.contains(&container.container().state.as_str())
+            })
+        })
+        .unwrap_or(true);
+    let stopped = if running {
+        runtime.block_on(docker::stop(env, container_id))
+    } else {
+        Ok(())
+    };
+    let removed = runtime.block_on(docker::remove_container(env, container_id));
+    let networks_removed = runtime.block_on(docker::remove_networks_from_prob(
+        env,
+        problem,
+        Some(instance),
+    ));
+
+    for error in vec![stopped, removed, networks_removed]
+        .into_iter()
+        .filter_map(Result::err)
+    {
+        env.report(Event::Warning(format!(
+            "Failed to clean up the problem container: {}",
+            error
+        )));
+    }
+}
+
+// Starts the container again, or replaces it with a container from the current image
+pub fn restart(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    prob_query: &str,
+    instance: Option<&str>,
+    recreate: bool,
+    runtime: &mut Runtime,
+) -> SomaResult<String> {
+    let problem = env.repo_manager().search_prob(prob_query)?;
+    let container = instance_container(env, &problem, instance, runtime)?;
+    let container_id = &container.container().id;
+    let running = STATES_TO_STOP.contains(&container.container().state.as_str());
+
+    if !recreate {
+        if running {
+            runtime.block_on(docker::stop(env, container_id))?;
+        }
+        runtime.block_on(docker::start(env, container_id))?;
+        let healthcheck = problem.load_manifest()?.healthcheck();
+        wait_until_healthy(env, &problem, container_id, &healthcheck, runtime)?;
+        env.report(Event::ContainerRestarted {
+            problem: problem.fully_qualified_name(),
+            instance: container.instance().clone(),
+            id: container_id.clone(),
+        });
+        return Ok(container_id.clone());
+    }
+
+    let (bind_address, port) = match container.binding() {
+        Some(binding) => binding,
+        None => Err(SomaError::PortBindingUnknown)?,
+    };
+    // Endpoints without networks report none, which leaves the manifest to decide
+    let networks = runtime.block_on(docker::list_networks_from_prob(
+        env,
+        &problem,
+        Some(container.instance()),
+    ))?;
+    let options = RunOptions {
+        bind_address,
+        internal: networks.iter().any(|network| network.internal),
+        instance: Some(NameString::try_from(container.instance().as_str())?),
+        restart_policy: Some(container.restart_policy()),
+    };
+
+    if running {
+        runtime.block_on(docker::stop(env, container_id))?;
+    }
+    runtime.block_on(docker::remove_container(env, container_id))?;
+    run_problem(env, &problem, port, &options, runtime)
+}
+
+pub fn logs(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    prob_query: &str,
+    instance: Option<&str>,
+    runtime: &mut Runtime,
+) -> SomaResult<String> {
+    let problem = env.repo_manager().search_prob(prob_query)?;
+    let container = instance_container(env, &problem, instance, runtime)?;
+    runtime.block_on(docker::logs(env, &container.container().id))
+}
+
+pub fn exec(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    prob_query: &str,
+    instance: Option<&str>,
+    cmd: Vec<&str>,
+    runtime: &mut Runtime,
+) -> SomaResult<ExecOutput> {
+    let problem = env.repo_manager().search_prob(prob_query)?;
+    let container = instance_container(env, &problem, instance, runtime)?;
+    if container.container().state != "running" {
+        Err(SomaError::ProblemNotRunning)?;
+    }
+    runtime.block_on(docker::exec(env, &container.container().id, cmd))
+}
+
+// An update fetched once, with the vanished problems that still have images or containers
+pub struct PendingUpdate {
+    repo_name: NameString,
+    fetched: FetchedUpdate,
+    problems_in_use: Vec<Problem>,
+}
+
+impl PendingUpdate {
+    pub fn problems_in_use(&self) -> &Vec<Problem> {
+        &self.problems_in_use
+    }
+
+    pub fn diff(&self) -> &UpdateDiff {
+        self.fetched.diff()
+    }
+}
+
 pub fn update(
-    env: &Environment<impl Connect, impl Printer>,
+    env: &mut Environment<impl ContainerEngine, impl Printer>,
     repo_name: &NameString,
+    prune: bool,
     runtime: &mut Runtime,
 ) -> SomaResult<()> {
-    let mut repository = env.repo_manager().get_repo(repo_name)?;
-    repository.update(&runtime.block_on(docker::list_images(env))?)?;
-    env.printer()
-        .write_line(&format!("Repository updated: '{}'", repo_name));
+    let pending = prepare_update(env, repo_name, runtime)?;
+    apply_update(env, pending, prune, runtime)
+}
+
+pub fn prepare_update(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    repo_name: &NameString,
+    runtime: &mut Runtime,
+) -> SomaResult<PendingUpdate> {
+    let fetched = env.repo_manager().get_repo(repo_name)?.fetch_update()?;
+    let vanished_problems = fetched
+        .diff()
+        .vanished()
+        .map(|prob_name| {
+            env.repo_manager()
+                .search_prob(&format!("{}.{}", repo_name, prob_name))
+        })
+        .collect::<SomaResult<Vec<_>>>()?;
+
+    let images = runtime.block_on(docker::list_images(env))?;
+    let containers = runtime.block_on(docker::list_containers(env))?;
+    let problems_in_use = vanished_problems
+        .into_iter()
+        .filter(|problem| {
+            docker::image_from_prob_exists(&images, problem)
+                || docker::container_from_prob_exists(&containers, problem)
+        })
+        .collect();
+
+    Ok(PendingUpdate {
+        repo_name: repo_name.clone(),
+        fetched,
+        problems_in_use,
+    })
+}
+
+// Installs exactly the tree that prepare_update inspected
+pub fn apply_update(
+    env: &mut Environment<impl ContainerEngine, impl Printer>,
+    pending: PendingUpdate,
+    prune: bool,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    let PendingUpdate {
+        repo_name,
+        fetched,
+        problems_in_use,
+    } = pending;
+    let repo_name = &repo_name;
+
+    if !problems_in_use.is_empty() {
+        if !prune {
+            Err(SomaError::UnsupportedUpdate)?;
+        }
+        for problem in &problems_in_use {
+            purge_problem(env, problem, runtime)?;
+        }
+    }
+
+    let images = runtime.block_on(docker::list_images(env))?;
+    let diff = env
+        .repo_manager_mut()
+        .update_repo(repo_name, fetched, &images)?;
+
+    let qualify = |prob_name| format!("{}.{}", repo_name, prob_name);
+    for prob_name in diff.added() {
+        env.report(Event::ProblemAdded(qualify(prob_name)));
+    }
+    for prob_name in diff.removed() {
+        env.report(Event::ProblemRemoved(qualify(prob_name)));
+    }
+    for (old_name, new_name) in diff.renamed() {
+        env.report(Event::ProblemRenamed {
+            from: qualify(old_name),
+            to: qualify(new_name),
+        });
+    }
+    for prob_name in diff.changed() {
+        env.report(Event::ProblemChanged(qualify(prob_name)));
+    }
+    if diff.is_empty() {
+        env.report(Event::NoProblemChanged);
+    }
+    env.report(Event::RepositoryUpdated(repo_name.clone()));
+
+    let outdated_problems: Vec<_> = outdated_problems(env, &images)?
+        .into_iter()
+        .filter(|problem| problem.repo_name() == repo_name)
+        .collect();
+    if !outdated_problems.is_empty() {
+        for problem in &outdated_problems {
+            env.report(Event::ImageOutdated(problem.fully_qualified_name()));
+        }
+        env.report(Event::RebuildSuggested);
+    }
+
+    Ok(())
+}
+
+// Stops and removes every container and image of the problem
+fn purge_problem(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problem: &Problem,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    let container_list = runtime.block_on(docker::list_containers(env))?;
+    for container in docker::containers_from_prob(container_list, problem) {
+        let container = container.container();
+        if STATES_TO_STOP.contains(&container.state.as_str()) {
+            runtime.block_on(docker::stop(env, &container.id))?;
+        }
+        runtime.block_on(docker::remove_container(env, &container.id))?;
+    }
+    runtime.block_on(docker::remove_networks_from_prob(env, problem, None))?;
+
+    let images = runtime.block_on(docker::list_images(env))?;
+    if docker::image_exists(&images, &problem.docker_image_name(env.username())) {
+        runtime.block_on(docker::remove_image(
+            env,
+            &problem.docker_image_name(env.username()),
+        ))?;
+    }
+    runtime.block_on(docker::prune_images_from_prob(env, problem))?;
+
+    env.report(Event::ProblemPurged(problem.fully_qualified_name()));
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GarbageReason {
+    Stopped,
+    Dangling,
+    UnknownProblem,
+    VersionMismatch,
+    NoVersionFound,
+    Unused,
+}
+
+impl Display for GarbageReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GarbageReason::Stopped => write!(f, "stopped"),
+            GarbageReason::Dangling => write!(f, "dangling"),
+            GarbageReason::UnknownProblem => write!(f, "unknown problem"),
+            GarbageReason::VersionMismatch => write!(f, "built by another soma version"),
+            GarbageReason::NoVersionFound => write!(f, "no soma version found"),
+            GarbageReason::Unused => write!(f, "no running container"),
+        }
+    }
+}
+
+pub struct Garbage {
+    pub containers: Vec<(SomaContainer, GarbageReason)>,
+    pub images: Vec<(SomaImage, GarbageReason)>,
+    pub networks: Vec<(NetworkSummary, GarbageReason)>,
+}
+
+impl Garbage {
+    pub fn is_empty(&self) -> bool {
+        self.containers.is_empty() && self.images.is_empty() && self.networks.is_empty()
+    }
+
+    // Layers shared between images are counted for each of them, so this is an upper bound
+    pub fn image_size(&self) -> u64 {
+        self.images
+            .iter()
+            .map(|(image, _)| image.image().size)
+            .sum()
+    }
+}
+
+// Running containers and the images and networks they use are never garbage
+pub fn find_garbage(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    runtime: &mut Runtime,
+) -> SomaResult<Garbage> {
+    let known_problems: HashSet<_> = env
+        .repo_manager()
+        .list_prob()
+        .map(|problem| problem.fully_qualified_name())
+        .collect();
+
+    let (containers, kept_containers): (Vec<_>, Vec<_>) = runtime
+        .block_on(docker::list_containers(env))?
+        .into_iter()
+        .partition(|container| !STATES_TO_STOP.contains(&container.container().state.as_str()));
+    // Proxies are garbage once the problem container they publish is gone or stopped
+    let (proxies, kept_proxies): (Vec<_>, Vec<_>) = runtime
+        .block_on(docker::list_proxies(env))?
+        .into_iter()
+        .partition(|proxy| {
+            !STATES_TO_STOP.contains(&proxy.container().state.as_str())
+                || docker::find_same_instance(&kept_containers, proxy).is_none()
+        });
+    let images_in_use: HashSet<_> = kept_containers
+        .iter()
+        .map(|container| container.container().image_id.clone())
+        .collect();
+    let networks_in_use: HashSet<_> = kept_containers
+        .iter()
+        .chain(&kept_proxies)
+        .flat_map(|container| container.container().networks.iter().cloned())
+        .collect();
+
+    let images = runtime
+        .block_on(docker::list_images(env))?
+        .into_iter()
+        .filter(|image| !images_in_use.contains(&image.image().id))
+        .filter_map(|image| {
+            let prob_name = format!("{}.{}", image.repo_name(), image.prob_name());
+            let reason = if image.is_dangling() {
+                GarbageReason::Dangling
+            } else if !known_problems.contains(&prob_name) {
+                GarbageReason::UnknownProblem
+            } else {
+                match image.status() {
+                    VersionStatus::Normal => return None,
+                    VersionStatus::VersionMismatch => GarbageReason::VersionMismatch,
+                    VersionStatus::NoVersionFound => GarbageReason::NoVersionFound,
+                }
+            };
+            Some((image, reason))
+        })
+        .collect();
+
+    // Networks are left behind by containers that exited or were removed without being stopped
+    let networks = runtime
+        .block_on(docker::list_networks(env))?
+        .into_iter()
+        .filter(|network| !networks_in_use.contains(&network.name))
+        .map(|network| (network, GarbageReason::Unused))
+        .collect();
+
+    Ok(Garbage {
+        containers: containers
+            .into_iter()
+            .map(|container| (container, GarbageReason::Stopped))
+            .chain(proxies.into_iter().map(|proxy| {
+                let reason = if STATES_TO_STOP.contains(&proxy.container().state.as_str()) {
+                    GarbageReason::Unused
+                } else {
+                    GarbageReason::Stopped
+                };
+                (proxy, reason)
+            }))
+            .collect(),
+        images,
+        networks,
+    })
+}
+
+pub fn collect_garbage(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    garbage: &Garbage,
+    runtime: &mut Runtime,
+) -> SomaResult<()> {
+    // Every removal is attempted, and failures are reported in a summary at the end
+    let mut results = vec![];
+
+    // Containers go first, since the engine refuses to remove images they use
+    for (container, _) in &garbage.containers {
+        let problem = format!("{}.{}", container.repo_name(), container.prob_name());
+        let id = &container.container().id;
+        // Proxies without a running problem container may still be running
+        let result = if STATES_TO_STOP.contains(&container.container().state.as_str()) {
+            runtime.block_on(docker::stop(env, id))
+        } else {
+            Ok(())
+        }
+        .and_then(|_| runtime.block_on(docker::remove_container(env, id)));
+        if result.is_ok() {
+            env.report(Event::ContainerRemoved {
+                problem: problem.clone(),
+                id: id.clone(),
+            });
+        }
+        results.push((
+            format!("{} (container {})", problem, short_id(id)),
+            result.map(|_| "removed".to_owned()),
+        ));
+    }
+    for (image, _) in &garbage.images {
+        let problem = format!("{}.{}", image.repo_name(), image.prob_name());
+        let id = &image.image().id;
+        let result = runtime.block_on(docker::remove_image(env, id));
+        if result.is_ok() {
+            env.report(Event::ImageRemoved {
+                problem: problem.clone(),
+                id: id.clone(),
+            });
+        }
+        results.push((
+            format!("{} (image {})", problem, short_id(id)),
+            result.map(|_| "removed".to_owned()),
+        ));
+    }
+    for (network, _) in &garbage.networks {
+        let result = runtime.block_on(docker::remove_network(env, &network.id));
+        if result.is_ok() {
+            env.report(Event::NetworkRemoved(network.name.clone()));
+        }
+        results.push((
+            format!("{} (network)", network.name),
+            result.map(|_| "removed".to_owned()),
+        ));
+    }
+
+    if results.iter().any(|(_, result)| result.is_err()) {
+        print_summary(env, results)
+    } else {
+        Ok(())
+    }
+}
 
+// A single problem keeps its own error, while batches report every failure in a summary
+fn for_each_problem(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    problems: &[Problem],
+    mut operation: impl FnMut(&Problem) -> SomaResult<String>,
+) -> SomaResult<()> {
+    if let [problem] = problems {
+        return operation(problem).map(|_| ());
+    }
+
+    let results = problems
+        .iter()
+        .map(|problem| (problem.fully_qualified_name(), operation(problem)))
+        .collect();
+    print_summary(env, results)
+}
+
+fn print_summary(
+    env: &Environment<impl ContainerEngine, impl Printer>,
+    results: Vec<(String, SomaResult<String>)>,
+) -> SomaResult<()> {
+    env.report(Event::SummaryStarted);
+    let failed_count = results.iter().filter(|(_, result)| result.is_err()).count();
+    let succeeded_count = results.len() - failed_count;
+    for (prob_name, result) in results {
+        env.report(match result {
+            Ok(outcome) => Event::ProblemSucceeded {
+                problem: prob_name,
+                outcome,
+            },
+            Err(error) => Event::ProblemFailed {
+                problem: prob_name,
+                error: error.to_string(),
+            },
+        });
+    }
+    env.report(Event::SummaryFinished {
+        succeeded: succeeded_count,
+        failed: failed_count,
+    });
+
+    if failed_count > 0 {
+        Err(SomaError::BatchOperationFailed)?;
+    }
     Ok(())
 }
@@ -1,15 +1,27 @@
 use std::path::{Path, PathBuf};
 
+use path_slash::PathBufExt;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use self::configs::{BinaryConfig, FileEntry, SolidBinaryConfig};
+use self::configs::{
+    BinaryConfig, BuildConfig, DockerConfig, FileEntry, Healthcheck, HealthcheckConfig,
+    SolidBinaryConfig, SolidBuildConfig, SolidDockerConfig,
+};
+use crate::docker::RestartPolicy;
 use crate::prelude::*;
-use crate::{read_file_contents, NameString};
+use crate::template::TemplateOverrides;
+use crate::{read_dir_contents, read_file_contents, NameString};
 
 pub mod configs;
 
+pub use self::configs::is_pinned;
+
 pub const MANIFEST_FILE_NAME: &str = "soma.toml";
 
+// Port number that problem containers listen on unless specified otherwise
+pub const DEFAULT_CONTAINER_PORT: u16 = 1337;
+
 #[derive(Debug)]
 pub struct Problem {
     repo_name: NameString,
@@ -34,6 +46,16 @@ impl Problem {
         format!("soma.{}/{}", user_name, self.fully_qualified_name())
     }
 
+    // Network names cannot contain slashes
+    pub fn docker_network_name(&self, user_name: &str, instance: &str) -> String {
+        format!(
+            "soma.{}.{}.{}",
+            user_name,
+            self.fully_qualified_name(),
+            instance
+        )
+    }
+
     pub fn repo_name(&self) -> &NameString {
         &self.repo_name
     }
@@ -50,20 +72,53 @@ impl Problem {
         let manifest_path = self.path().join(MANIFEST_FILE_NAME);
         read_manifest(manifest_path)
     }
+
+    // Digest of every file in the problem directory including the manifest, and of the templates
+    // and partials the repository overrides, in the docker digest format
+    pub fn content_hash(&self, overrides: &TemplateOverrides) -> SomaResult<String> {
+        // Counts keep boundaries between the files, templates, and partials unambiguous
+        let mut hasher = Sha256::new();
+        let files = read_dir_contents(self.path())?;
+        hasher.input((files.len() as u64).to_le_bytes());
+        for (relative_path, contents) in files {
+            let relative_path = relative_path.to_slash().ok_or(SomaError::FileUnreachable)?;
+            input_hash_entry(&mut hasher, &relative_path, &contents);
+        }
+        for entries in &[overrides.templates(), overrides.partials()] {
+            hasher.input((entries.len() as u64).to_le_bytes());
+            for (name, contents) in entries.iter() {
+                input_hash_entry(&mut hasher, name, contents.as_bytes());
+            }
+        }
+
+        let digest: String = hasher
+            .result()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Ok(format!("sha256:{}", digest))
+    }
 }
 
 #[derive(Deserialize)]
 pub struct Manifest {
     name: NameString,
     work_dir: Option<PathBuf>,
-    binary: BinaryConfig,
+    internal: Option<bool>,
+    restart: Option<RestartPolicy>,
+    healthcheck: Option<HealthcheckConfig>,
+    binary: Option<BinaryConfig>,
+    build: Option<BuildConfig>,
+    docker: Option<DockerConfig>,
 }
 
 #[derive(Serialize)]
 pub struct SolidManifest {
     name: NameString,
     work_dir: PathBuf,
-    binary: SolidBinaryConfig,
+    binary: Option<SolidBinaryConfig>,
+    build: Option<SolidBuildConfig>,
+    docker: Option<SolidDockerConfig>,
 }
 
 impl Manifest {
@@ -72,15 +127,56 @@ impl Manifest {
     }
 
     pub fn public_files(&self) -> Vec<&PathBuf> {
-        let binary = &self.binary;
-        let executables = binary.executable().iter();
-        let readonly = binary.readonly().iter();
+        let mut public_files = Vec::new();
+
+        if let Some(binary) = &self.binary {
+            let executables = binary.executable().iter();
+            let readonly = binary.readonly().iter();
+
+            public_files.extend(
+                executables
+                    .chain(readonly)
+                    .filter(|file_entry| file_entry.public())
+                    .map(FileEntry::path),
+            );
+        }
+
+        if let Some(docker) = &self.docker {
+            public_files.extend(docker.public());
+        }
 
-        executables
-            .chain(readonly)
-            .filter(|file_entry| file_entry.public())
-            .map(FileEntry::path)
-            .collect()
+        public_files
+    }
+
+    pub fn container_port(&self) -> u16 {
+        match &self.docker {
+            Some(docker) => docker.port().unwrap_or(DEFAULT_CONTAINER_PORT),
+            None => DEFAULT_CONTAINER_PORT,
+        }
+    }
+
+    // Problems on internal networks reach neither the internet nor the host
+    pub fn internal(&self) -> bool {
+        self.internal.unwrap_or(false)
+    }
+
+    pub fn restart_policy(&self) -> RestartPolicy {
+        self.restart.unwrap_or_default()
+    }
+
+    // Binary problems serve through socat, which can also probe the service port
+    // Commands run with 'sh -c' inside the container, and the binary template installs socat
+    pub fn healthcheck(&self) -> Healthcheck {
+        let default_cmd = self.binary.as_ref().map(|_| {
+            format!(
+                "socat -u OPEN:/dev/null TCP:127.0.0.1:{}",
+                self.container_port()
+            )
+        });
+        match &self.healthcheck {
+            Some(healthcheck) => healthcheck.solidify(default_cmd),
+            None => Healthcheck::new(default_cmd),
+        }
     }
 
     pub fn solidify(&self) -> SomaResult<SolidManifest> {
@@ -94,20 +190,50 @@ impl Manifest {
             Err(SomaError::InvalidManifest)?;
         }
 
-        let binary = self.binary.solidify(&work_dir)?;
+        // Exactly one subconfiguration should describe how to build the problem image
+        let (binary, docker) = match (&self.binary, &self.docker) {
+            (Some(binary), None) => (Some(binary.solidify(&work_dir)?), None),
+            (None, Some(docker)) => (None, Some(docker.solidify(DEFAULT_CONTAINER_PORT)?)),
+            _ => Err(SomaError::InvalidManifest)?,
+        };
+
+        // Build artifacts are copied into the image generated by binary subconfiguration
+        let build = match (&self.build, &binary) {
+            (Some(build), Some(_)) => Some(build.solidify(&work_dir)?),
+            (Some(_), None) => Err(SomaError::InvalidManifest)?,
+            (None, _) => None,
+        };
 
         Ok(SolidManifest {
             name: self.name.clone(),
             work_dir,
             binary,
+            build,
+            docker,
         })
     }
 }
 
 impl SolidManifest {
-    pub fn binary(&self) -> &SolidBinaryConfig {
-        &self.binary
+    pub fn binary(&self) -> Option<&SolidBinaryConfig> {
+        self.binary.as_ref()
+    }
+
+    pub fn build(&self) -> Option<&SolidBuildConfig> {
+        self.build.as_ref()
     }
+
+    pub fn docker(&self) -> Option<&SolidDockerConfig> {
+        self.docker.as_ref()
+    }
+}
+
+// Lengths keep boundaries between names and contents unambiguous
+fn input_hash_entry(hasher: &mut Sha256, name: &str, contents: &[u8]) {
+    hasher.input((name.len() as u64).to_le_bytes());
+    hasher.input(name.as_bytes());
+    hasher.input((contents.len() as u64).to_le_bytes());
+    hasher.input(contents);
 }
 
 pub fn read_manifest(path: impl AsRef<Path>) -> SomaResult<Manifest> {
@@ -1,5 +1,12 @@
 pub use self::binary::{BinaryConfig, SolidBinaryConfig};
-pub use self::common::{FileEntry, SolidFileEntry};
+pub use self::build::{BuildConfig, SolidBuildConfig};
+pub(crate) use self::common::{is_contained, is_valid_env_key};
+pub use self::common::{is_pinned, FileEntry, SolidFileEntry};
+pub use self::docker::{DockerConfig, SolidDockerConfig};
+pub use self::healthcheck::{Healthcheck, HealthcheckConfig};
 
 mod binary;
+mod build;
 mod common;
+mod docker;
+mod healthcheck;
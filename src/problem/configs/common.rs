@@ -1,13 +1,23 @@
 use std::fmt;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
+use lazy_static::lazy_static;
 use path_slash::PathBufExt;
+use regex::Regex;
 use serde::de::{self, Deserializer, Unexpected, Visitor};
 use serde::ser::Serializer;
 use serde::{Deserialize, Serialize};
 
 use crate::prelude::*;
 
+lazy_static! {
+    static ref PINNED_IMAGE_REGEX: Regex = Regex::new(r"^[^@\s]+@sha256:[0-9a-f]{64}$").unwrap();
+    static ref ENV_KEY_REGEX: Regex = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+    // Debian package name with an optional architecture qualifier and version pin
+    static ref PACKAGE_NAME_REGEX: Regex =
+        Regex::new(r"^[a-z0-9][a-z0-9+.-]+(:[a-z0-9-]+)?(=[A-Za-z0-9.+:~-]+)?$").unwrap();
+}
+
 #[derive(Debug, PartialEq)]
 pub enum FilePermissions {
     Custom(u16),
@@ -76,6 +86,37 @@ where
     }
 }
 
+// Checks whether the relative path stays inside of the directory it is joined to
+pub(crate) fn is_contained(path: impl AsRef<Path>) -> bool {
+    let path = path.as_ref();
+    path.is_relative()
+        && !path
+            .components()
+            .any(|component| component == Component::ParentDir)
+}
+
+// Environment variable names are restricted to what every shell accepts
+pub(crate) fn is_valid_env_key(key: &str) -> bool {
+    ENV_KEY_REGEX.is_match(key)
+}
+
+// Package names are rendered unescaped into the apt-get command line
+pub(super) fn is_valid_package(package: &str) -> bool {
+    PACKAGE_NAME_REGEX.is_match(package)
+}
+
+// Checks whether the image reference is pinned by a content digest
+pub fn is_pinned(image: &str) -> bool {
+    PINNED_IMAGE_REGEX.is_match(image)
+}
+
+// Image references are either unpinned tags or well-formed digest references
+pub(super) fn is_valid_image(image: &str) -> bool {
+    !image.is_empty()
+        && !image.contains(char::is_whitespace)
+        && (!image.contains('@') || is_pinned(image))
+}
+
 // target_path is defined as String instead of PathBuf to support Windows
 #[derive(Deserialize)]
 pub struct FileEntry {
@@ -86,6 +127,7 @@ pub struct FileEntry {
 
 #[derive(Serialize)]
 pub struct SolidFileEntry {
+    #[serde(serialize_with = "serialize_as_slash_path")]
     path: PathBuf,
     public: bool,
     #[serde(serialize_with = "serialize_as_slash_path")]
@@ -133,6 +175,10 @@ impl SolidFileEntry {
     pub fn path_map(&self) -> (&PathBuf, &PathBuf) {
         (&self.path, &self.target_path)
     }
+
+    pub fn public(&self) -> bool {
+        self.public
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use path_slash::PathBufExt;
+use serde::{Deserialize, Serialize};
+
+use super::common::is_contained;
+use crate::prelude::*;
+
+const DEFAULT_DOCKERFILE: &str = "Dockerfile";
+
+#[derive(Deserialize)]
+pub struct DockerConfig {
+    context: Option<PathBuf>,
+    dockerfile: Option<PathBuf>,
+    port: Option<u16>,
+    public: Option<Vec<PathBuf>>,
+}
+
+#[derive(Serialize)]
+pub struct SolidDockerConfig {
+    context: PathBuf,
+    dockerfile: String,
+    port: u16,
+}
+
+impl DockerConfig {
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    pub fn public(&self) -> Vec<&PathBuf> {
+        match &self.public {
+            Some(public) => public.iter().collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn solidify(&self, default_port: u16) -> SomaResult<SolidDockerConfig> {
+        let context = self.context.clone().unwrap_or_else(|| PathBuf::from("."));
+        let dockerfile = self
+            .dockerfile
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_DOCKERFILE));
+
+        // TODO: More descriptive error
+        // Build context is sent to the docker daemon, and public files are copied out by fetch,
+        // so both should stay inside the problem directory
+        if !is_contained(&context)
+            || !is_contained(&dockerfile)
+            || !self.public().into_iter().all(is_contained)
+        {
+            Err(SomaError::InvalidManifest)?;
+        }
+
+        // Docker daemon expects a slash-delimited path relative to the build context
+        let dockerfile = dockerfile.to_slash().ok_or(SomaError::InvalidUnicode)?;
+
+        Ok(SolidDockerConfig {
+            context,
+            dockerfile,
+            port: self.port.unwrap_or(default_port),
+        })
+    }
+}
+
+impl SolidDockerConfig {
+    pub fn context(&self) -> &PathBuf {
+        &self.context
+    }
+
+    pub fn dockerfile(&self) -> &str {
+        &self.dockerfile
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_docker_config(config: &str) -> DockerConfig {
+        toml::from_str(config).expect("Failed to parse the docker config")
+    }
+
+    #[test]
+    fn test_docker_config_defaults() {
+        let docker_config = parse_docker_config("").solidify(1337).unwrap();
+
+        assert_eq!(docker_config.context(), &PathBuf::from("."));
+        assert_eq!(docker_config.dockerfile(), "Dockerfile");
+        assert_eq!(docker_config.port(), 1337);
+    }
+
+    #[test]
+    fn test_docker_config_escape() {
+        let docker_config = parse_docker_config(r#"context = "../other-problem""#);
+        assert!(docker_config.solidify(1337).is_err());
+
+        let docker_config = parse_docker_config(r#"dockerfile = "/etc/Dockerfile""#);
+        assert!(docker_config.solidify(1337).is_err());
+
+        let docker_config = parse_docker_config(r#"public = ["dist/app", "../../etc/passwd"]"#);
+        assert!(docker_config.solidify(1337).is_err());
+
+        let docker_config = parse_docker_config(
+            r#"
+            context = "docker"
+            dockerfile = "build/Dockerfile.prod"
+            port = 8080
+            "#,
+        )
+        .solidify(1337)
+        .unwrap();
+        assert_eq!(docker_config.dockerfile(), "build/Dockerfile.prod");
+        assert_eq!(docker_config.port(), 8080);
+    }
+}
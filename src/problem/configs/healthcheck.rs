@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+// Seconds that run waits for the problem container to become healthy
+const DEFAULT_TIMEOUT: u64 = 30;
+
+#[derive(Deserialize)]
+pub struct HealthcheckConfig {
+    cmd: Option<String>,
+    timeout: Option<u64>,
+}
+
+pub struct Healthcheck {
+    cmd: Option<String>,
+    timeout: Duration,
+}
+
+impl HealthcheckConfig {
+    // The default command applies when the manifest does not declare one
+    pub fn solidify(&self, default_cmd: Option<String>) -> Healthcheck {
+        Healthcheck {
+            cmd: self.cmd.clone().or(default_cmd),
+            timeout: Duration::from_secs(self.timeout.unwrap_or(DEFAULT_TIMEOUT)),
+        }
+    }
+}
+
+impl Healthcheck {
+    pub fn new(cmd: Option<String>) -> Healthcheck {
+        Healthcheck {
+            cmd,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT),
+        }
+    }
+
+    // A shell command which exits with zero while the problem serves connections
+    pub fn cmd(&self) -> Option<&str> {
+        self.cmd.as_deref()
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_healthcheck_config(config: &str) -> HealthcheckConfig {
+        toml::from_str(config).expect("Failed to parse the healthcheck config")
+    }
+
+    #[test]
+    fn test_healthcheck_config() {
+        let healthcheck = parse_healthcheck_config("").solidify(Some("true".to_owned()));
+        assert_eq!(healthcheck.cmd(), Some("true"));
+        assert_eq!(healthcheck.timeout(), Duration::from_secs(DEFAULT_TIMEOUT));
+
+        let healthcheck = parse_healthcheck_config(
+            r#"
+            cmd = "curl -f http://localhost:8080/"
+            timeout = 5
+            "#,
+        )
+        .solidify(None);
+        assert_eq!(healthcheck.cmd(), Some("curl -f http://localhost:8080/"));
+        assert_eq!(healthcheck.timeout(), Duration::from_secs(5));
+    }
+}
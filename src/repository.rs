@@ -1,13 +1,15 @@
-use std::collections::HashSet;
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 
+use git2::{Oid, Repository as GitRepository, Tree};
 use serde::{Deserialize, Serialize};
 
 use crate::docker::{self, SomaImage};
 use crate::prelude::*;
-use crate::problem::{read_manifest, MANIFEST_FILE_NAME};
-use crate::repository::backend::{Backend, BackendExt};
-use crate::{read_file_contents, NameString};
+use crate::problem::{read_manifest, Manifest, MANIFEST_FILE_NAME};
+use crate::repository::backend::{Backend, BackendExt, FetchedTree};
+use crate::template::{TemplateOverrides, TEMPLATE_DIR_PATH};
+use crate::{read_dir_contents, read_file_contents, NameString};
 
 pub use self::manager::RepositoryManager;
 
@@ -15,14 +17,36 @@ pub mod backend;
 mod manager;
 
 const LIST_FILE_NAME: &str = "soma-list.toml";
+// Git stores symbolic links with this file mode
+const SYMLINK_FILE_MODE: i32 = 0o120_000;
 
 #[derive(Deserialize)]
 struct ProblemList {
     problems: Vec<PathBuf>,
+    templates: Option<BTreeMap<String, PathBuf>>,
+    partials: Option<BTreeMap<String, PathBuf>>,
 }
 
 impl ProblemList {
+    fn template_paths(&self) -> impl Iterator<Item = &PathBuf> {
+        let templates = self.templates.iter().flat_map(BTreeMap::values);
+        let partials = self.partials.iter().flat_map(BTreeMap::values);
+        templates.chain(partials)
+    }
+
     fn sanity_check(&self, repo_path: impl AsRef<Path>) -> SomaResult<()> {
+        let canonical_repo_path = repo_path.as_ref().canonicalize()?;
+        for template_path in self.template_paths() {
+            let canonical_template_path = repo_path
+                .as_ref()
+                .join(template_path)
+                .canonicalize()
+                .map_err(|_| SomaError::InvalidSomaList)?;
+            if !canonical_template_path.starts_with(&canonical_repo_path) {
+                Err(SomaError::InvalidSomaList)?;
+            }
+        }
+
         let hash_set = self
             .problems
             .iter()
@@ -36,6 +60,27 @@ impl ProblemList {
 
         Ok(())
     }
+
+    // Checks the same as sanity_check, for a tree which is not checked out
+    fn tree_sanity_check(&self, tree: &Tree) -> SomaResult<()> {
+        for template_path in self.template_paths() {
+            if tree_entry_id(tree, template_path)?.is_none() {
+                Err(SomaError::InvalidSomaList)?;
+            }
+        }
+
+        let hash_set = self
+            .problems
+            .iter()
+            .map(tree_path)
+            .collect::<SomaResult<HashSet<_>>>()?;
+
+        if hash_set.len() != self.problems.len() {
+            Err(SomaError::InvalidSomaList)?;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
@@ -82,39 +127,66 @@ impl<'a> Repository<'a> {
         self.manager.repo_path(&self.name)
     }
 
-    pub fn update(&mut self, images: &[SomaImage]) -> SomaResult<()> {
-        let current_prob_set: HashSet<_> = self
-            .prob_list
-            .clone()
-            .into_iter()
-            .map(|prob_index| prob_index.name)
-            .collect();
-        let new_prob_list = {
-            let temp_dir = tempfile::tempdir()?;
-            self.backend().update_at(temp_dir.path())?;
-            read_prob_list(temp_dir.path())?
+    // Fetches the latest version without changing the local copy and compares the two trees
+    pub fn fetch_update(&self) -> SomaResult<FetchedUpdate> {
+        let local_path = self.path();
+        let tree = self.backend().fetch_at(&local_path)?;
+        let (prob_list, diff) = match &tree {
+            FetchedTree::Commit(commit_id) => {
+                let git_repo = GitRepository::open(&local_path)?;
+                let old_tree = git_repo.head()?.peel_to_tree()?;
+                let new_tree = git_repo.find_commit(*commit_id)?.tree()?;
+                let prob_list = read_tree_prob_list(&git_repo, &new_tree)?;
+                // Unchanged directories keep the ids of their trees
+                let diff = UpdateDiff::new(&self.prob_list, &prob_list, |old_index, new_index| {
+                    let old_id = tree_entry_id(&old_tree, &old_index.path)?;
+                    Ok(old_id.is_some() && old_id != tree_entry_id(&new_tree, &new_index.path)?)
+                })?;
+                (prob_list, diff)
+            }
+            FetchedTree::Copy(temp_dir) => {
+                let prob_list = read_prob_list(temp_dir.path())?;
+                let diff = UpdateDiff::new(&self.prob_list, &prob_list, |old_index, new_index| {
+                    let old_path = local_path.join(&old_index.path);
+                    let new_path = temp_dir.path().join(&new_index.path);
+                    Ok(old_path.is_dir() && dir_differs(old_path, new_path)?)
+                })?;
+                (prob_list, diff)
+            }
         };
-        let new_prob_set: HashSet<_> = new_prob_list
-            .clone()
-            .into_iter()
-            .map(|prob_index| prob_index.name)
-            .collect();
-
-        let existing_problem_removed = current_prob_set
-            .difference(&new_prob_set)
-            .filter(|prob_name| {
-                docker::image_from_repo_and_prob_exists(images, &self.name, prob_name)
-            })
-            .count()
-            > 0;
+        Ok(FetchedUpdate {
+            tree,
+            prob_list,
+            diff,
+        })
+    }
+
+    pub fn update(&mut self, images: &[SomaImage]) -> SomaResult<UpdateDiff> {
+        let fetched = self.fetch_update()?;
+        self.install_update(fetched, images)
+    }
 
+    // Problems that disappear from the list should not have images left behind
+    pub fn install_update(
+        &mut self,
+        fetched: FetchedUpdate,
+        images: &[SomaImage],
+    ) -> SomaResult<UpdateDiff> {
+        let existing_problem_removed = fetched.diff.vanished().any(|prob_name| {
+            docker::image_from_repo_and_prob_exists(images, &self.name, prob_name)
+        });
         if existing_problem_removed {
             Err(SomaError::UnsupportedUpdate)?;
         }
 
-        self.backend.update_at(self.path())?;
-        self.prob_list = new_prob_list;
-        Ok(())
+        self.backend().install_at(self.path(), fetched.tree)?;
+
+        self.prob_list = fetched.prob_list;
+        Ok(fetched.diff)
+    }
+
+    pub fn template_overrides(&self) -> SomaResult<TemplateOverrides> {
+        read_template_overrides(self.path())
     }
 
     pub fn prob_name_iter(&'a self) -> impl Iterator<Item = &'a NameString> {
@@ -122,6 +194,104 @@ impl<'a> Repository<'a> {
     }
 }
 
+// The tree of a single fetch, so that the changes shown are the ones installed
+pub struct FetchedUpdate {
+    tree: FetchedTree,
+    prob_list: Vec<ProblemIndex>,
+    diff: UpdateDiff,
+}
+
+impl FetchedUpdate {
+    pub fn diff(&self) -> &UpdateDiff {
+        &self.diff
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct UpdateDiff {
+    added: Vec<NameString>,
+    removed: Vec<NameString>,
+    renamed: Vec<(NameString, NameString)>,
+    changed: Vec<NameString>,
+}
+
+impl UpdateDiff {
+    // A removed and an added problem at the same path are treated as a rename
+    fn new(
+        old_list: &[ProblemIndex],
+        new_list: &[ProblemIndex],
+        differs: impl Fn(&ProblemIndex, &ProblemIndex) -> SomaResult<bool>,
+    ) -> SomaResult<UpdateDiff> {
+        let mut diff = UpdateDiff::default();
+
+        for new_index in new_list {
+            match old_list.iter().find(|old| old.name == new_index.name) {
+                Some(old_index) => {
+                    if differs(old_index, new_index)? {
+                        diff.changed.push(new_index.name.clone());
+                    }
+                }
+                None => {
+                    let renamed_from = old_list.iter().find(|old| {
+                        old.path == new_index.path
+                            && new_list.iter().all(|new| new.name != old.name)
+                    });
+                    match renamed_from {
+                        Some(old_index) => diff
+                            .renamed
+                            .push((old_index.name.clone(), new_index.name.clone())),
+                        None => diff.added.push(new_index.name.clone()),
+                    }
+                }
+            }
+        }
+
+        for old_index in old_list {
+            let still_listed = new_list.iter().any(|new| new.name == old_index.name);
+            let renamed = diff.renamed.iter().any(|(from, _)| *from == old_index.name);
+            if !still_listed && !renamed {
+                diff.removed.push(old_index.name.clone());
+            }
+        }
+
+        Ok(diff)
+    }
+
+    pub fn added(&self) -> &Vec<NameString> {
+        &self.added
+    }
+
+    pub fn removed(&self) -> &Vec<NameString> {
+        &self.removed
+    }
+
+    pub fn renamed(&self) -> &Vec<(NameString, NameString)> {
+        &self.renamed
+    }
+
+    pub fn changed(&self) -> &Vec<NameString> {
+        &self.changed
+    }
+
+    // Names of problems that no longer exist after the update, including old names of renamed ones
+    pub fn vanished(&self) -> impl Iterator<Item = &NameString> {
+        self.removed
+            .iter()
+            .chain(self.renamed.iter().map(|(from, _)| from))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+fn dir_differs(left: impl AsRef<Path>, right: impl AsRef<Path>) -> SomaResult<bool> {
+    Ok(read_dir_contents(left)? != read_dir_contents(right)?)
+}
+
 fn read_prob_manifest(
     repo_path: impl AsRef<Path>,
     prob_relative_path: impl AsRef<Path>,
@@ -153,3 +323,98 @@ fn read_prob_list(repo_path: impl AsRef<Path>) -> SomaResult<Vec<ProblemIndex>>
         Ok(vec![read_prob_manifest(&repo_path, "./")?])
     }
 }
+
+// Paths in a git tree have no current directory components and cannot leave the tree
+fn tree_path(path: impl AsRef<Path>) -> SomaResult<PathBuf> {
+    path.as_ref()
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| match component {
+            Component::Normal(name) => Ok(name),
+            _ => Err(SomaError::InvalidSomaList.into()),
+        })
+        .collect()
+}
+
+// Symbolic links could point outside of the repository, so they are rejected like escaping paths
+fn tree_entry_id(tree: &Tree, path: impl AsRef<Path>) -> SomaResult<Option<Oid>> {
+    let path = tree_path(path)?;
+    if path.as_os_str().is_empty() {
+        return Ok(Some(tree.id()));
+    }
+
+    match tree.get_path(&path) {
+        Ok(entry) if entry.filemode() == SYMLINK_FILE_MODE => Err(SomaError::InvalidSomaList)?,
+        Ok(entry) => Ok(Some(entry.id())),
+        Err(_) => Ok(None),
+    }
+}
+
+fn read_tree_file(
+    git_repo: &GitRepository,
+    tree: &Tree,
+    path: impl AsRef<Path>,
+) -> SomaResult<Option<Vec<u8>>> {
+    match tree_entry_id(tree, path)? {
+        Some(blob_id) => Ok(Some(git_repo.find_blob(blob_id)?.content().to_owned())),
+        None => Ok(None),
+    }
+}
+
+fn read_tree_prob_manifest(
+    git_repo: &GitRepository,
+    tree: &Tree,
+    prob_relative_path: impl AsRef<Path>,
+) -> SomaResult<ProblemIndex> {
+    let manifest_path = prob_relative_path.as_ref().join(MANIFEST_FILE_NAME);
+    let manifest: Manifest = match read_tree_file(git_repo, tree, manifest_path)? {
+        Some(contents) => toml::from_slice(&contents)?,
+        None => Err(SomaError::InvalidRepository)?,
+    };
+    Ok(ProblemIndex {
+        name: manifest.name().clone(),
+        path: prob_relative_path.as_ref().to_owned(),
+    })
+}
+
+// Reads the problem list of a fetched commit without checking it out
+fn read_tree_prob_list(git_repo: &GitRepository, tree: &Tree) -> SomaResult<Vec<ProblemIndex>> {
+    match read_tree_file(git_repo, tree, LIST_FILE_NAME)? {
+        Some(contents) => {
+            let prob_list: ProblemList = toml::from_slice(&contents)?;
+            prob_list.tree_sanity_check(tree)?;
+            prob_list
+                .problems
+                .iter()
+                .map(|prob_relative_path| {
+                    read_tree_prob_manifest(git_repo, tree, prob_relative_path)
+                })
+                .collect()
+        }
+        None => Ok(vec![read_tree_prob_manifest(git_repo, tree, "./")?]),
+    }
+}
+
+// Entries in soma-list.toml take priority over files in the template directory
+fn read_template_overrides(repo_path: impl AsRef<Path>) -> SomaResult<TemplateOverrides> {
+    let mut overrides =
+        TemplateOverrides::from_dir(repo_path.as_ref().join(TEMPLATE_DIR_PATH), &repo_path)?;
+
+    let list_path = repo_path.as_ref().join(LIST_FILE_NAME);
+    if list_path.exists() {
+        let prob_list: ProblemList = toml::from_slice(&read_file_contents(list_path)?)?;
+        prob_list.sanity_check(&repo_path)?;
+        if let Some(templates) = &prob_list.templates {
+            for (name, path) in templates {
+                overrides.insert_template(name, repo_path.as_ref().join(path))?;
+            }
+        }
+        if let Some(partials) = &prob_list.partials {
+            for (name, path) in partials {
+                overrides.insert_partial(name, repo_path.as_ref().join(path))?;
+            }
+        }
+    }
+
+    Ok(overrides)
+}
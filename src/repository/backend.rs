@@ -1,17 +1,45 @@
 use std::fmt::{self, Display};
+use std::fs;
 use std::path::{Path, PathBuf};
 
 use fs_extra::dir;
-use git2::{BranchType, ObjectType, Repository as GitRepository, ResetType};
+use git2::{BranchType, Commit, ObjectType, Oid, Repository as GitRepository, ResetType};
 use remove_dir_all::remove_dir_all;
 use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
 use url::Url;
 
+use crate::export;
 use crate::prelude::*;
 
+// Not a valid repository name, so fetched trees never collide with a local copy
+const UPDATE_DIR_PREFIX: &str = ".update-";
+
+// The latest version of a repository, fetched without changing the local copy
+pub enum FetchedTree {
+    // A commit fetched into the local git repository
+    Commit(Oid),
+    // A copy of the latest version next to the local copy
+    Copy(TempDir),
+}
+
 #[typetag::serde(tag = "type")]
 pub trait Backend: BackendClone + Display {
     fn update_at_path(&self, local_path: &Path) -> SomaResult<()>;
+    // Fails when a later update cannot succeed, without fetching anything
+    fn check_at_path(&self, local_path: &Path) -> SomaResult<()>;
+
+    // Backends without history copy the whole tree
+    fn fetch_at_path(&self, local_path: &Path) -> SomaResult<FetchedTree> {
+        fetch_copy(self, local_path)
+    }
+
+    fn install_at_path(&self, local_path: &Path, fetched: FetchedTree) -> SomaResult<()> {
+        match fetched {
+            FetchedTree::Copy(temp_dir) => install_copy(local_path, temp_dir),
+            FetchedTree::Commit(_) => Err(SomaError::InvalidRepository)?,
+        }
+    }
 }
 
 pub trait BackendClone {
@@ -37,6 +65,18 @@ pub trait BackendExt: Backend {
     fn update_at(&self, local_path: impl AsRef<Path>) -> SomaResult<()> {
         self.update_at_path(local_path.as_ref())
     }
+
+    fn check_at(&self, local_path: impl AsRef<Path>) -> SomaResult<()> {
+        self.check_at_path(local_path.as_ref())
+    }
+
+    fn fetch_at(&self, local_path: impl AsRef<Path>) -> SomaResult<FetchedTree> {
+        self.fetch_at_path(local_path.as_ref())
+    }
+
+    fn install_at(&self, local_path: impl AsRef<Path>, fetched: FetchedTree) -> SomaResult<()> {
+        self.install_at_path(local_path.as_ref(), fetched)
+    }
 }
 
 impl<T> BackendExt for T where T: ?Sized + Backend {}
@@ -89,16 +129,62 @@ impl Backend for GitBackend {
     fn update_at_path(&self, local_path: &Path) -> SomaResult<()> {
         let git_repo = GitRepository::open(local_path)
             .or_else(|_| GitRepository::clone(&self.url, local_path))?;
-        git_repo
-            .find_remote("origin")?
-            .fetch(&["master"], None, None)?;
+        let head_commit = fetch_origin_master(&git_repo)?;
+        git_repo.reset(head_commit.as_object(), ResetType::Hard, None)?;
 
-        let origin_master = git_repo.find_branch("origin/master", BranchType::Remote)?;
-        let head_commit = origin_master.get().peel(ObjectType::Commit)?;
-        git_repo.reset(&head_commit, ResetType::Hard, None)?;
+        Ok(())
+    }
 
+    fn check_at_path(&self, local_path: &Path) -> SomaResult<()> {
+        GitRepository::open(local_path)?.find_remote("origin")?;
         Ok(())
     }
+
+    // Only new objects are fetched into the local repository, and the checkout stays as it is
+    fn fetch_at_path(&self, local_path: &Path) -> SomaResult<FetchedTree> {
+        match GitRepository::open(local_path) {
+            Ok(git_repo) => Ok(FetchedTree::Commit(fetch_origin_master(&git_repo)?.id())),
+            // The local copy does not exist yet right after the repository is added
+            Err(_) => fetch_copy(self, local_path),
+        }
+    }
+
+    fn install_at_path(&self, local_path: &Path, fetched: FetchedTree) -> SomaResult<()> {
+        match fetched {
+            FetchedTree::Commit(commit_id) => {
+                let git_repo = GitRepository::open(local_path)?;
+                let commit = git_repo.find_object(commit_id, Some(ObjectType::Commit))?;
+                git_repo.reset(&commit, ResetType::Hard, None)?;
+                Ok(())
+            }
+            FetchedTree::Copy(temp_dir) => install_copy(local_path, temp_dir),
+        }
+    }
+}
+
+fn fetch_origin_master(git_repo: &GitRepository) -> SomaResult<Commit> {
+    git_repo
+        .find_remote("origin")?
+        .fetch(&["master"], None, None)?;
+
+    let origin_master = git_repo.find_branch("origin/master", BranchType::Remote)?;
+    Ok(origin_master.get().peel_to_commit()?)
+}
+
+fn fetch_copy(backend: &(impl Backend + ?Sized), local_path: &Path) -> SomaResult<FetchedTree> {
+    let temp_dir = tempfile::Builder::new()
+        .prefix(UPDATE_DIR_PREFIX)
+        .tempdir_in(local_path.parent().ok_or(SomaError::FileNameNotFound)?)?;
+    backend.update_at_path(temp_dir.path())?;
+    Ok(FetchedTree::Copy(temp_dir))
+}
+
+fn install_copy(local_path: &Path, temp_dir: TempDir) -> SomaResult<()> {
+    if local_path.exists() {
+        remove_dir_all(local_path)?;
+    }
+    fs::rename(temp_dir.path(), local_path)?;
+    Ok(())
 }
 
 impl Display for GitBackend {
@@ -133,6 +219,13 @@ impl Backend for LocalBackend {
 
         Ok(())
     }
+
+    fn check_at_path(&self, _local_path: &Path) -> SomaResult<()> {
+        if !self.origin.is_dir() {
+            Err(SomaError::FileUnreachable)?;
+        }
+        Ok(())
+    }
 }
 
 impl Display for LocalBackend {
@@ -140,3 +233,39 @@ impl Display for LocalBackend {
         write!(f, "Local: {}", self.origin.to_string_lossy())
     }
 }
+
+// Problem files unpacked from an archive created by export command
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ArchiveBackend {
+    origin: PathBuf,
+}
+
+impl ArchiveBackend {
+    pub fn new(origin: PathBuf) -> Self {
+        ArchiveBackend { origin }
+    }
+}
+
+#[typetag::serde]
+impl Backend for ArchiveBackend {
+    fn update_at_path(&self, local_path: &Path) -> SomaResult<()> {
+        if local_path.exists() {
+            remove_dir_all(local_path)?;
+        }
+
+        export::unpack_problem(&self.origin, local_path)
+    }
+
+    fn check_at_path(&self, _local_path: &Path) -> SomaResult<()> {
+        if !self.origin.is_file() {
+            Err(SomaError::FileUnreachable)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ArchiveBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Archive: {}", self.origin.to_string_lossy())
+    }
+}
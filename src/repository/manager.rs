@@ -3,14 +3,16 @@ use std::fs::File;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 
+use fs_extra::dir;
 use remove_dir_all::remove_dir_all;
 use serde::{Deserialize, Serialize};
 
 use crate::data_dir::{DirectoryManager, Registration};
+use crate::docker::SomaImage;
 use crate::prelude::*;
 use crate::problem::Problem;
 use crate::repository::backend::{Backend, BackendExt};
-use crate::repository::{read_prob_list, ProblemIndex, Repository};
+use crate::repository::{read_prob_list, FetchedUpdate, ProblemIndex, Repository, UpdateDiff};
 use crate::NameString;
 
 const INDEX_FILE_NAME: &str = "index";
@@ -84,6 +86,50 @@ impl<'a> RepositoryManager<'a> {
         Ok(())
     }
 
+    // Serializes index entries of repositories in the same format as the index file
+    pub fn bundle_index(&self, repo_names: &[NameString]) -> SomaResult<Vec<u8>> {
+        let repo_index = repo_names
+            .iter()
+            .map(|repo_name| match self.repo_index.get(repo_name) {
+                Some(index) => Ok((repo_name.clone(), index.clone())),
+                None => Err(SomaError::RepositoryNotFound)?,
+            })
+            .collect::<SomaResult<BTreeMap<_, _>>>()?;
+
+        Ok(serde_cbor::to_vec(&repo_index)?)
+    }
+
+    // Registers repositories whose contents are stored under content_root/<repo name>
+    pub fn restore_repos(
+        &mut self,
+        index: &[u8],
+        content_root: impl AsRef<Path>,
+    ) -> SomaResult<Vec<NameString>> {
+        let repo_index: BTreeMap<NameString, Index> = serde_cbor::from_slice(index)?;
+        if repo_index
+            .keys()
+            .any(|repo_name| self.repo_exists(repo_name))
+        {
+            Err(SomaError::DuplicateRepository)?;
+        }
+
+        let mut copy_options = dir::CopyOptions::new();
+        copy_options.copy_inside = true;
+        for repo_name in repo_index.keys() {
+            let content_path = content_root.as_ref().join(repo_name);
+            if !content_path.is_dir() {
+                Err(SomaError::InvalidBundle)?;
+            }
+            dir::copy(content_path, self.repo_path(repo_name), &copy_options)?;
+        }
+
+        let repo_names = repo_index.keys().cloned().collect();
+        self.repo_index.extend(repo_index);
+        self.dirty = true;
+
+        Ok(repo_names)
+    }
+
     pub fn remove_repo(&mut self, repo_name: &NameString) -> SomaResult<()> {
         let local_path = self.repo_path(&repo_name);
         if local_path.is_dir() {
@@ -98,6 +144,28 @@ impl<'a> RepositoryManager<'a> {
         Ok(())
     }
 
+    // Installs the fetched tree as the local copy of the repository and saves its new problem list
+    pub fn update_repo(
+        &mut self,
+        repo_name: &NameString,
+        fetched: FetchedUpdate,
+        images: &[SomaImage],
+    ) -> SomaResult<UpdateDiff> {
+        let (prob_list, diff) = {
+            let mut repository = self.get_repo(repo_name)?;
+            let diff = repository.install_update(fetched, images)?;
+            (repository.prob_list, diff)
+        };
+
+        self.repo_index
+            .get_mut(repo_name)
+            .ok_or(SomaError::RepositoryNotFound)?
+            .prob_list = prob_list;
+        self.dirty = true;
+
+        Ok(diff)
+    }
+
     pub fn get_repo(&self, repo_name: &NameString) -> SomaResult<Repository> {
         let repository = match self.repo_index.get(&repo_name) {
             Some(index) => Repository::new(
@@ -127,6 +195,38 @@ impl<'a> RepositoryManager<'a> {
         }
     }
 
+    // batch query is a problem query, a repository name, or a glob pattern on problem names
+    pub fn select_probs(&self, query: &str) -> SomaResult<Vec<Problem>> {
+        let result: Vec<_> = if is_glob(query) {
+            self.list_prob()
+                .filter(|problem| {
+                    glob_match(query, problem.prob_name())
+                        || glob_match(query, &problem.fully_qualified_name())
+                })
+                .collect()
+        } else {
+            match self.search_prob(query) {
+                Ok(problem) => vec![problem],
+                Err(error) => {
+                    let repo_problems: Vec<_> = self
+                        .list_prob()
+                        .filter(|problem| query == problem.repo_name())
+                        .collect();
+                    if repo_problems.is_empty() {
+                        return Err(error);
+                    }
+                    repo_problems
+                }
+            }
+        };
+
+        if result.is_empty() {
+            Err(SomaError::ProblemNotFound)?
+        } else {
+            Ok(result)
+        }
+    }
+
     pub fn list_repo(&'a self) -> impl Iterator<Item = Repository<'a>> {
         self.repo_index.iter().map(move |(name, index)| {
             Repository::new(
@@ -155,6 +255,41 @@ impl<'a> RepositoryManager<'a> {
     }
 }
 
+fn is_glob(query: &str) -> bool {
+    query.contains('*') || query.contains('?')
+}
+
+// '*' matches any sequence of characters and '?' matches a single character
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut pattern_index, mut text_index) = (0, 0);
+    let mut backtrack = None;
+
+    while text_index < text.len() {
+        match pattern.get(pattern_index) {
+            Some('*') => {
+                backtrack = Some((pattern_index, text_index));
+                pattern_index += 1;
+            }
+            Some(&c) if c == '?' || c == text[text_index] => {
+                pattern_index += 1;
+                text_index += 1;
+            }
+            _ => match backtrack {
+                Some((star_index, star_text_index)) => {
+                    pattern_index = star_index + 1;
+                    text_index = star_text_index + 1;
+                    backtrack = Some((star_index, star_text_index + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[pattern_index..].iter().all(|&c| c == '*')
+}
+
 impl<'a> Drop for RepositoryManager<'a> {
     fn drop(&mut self) {
         if self.dirty {
@@ -169,3 +304,21 @@ impl<'a> Drop for RepositoryManager<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("pwn-*", "pwn-baby"));
+        assert!(glob_match("repo.*", "repo.r0pbaby"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a?c", "abc"));
+        assert!(glob_match("*b*b*", "babyecho-bof"));
+        assert!(!glob_match("pwn-*", "web-pwn"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(!glob_match("repo.*", "repository.a"));
+        assert!(!is_glob("repo.prob"));
+    }
+}
@@ -1,37 +1,176 @@
-use std::fs::File;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 
 use handlebars::Handlebars;
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::Serialize;
 
 use crate::prelude::*;
+use crate::read_file_contents;
+
+pub const TEMPLATE_DIR_PATH: &str = ".soma/templates";
+const PARTIAL_DIR_NAME: &str = "partials";
+const PARTIAL_EXTENSION: &str = "hbs";
+
+lazy_static! {
+    static ref TEMPLATE_NAME_REGEX: Regex = Regex::new(r"^[A-Za-z0-9_-][A-Za-z0-9_.-]*$").unwrap();
+}
 
 pub enum Templates {
     Binary,
+    Import,
 }
 
 impl Templates {
-    fn templates(&self) -> &[(&str, &str)] {
+    // (template name, output path, template string)
+    fn templates(&self) -> &[(&str, &str, &str)] {
         match self {
             Templates::Binary => &[
-                ("Dockerfile", include_str!("../templates/binary/Dockerfile")),
                 (
+                    "Dockerfile",
+                    "Dockerfile",
+                    include_str!("../templates/binary/Dockerfile"),
+                ),
+                (
+                    "start.sh",
                     ".soma/start.sh",
                     include_str!("../templates/binary/start.sh"),
                 ),
                 (
+                    "configure_permissions.sh",
                     ".soma/configure_permissions.sh",
                     include_str!("../templates/binary/configure_permissions.sh"),
                 ),
             ],
+            Templates::Import => &[(
+                "Dockerfile",
+                "Dockerfile",
+                include_str!("../templates/import/Dockerfile"),
+            )],
         }
     }
+
+    fn partials(&self) -> &[(&str, &str)] {
+        match self {
+            Templates::Binary => &[(
+                "timezone",
+                include_str!("../templates/binary/partials/timezone.hbs"),
+            )],
+            Templates::Import => &[],
+        }
+    }
+}
+
+// Repository-provided templates and partials which take priority over the embedded ones
+#[derive(Default)]
+pub struct TemplateOverrides {
+    templates: BTreeMap<String, String>,
+    partials: BTreeMap<String, String>,
+}
+
+impl TemplateOverrides {
+    pub fn new() -> TemplateOverrides {
+        TemplateOverrides::default()
+    }
+
+    // Loads overrides from a template directory with the following layout:
+    // <dir>/<template name>, <dir>/partials/<partial name>.hbs
+    // Every entry should resolve to a file inside of the root, such as the repository directory
+    pub fn from_dir(
+        dir: impl AsRef<Path>,
+        root: impl AsRef<Path>,
+    ) -> SomaResult<TemplateOverrides> {
+        let mut overrides = TemplateOverrides::new();
+        let dir = dir.as_ref();
+        if !dir.is_dir() {
+            return Ok(overrides);
+        }
+
+        let canonical_root = root.as_ref().canonicalize()?;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_file() {
+                let name = path
+                    .file_name()
+                    .ok_or(SomaError::FileNameNotFound)?
+                    .to_str()
+                    .ok_or(SomaError::InvalidUnicode)?;
+                overrides.insert_template(name, contained_path(&path, &canonical_root)?)?;
+            }
+        }
+
+        let partial_dir = dir.join(PARTIAL_DIR_NAME);
+        if partial_dir.is_dir() {
+            for entry in fs::read_dir(partial_dir)? {
+                let path = entry?.path();
+                if path.is_file()
+                    && path.extension().and_then(|ext| ext.to_str()) == Some(PARTIAL_EXTENSION)
+                {
+                    let name = path
+                        .file_stem()
+                        .ok_or(SomaError::FileNameNotFound)?
+                        .to_str()
+                        .ok_or(SomaError::InvalidUnicode)?;
+                    overrides.insert_partial(name, contained_path(&path, &canonical_root)?)?;
+                }
+            }
+        }
+
+        Ok(overrides)
+    }
+
+    pub fn insert_template(&mut self, name: &str, path: impl AsRef<Path>) -> SomaResult<()> {
+        self.templates
+            .insert(check_template_name(name)?, read_template(path)?);
+        Ok(())
+    }
+
+    pub fn insert_partial(&mut self, name: &str, path: impl AsRef<Path>) -> SomaResult<()> {
+        self.partials
+            .insert(check_template_name(name)?, read_template(path)?);
+        Ok(())
+    }
+
+    pub fn templates(&self) -> &BTreeMap<String, String> {
+        &self.templates
+    }
+
+    pub fn partials(&self) -> &BTreeMap<String, String> {
+        &self.partials
+    }
+}
+
+// Symbolic links from an untrusted repository should not expose files outside of it
+fn contained_path(path: &Path, canonical_root: &Path) -> SomaResult<PathBuf> {
+    let canonical_path = path.canonicalize()?;
+    if canonical_path.starts_with(canonical_root) {
+        Ok(canonical_path)
+    } else {
+        Err(SomaError::InvalidTemplate)?
+    }
+}
+
+fn check_template_name(name: &str) -> SomaResult<String> {
+    // Template names are used as file names in the build context
+    if TEMPLATE_NAME_REGEX.is_match(name) {
+        Ok(name.to_owned())
+    } else {
+        Err(SomaError::InvalidTemplate)?
+    }
+}
+
+fn read_template(path: impl AsRef<Path>) -> SomaResult<String> {
+    let contents = read_file_contents(path).or(Err(SomaError::InvalidTemplate))?;
+    Ok(String::from_utf8(contents).or(Err(SomaError::InvalidTemplate))?)
 }
 
 pub trait HandleBarsExt {
     fn render_templates(
-        &self,
+        &mut self,
         templates: Templates,
+        overrides: &TemplateOverrides,
         context: &impl Serialize,
         output_dir: impl AsRef<Path>,
     ) -> SomaResult<()>;
@@ -39,16 +178,219 @@ pub trait HandleBarsExt {
 
 impl HandleBarsExt for Handlebars {
     fn render_templates(
-        &self,
+        &mut self,
         templates: Templates,
+        overrides: &TemplateOverrides,
         context: &impl Serialize,
         output_dir: impl AsRef<Path>,
     ) -> SomaResult<()> {
-        for (file_name, template_string) in templates.templates() {
+        // Missing variables in repository-provided templates should not be silently ignored
+        self.set_strict_mode(true);
+
+        for (partial_name, partial_string) in templates.partials() {
+            self.register_partial(partial_name, partial_string)?;
+        }
+        for (partial_name, partial_string) in &overrides.partials {
+            self.register_partial(partial_name, partial_string)?;
+        }
+
+        for (template_name, file_name, template_string) in templates.templates() {
+            let template_string = overrides
+                .templates
+                .get(*template_name)
+                .map(String::as_str)
+                .unwrap_or(template_string);
             let mut rendered_file = File::create(output_dir.as_ref().join(file_name))?;
             self.render_template_to_write(template_string, context, &mut rendered_file)?;
         }
 
+        // Templates not known to soma are rendered into .soma directory
+        let extra_templates = overrides.templates.iter().filter(|(template_name, _)| {
+            !templates
+                .templates()
+                .iter()
+                .any(|(name, _, _)| *name == template_name.as_str())
+        });
+        for (template_name, template_string) in extra_templates {
+            let mut rendered_file =
+                File::create(output_dir.as_ref().join(".soma").join(template_name))?;
+            self.render_template_to_write(template_string, context, &mut rendered_file)?;
+        }
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::problem::Manifest;
+
+    fn test_manifest() -> Manifest {
+        toml::from_str(
+            r#"
+            name = "simple-bof"
+
+            [binary]
+            os = "ubuntu:16.04"
+            cmd = "./simple-bof"
+            executable = []
+            readonly = []
+            "#,
+        )
+        .expect("Failed to parse the manifest")
+    }
+
+    fn render(overrides: &TemplateOverrides) -> SomaResult<tempfile::TempDir> {
+        let output_dir = tempfile::tempdir()?;
+        fs::create_dir(output_dir.path().join(".soma"))?;
+        Handlebars::new().render_templates(
+            Templates::Binary,
+            overrides,
+            &test_manifest().solidify()?,
+            output_dir.path(),
+        )?;
+        Ok(output_dir)
+    }
+
+    #[test]
+    fn test_render_default_templates() {
+        let output_dir = render(&TemplateOverrides::new()).unwrap();
+        let dockerfile = fs::read_to_string(output_dir.path().join("Dockerfile")).unwrap();
+        assert!(dockerfile.starts_with("FROM ubuntu:16.04"));
+        assert!(dockerfile.contains("#ENV TZ=Asia/Seoul"));
+        assert!(output_dir.path().join(".soma/start.sh").is_file());
+    }
+
+    #[test]
+    fn test_render_build_stage() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            name = "simple-bof"
+
+            [binary]
+            os = "ubuntu:16.04"
+            cmd = "./simple-bof"
+            executable = []
+            readonly = []
+
+            [build]
+            image = "gcc:9"
+            cmd = "make"
+
+            [[build.artifact]]
+            path = "out/simple-bof"
+            public = true
+            "#,
+        )
+        .unwrap();
+
+        let output_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(output_dir.path().join(".soma")).unwrap();
+        Handlebars::new()
+            .render_templates(
+                Templates::Binary,
+                &TemplateOverrides::new(),
+                &manifest.solidify().unwrap(),
+                output_dir.path(),
+            )
+            .unwrap();
+
+        let dockerfile = fs::read_to_string(output_dir.path().join("Dockerfile")).unwrap();
+        assert!(dockerfile.contains("FROM gcc:9 AS builder"));
+        assert!(dockerfile.contains(
+            r#"COPY --from=builder ["/build/out/simple-bof", "/home/simple-bof/simple-bof"]"#
+        ));
+        let configure_permissions =
+            fs::read_to_string(output_dir.path().join(".soma/configure_permissions.sh")).unwrap();
+        assert!(configure_permissions.contains(r#"chmod 550 "/home/simple-bof/simple-bof""#));
+    }
+
+    #[test]
+    fn test_render_binary_fields() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            name = "simple-bof"
+
+            [binary]
+            os = "ubuntu:16.04"
+            cmd = "./simple-bof"
+            executable = []
+            readonly = []
+            packages = ["libc6-i386", "python3=3.5.1-3"]
+            setup = ["echo 1 > /proc/sys/kernel/randomize_va_space"]
+
+            [binary.env]
+            BANNER = "say \"hello\" to $PROB"
+            "#,
+        )
+        .unwrap();
+
+        let output_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(output_dir.path().join(".soma")).unwrap();
+        Handlebars::new()
+            .render_templates(
+                Templates::Binary,
+                &TemplateOverrides::new(),
+                &manifest.solidify().unwrap(),
+                output_dir.path(),
+            )
+            .unwrap();
+
+        let dockerfile = fs::read_to_string(output_dir.path().join("Dockerfile")).unwrap();
+        let lines: Vec<_> = dockerfile.lines().collect();
+        assert!(lines.contains(
+            &"RUN apt-get -qq update && apt-get install -yqq socat libc6-i386 python3=3.5.1-3"
+        ));
+        assert!(lines.contains(&r#"ENV BANNER "say \"hello\" to \$PROB""#));
+        assert!(lines.contains(&"RUN echo 1 > /proc/sys/kernel/randomize_va_space"));
+    }
+
+    #[test]
+    fn test_render_overrides() {
+        let template_dir = tempfile::tempdir().unwrap();
+        let partial_dir = template_dir.path().join(PARTIAL_DIR_NAME);
+        fs::create_dir(&partial_dir).unwrap();
+        fs::write(partial_dir.join("timezone.hbs"), "ENV TZ=UTC\n").unwrap();
+        fs::write(template_dir.path().join("motd"), "Welcome to {{ name }}\n").unwrap();
+
+        let overrides =
+            TemplateOverrides::from_dir(template_dir.path(), template_dir.path()).unwrap();
+        let output_dir = render(&overrides).unwrap();
+        let dockerfile = fs::read_to_string(output_dir.path().join("Dockerfile")).unwrap();
+        assert!(dockerfile.contains("ENV TZ=UTC"));
+        assert!(!dockerfile.contains("#ENV TZ=Asia/Seoul"));
+        assert_eq!(
+            fs::read_to_string(output_dir.path().join(".soma/motd")).unwrap(),
+            "Welcome to simple-bof\n"
+        );
+
+        // Strict mode rejects variables missing from the manifest
+        fs::write(template_dir.path().join("motd"), "{{ flag }}\n").unwrap();
+        let overrides =
+            TemplateOverrides::from_dir(template_dir.path(), template_dir.path()).unwrap();
+        assert!(render(&overrides).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_overrides_outside_root() {
+        let repo_dir = tempfile::tempdir().unwrap();
+        let secret_dir = tempfile::tempdir().unwrap();
+        let secret_path = secret_dir.path().join("id_rsa");
+        fs::write(&secret_path, "secret\n").unwrap();
+
+        let template_dir = repo_dir.path().join(TEMPLATE_DIR_PATH);
+        fs::create_dir_all(&template_dir).unwrap();
+        std::os::unix::fs::symlink(&secret_path, template_dir.join("Dockerfile")).unwrap();
+        assert!(TemplateOverrides::from_dir(&template_dir, repo_dir.path()).is_err());
+
+        fs::remove_file(template_dir.join("Dockerfile")).unwrap();
+        fs::write(repo_dir.path().join("motd"), "Welcome\n").unwrap();
+        std::os::unix::fs::symlink(repo_dir.path().join("motd"), template_dir.join("motd"))
+            .unwrap();
+        assert!(TemplateOverrides::from_dir(&template_dir, repo_dir.path()).is_ok());
+    }
+}
@@ -4,6 +4,11 @@ set -e
 {{ #each binary.file_entries }}
 chmod {{ permissions }} "{{ target_path }}"
 {{ /each }}
+{{ #if build }}
+{{ #each build.artifacts }}
+chmod {{ permissions }} "{{ target_path }}"
+{{ /each }}
+{{ /if }}
 
 chown -R root:$PROB /home/$PROB
 chmod 555 /.soma/start.sh
@@ -9,13 +9,14 @@ mod common;
 
 #[test]
 fn test_add_remove() {
+    let simple_bof = simple_bof_git();
     let (_, mut data_dir) = temp_data_dir();
-    let mut env = test_env(&mut data_dir);
+    let mut env = fake_env(&mut data_dir);
     let mut runtime = default_runtime();
 
     // Repository names should follow NameString rules
     let repo_name = SIMPLE_BOF_REPO_NAME.to_sanitized();
-    assert!(add(&mut env, SIMPLE_BOF_GIT, None).is_ok());
+    assert!(add(&mut env, &simple_bof.url(), None).is_ok());
 
     assert!(env.repo_manager().repo_exists(&repo_name));
     let local_path = env
@@ -31,11 +32,12 @@ fn test_add_remove() {
 
 #[test]
 fn test_add_with_name() {
+    let simple_bof = simple_bof_git();
     let (_, mut data_dir) = temp_data_dir();
-    let mut env = test_env(&mut data_dir);
+    let mut env = fake_env(&mut data_dir);
 
     let repo_name = "complicated-bof".to_sanitized();
-    assert!(add(&mut env, SIMPLE_BOF_GIT, Some(&repo_name)).is_ok());
+    assert!(add(&mut env, &simple_bof.url(), Some(&repo_name)).is_ok());
 
     assert!(env.repo_manager().repo_exists(&repo_name));
     let local_path = env
@@ -48,16 +50,17 @@ fn test_add_with_name() {
 
 #[test]
 fn test_prob_search() {
+    let simple_bof = simple_bof_git();
     let (_, mut data_dir) = temp_data_dir();
-    let mut env = test_env(&mut data_dir);
+    let mut env = fake_env(&mut data_dir);
     let mut runtime = default_runtime();
 
     let repo_name_1 = "bof1".to_sanitized();
     let repo_name_2 = "bof2".to_sanitized();
 
-    assert!(add(&mut env, SIMPLE_BOF_GIT, Some(&repo_name_1)).is_ok());
+    assert!(add(&mut env, &simple_bof.url(), Some(&repo_name_1)).is_ok());
 
-    assert!(add(&mut env, SIMPLE_BOF_GIT, Some(&repo_name_2)).is_ok());
+    assert!(add(&mut env, &simple_bof.url(), Some(&repo_name_2)).is_ok());
 
     assert_matches!(
         env.repo_manager()
@@ -80,12 +83,13 @@ fn test_prob_search() {
 
 #[test]
 fn test_soma_list() {
+    let bata_list = bata_list_git();
     let (_, mut data_dir) = temp_data_dir();
-    let mut env = test_env(&mut data_dir);
+    let mut env = fake_env(&mut data_dir);
     let mut runtime = default_runtime();
 
     let repo_name = BATA_LIST_REPO_NAME.to_sanitized();
-    assert!(add(&mut env, BATA_LIST_GIT, None).is_ok());
+    assert!(add(&mut env, &bata_list.url(), None).is_ok());
 
     assert!(env.repo_manager().repo_exists(&repo_name));
     let local_path = env
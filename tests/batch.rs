@@ -0,0 +1,157 @@
+use std::fs;
+
+use fs_extra::dir;
+use matches::assert_matches;
+
+use soma::docker::{self, container_from_prob_running, image_exists, image_from_repo_exists};
+use soma::ops::{add, build_batch, clean_batch, run, run_batch, stop_batch, RunOptions};
+use soma::prelude::*;
+
+pub use self::common::*;
+
+mod common;
+
+#[test]
+fn test_batch_query() {
+    let (_, mut data_dir) = temp_data_dir();
+    let mut env = fake_env(&mut data_dir);
+
+    let repo_path = fs::canonicalize("test_repo/abc").unwrap();
+    assert!(add(
+        &mut env,
+        repo_path.to_str().unwrap(),
+        Some(&"test".to_sanitized())
+    )
+    .is_ok());
+    assert!(add(
+        &mut env,
+        repo_path.to_str().unwrap(),
+        Some(&"other".to_sanitized())
+    )
+    .is_ok());
+
+    let repo_manager = env.repo_manager();
+    assert_eq!(repo_manager.select_probs("test").unwrap().len(), 3);
+    assert_eq!(repo_manager.select_probs("test.*").unwrap().len(), 3);
+    assert_eq!(repo_manager.select_probs("*.a").unwrap().len(), 2);
+    assert_eq!(repo_manager.select_probs("?").unwrap().len(), 6);
+    assert_eq!(repo_manager.select_probs("other.b").unwrap().len(), 1);
+    assert_matches!(
+        repo_manager.select_probs("a").map_err(error_downcast),
+        Err(Ok(SomaError::ProblemQueryAmbiguous))
+    );
+    assert_matches!(
+        repo_manager.select_probs("pwn-*").map_err(error_downcast),
+        Err(Ok(SomaError::ProblemNotFound))
+    );
+}
+
+#[test]
+fn test_batch_build_run_stop_clean() {
+    let (_, mut data_dir) = temp_data_dir();
+    let mut env = fake_env(&mut data_dir);
+    let mut runtime = default_runtime();
+
+    let repo_path = fs::canonicalize("test_repo/abc").unwrap();
+    assert!(add(
+        &mut env,
+        repo_path.to_str().unwrap(),
+        Some(&"test".to_sanitized())
+    )
+    .is_ok());
+    let problems = env.repo_manager().select_probs("test").unwrap();
+
+    assert!(build_batch(&env, &problems, true, 2, &mut runtime).is_ok());
+    assert!(env.printer().output().contains("3 succeeded, 0 failed"));
+
+    // A failure does not stop the remaining problems
+    assert!(run(&env, "test.b", 40000, &RunOptions::default(), &mut runtime).is_ok());
+    assert_matches!(
+        run_batch(
+            &env,
+            &problems,
+            41000..,
+            &RunOptions::default(),
+            &mut runtime
+        )
+        .map_err(error_downcast),
+        Err(Ok(SomaError::BatchOperationFailed))
+    );
+    assert!(env
+        .printer()
+        .output()
+        .contains("test.a: running on port 41000"));
+    assert!(env
+        .printer()
+        .output()
+        .contains("test.c: running on port 41002"));
+    assert!(env.printer().output().contains("2 succeeded, 1 failed"));
+
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    for problem in &problems {
+        assert!(container_from_prob_running(&containers, problem));
+    }
+    let public_ports: Vec<_> = containers
+        .iter()
+        .flat_map(|container| &container.container().ports)
+        .filter_map(|port| port.public_port)
+        .collect();
+    assert!(public_ports.contains(&40000));
+    assert!(public_ports.contains(&41000));
+    assert!(public_ports.contains(&41002));
+
+    assert!(stop_batch(&env, &problems, None, &mut runtime).is_ok());
+    assert!(clean_batch(&env, &problems, &mut runtime).is_ok());
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    assert!(!image_from_repo_exists(&images, "test"));
+}
+
+#[test]
+fn test_parallel_build_logs() {
+    let (_, mut data_dir) = temp_data_dir();
+    let log_dir = data_dir.root_path().join("build-logs");
+    let mut env = fake_env(&mut data_dir);
+    let mut runtime = default_runtime();
+
+    // Problem "b" refers to a file that does not exist
+    let repo_dir = tempdir();
+    let mut copy_options = dir::CopyOptions::new();
+    copy_options.copy_inside = true;
+    dir::copy("test_repo/abc", repo_dir.path().join("test"), &copy_options).unwrap();
+    let manifest_path = repo_dir.path().join("test/b/soma.toml");
+    let manifest = fs::read_to_string(&manifest_path).unwrap();
+    fs::write(
+        &manifest_path,
+        manifest.replace("executable = []", "executable = [{ path = \"missing\" }]"),
+    )
+    .unwrap();
+
+    let repo_path = repo_dir.path().join("test");
+    assert!(add(&mut env, repo_path.to_str().unwrap(), None).is_ok());
+    let problems = env.repo_manager().select_probs("test").unwrap();
+
+    assert_matches!(
+        build_batch(&env, &problems, true, 2, &mut runtime).map_err(error_downcast),
+        Err(Ok(SomaError::BatchOperationFailed))
+    );
+    let output = env.printer().output().to_owned();
+    assert!(output.contains("test.a: Step 1/"));
+    assert!(output.contains("test.a: built"));
+    assert!(output.contains("test.b: failed"));
+    assert!(output.contains("2 succeeded, 1 failed"));
+
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    for problem in &problems {
+        let image_name = problem.docker_image_name(env.username());
+        assert_eq!(
+            image_exists(&images, &image_name),
+            problem.prob_name() != "b"
+        );
+    }
+
+    let log_a = fs::read_to_string(log_dir.join("test.a.log")).unwrap();
+    assert!(log_a.contains("Successfully tagged"));
+    let log_b = fs::read_to_string(log_dir.join("test.b.log")).unwrap();
+    assert!(log_b.contains("Loading manifest..."));
+    assert!(log_dir.join("test.c.log").is_file());
+}
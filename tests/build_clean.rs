@@ -8,11 +8,12 @@ mod common;
 
 #[test]
 fn test_build_clean() {
+    let simple_bof = simple_bof_git();
     let (_, mut data_dir) = temp_data_dir();
     let mut env = test_env(&mut data_dir);
     let mut runtime = default_runtime();
 
-    assert!(add(&mut env, SIMPLE_BOF_GIT, None).is_ok());
+    assert!(add(&mut env, &simple_bof.url(), None).is_ok());
 
     let prob_query = "simple-bof";
     let problem = env
@@ -22,7 +23,7 @@ fn test_build_clean() {
     let repo_name = problem.repo_name();
     let image_name = problem.docker_image_name(env.username());
 
-    assert!(build(&env, prob_query, &mut runtime).is_ok());
+    assert!(build(&env, prob_query, true, &mut runtime).is_ok());
     let images = runtime.block_on(docker::list_images(&env)).unwrap();
     assert!(image_exists(&images, &image_name));
     assert!(image_from_repo_exists(&images, repo_name));
@@ -37,11 +38,12 @@ fn test_build_clean() {
 
 #[test]
 fn test_build_clean_list() {
+    let bata_list = bata_list_git();
     let (_, mut data_dir) = temp_data_dir();
     let mut env = test_env(&mut data_dir);
     let mut runtime = default_runtime();
 
-    assert!(add(&mut env, BATA_LIST_GIT, None).is_ok());
+    assert!(add(&mut env, &bata_list.url(), None).is_ok());
 
     let prob_query = "r0pbaby";
     let problem = env
@@ -51,7 +53,7 @@ fn test_build_clean_list() {
     let repo_name = problem.repo_name();
     let image_name = problem.docker_image_name(env.username());
 
-    assert!(build(&env, prob_query, &mut runtime).is_ok());
+    assert!(build(&env, prob_query, true, &mut runtime).is_ok());
     let images = runtime.block_on(docker::list_images(&env)).unwrap();
     assert!(image_exists(&images, &image_name));
     assert!(image_from_repo_exists(&images, repo_name));
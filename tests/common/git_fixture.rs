@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use git2::{Commit, Oid, Repository, Signature};
+use tempfile::TempDir;
+use url::Url;
+
+const FILE_MODE_BLOB: i32 = 0o100_644;
+const FILE_MODE_EXECUTABLE: i32 = 0o100_755;
+const FILE_MODE_TREE: i32 = 0o040_000;
+
+// Bare git repository synthesized from directories under test_repo,
+// which is served to GitBackend through a file:// URL
+pub struct GitFixture {
+    _root: TempDir,
+    path: PathBuf,
+    repository: Repository,
+}
+
+impl GitFixture {
+    // The repository is named <name>.git so that its URL resolves to the given repository name
+    pub fn new(name: &str) -> GitFixture {
+        let root = super::tempdir();
+        let path = root.path().join(format!("{}.git", name));
+        let repository = Repository::init_bare(&path).expect("Failed to create a bare repository");
+        GitFixture {
+            _root: root,
+            path,
+            repository,
+        }
+    }
+
+    // Creates a repository whose master branch contains a single commit of the directory
+    pub fn from_dir(name: &str, dir: impl AsRef<Path>) -> GitFixture {
+        let fixture = GitFixture::new(name);
+        fixture.commit_dir("master", dir, "Initial commit");
+        fixture
+    }
+
+    pub fn url(&self) -> String {
+        Url::from_file_path(&self.path)
+            .expect("Failed to convert the repository path to URL")
+            .into_string()
+    }
+
+    // Replaces the contents of the branch with the directory on top of its history
+    pub fn commit_dir(&self, branch: &str, dir: impl AsRef<Path>, message: &str) -> Oid {
+        let reference = format!("refs/heads/{}", branch);
+        let parent = self
+            .repository
+            .find_reference(&reference)
+            .and_then(|reference| reference.peel_to_commit())
+            .ok();
+        let parents: Vec<&Commit> = parent.iter().collect();
+        self.commit(Some(&reference), dir.as_ref(), message, &parents)
+    }
+
+    // Points the branch to a new root commit of the directory, as a force push would do
+    pub fn rewrite_dir(&self, branch: &str, dir: impl AsRef<Path>, message: &str) -> Oid {
+        let commit_id = self.commit(None, dir.as_ref(), message, &[]);
+        self.repository
+            .reference(&format!("refs/heads/{}", branch), commit_id, true, message)
+            .expect("Failed to rewrite the branch");
+        commit_id
+    }
+
+    pub fn tag(&self, tag_name: &str, commit_id: Oid) {
+        let commit = self
+            .repository
+            .find_object(commit_id, None)
+            .expect("Failed to find the commit");
+        self.repository
+            .tag_lightweight(tag_name, &commit, false)
+            .expect("Failed to create the tag");
+    }
+
+    fn commit(
+        &self,
+        reference: Option<&str>,
+        dir: &Path,
+        message: &str,
+        parents: &[&Commit],
+    ) -> Oid {
+        let tree_id = self.write_tree(dir);
+        let tree = self
+            .repository
+            .find_tree(tree_id)
+            .expect("Failed to find the written tree");
+        let signature = Signature::now("Soma Test", "soma-test@localhost")
+            .expect("Failed to create a signature");
+        self.repository
+            .commit(reference, &signature, &signature, message, &tree, parents)
+            .expect("Failed to commit the directory")
+    }
+
+    fn write_tree(&self, dir: &Path) -> Oid {
+        let mut builder = self
+            .repository
+            .treebuilder(None)
+            .expect("Failed to create a tree builder");
+        for entry in fs::read_dir(dir).expect("Failed to read the fixture directory") {
+            let entry = entry.expect("Failed to read the fixture directory");
+            let path = entry.path();
+            let (object_id, file_mode) = if path.is_dir() {
+                (self.write_tree(&path), FILE_MODE_TREE)
+            } else {
+                let blob_id = self
+                    .repository
+                    .blob_path(&path)
+                    .expect("Failed to write the blob");
+                let file_mode = if is_executable(&path) {
+                    FILE_MODE_EXECUTABLE
+                } else {
+                    FILE_MODE_BLOB
+                };
+                (blob_id, file_mode)
+            };
+            builder
+                .insert(entry.file_name(), object_id, file_mode)
+                .expect("Failed to insert the tree entry");
+        }
+        builder.write().expect("Failed to write the tree")
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .map(|metadata| metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    false
+}
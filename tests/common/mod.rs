@@ -6,24 +6,22 @@ use std::path::Path;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-use hyper::client::connect::Connect;
 use tempfile::TempDir;
 use tokio::runtime::current_thread::Runtime;
 
 use soma::data_dir::DataDirectory;
-use soma::docker::connect_default;
+use soma::docker::{connect_default, ContainerEngine, DockerEngine, FakeEngine};
 use soma::{Environment, NameString};
 
+pub use self::git_fixture::GitFixture;
 pub use self::test_printer::TestPrinter;
 
+mod git_fixture;
 mod test_printer;
 
 static COUNTER: AtomicUsize = AtomicUsize::new(0);
 
-pub const SIMPLE_BOF_GIT: &str = "https://github.com/PLUS-POSTECH/simple-bof.git";
 pub const SIMPLE_BOF_REPO_NAME: &str = "simple-bof";
-
-pub const BATA_LIST_GIT: &str = "https://github.com/PLUS-POSTECH/soma-bata-list.git";
 pub const BATA_LIST_REPO_NAME: &str = "soma-bata-list";
 
 pub trait GuaranteedSanitization {
@@ -42,17 +40,38 @@ impl GuaranteedSanitization for String {
     }
 }
 
-pub fn test_env(data_dir: &mut DataDirectory) -> Environment<impl Connect, TestPrinter> {
+pub fn test_env(
+    data_dir: &mut DataDirectory,
+) -> Environment<'_, impl ContainerEngine, TestPrinter> {
     Environment::new(
         // This format should follow NameString rules
         format!("soma-test-{}", COUNTER.fetch_add(1, Ordering::SeqCst)).to_sanitized(),
         data_dir,
-        connect_default().expect("Failed to connect to docker"),
+        DockerEngine::new(connect_default().expect("Failed to connect to docker")),
         TestPrinter::new(),
     )
     .expect("Failed to create environment")
 }
 
+// Environment backed by an in-memory engine, which does not require a docker daemon
+pub fn fake_env(data_dir: &mut DataDirectory) -> Environment<'_, FakeEngine, TestPrinter> {
+    Environment::new(
+        format!("soma-test-{}", COUNTER.fetch_add(1, Ordering::SeqCst)).to_sanitized(),
+        data_dir,
+        FakeEngine::new(),
+        TestPrinter::new(),
+    )
+    .expect("Failed to create environment")
+}
+
+pub fn simple_bof_git() -> GitFixture {
+    GitFixture::from_dir(SIMPLE_BOF_REPO_NAME, "test_repo/simple-bof")
+}
+
+pub fn bata_list_git() -> GitFixture {
+    GitFixture::from_dir(BATA_LIST_REPO_NAME, "test_repo/soma-bata-list")
+}
+
 pub fn tempdir() -> TempDir {
     tempfile::tempdir().expect("Failed to create temporary directory")
 }
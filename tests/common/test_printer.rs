@@ -1,14 +1,17 @@
+use soma::event::Event;
 use soma::Printer;
 
 #[derive(Default)]
 pub struct TestPrinter {
     output: String,
+    events: Vec<Event>,
 }
 
 impl TestPrinter {
     pub fn new() -> TestPrinter {
         TestPrinter {
             output: String::new(),
+            events: Vec::new(),
         }
     }
 }
@@ -17,6 +20,10 @@ impl TestPrinter {
     pub fn output(&self) -> &str {
         &self.output
     }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
 }
 
 impl Printer for TestPrinter {
@@ -34,4 +41,13 @@ impl Printer for TestPrinter {
         self.output.push_str(message);
         self.output.push('\n');
     }
+
+    fn report(&mut self, event: &Event) {
+        self.events.push(event.clone());
+        self.write_line(&event.to_string());
+    }
+
+    fn report_at(&mut self, _handle: &Self::Handle, event: &Event) {
+        self.report(event)
+    }
 }
@@ -0,0 +1,83 @@
+use std::fs;
+
+use matches::assert_matches;
+
+use soma::config::{ConfigManager, PortRange};
+use soma::data_dir::DataDirectory;
+use soma::ops::{add, available_ports, build_batch, run, run_batch, RunOptions};
+use soma::prelude::*;
+
+pub use self::common::*;
+
+mod common;
+
+#[test]
+fn test_config_persistence() {
+    let (temp_dir, data_dir) = temp_data_dir();
+    {
+        let mut config_manager = data_dir.register::<ConfigManager>().unwrap();
+        assert!(config_manager.config().port_range().is_none());
+        assert!(config_manager.set("port_range", "30000-30100").is_ok());
+        assert!(config_manager.set("username", "ctf-admin").is_ok());
+        assert_matches!(
+            config_manager
+                .set("build_jobs", "many")
+                .map_err(error_downcast),
+            Err(Ok(SomaError::InvalidConfigValue))
+        );
+    }
+    drop(data_dir);
+
+    let config_path = temp_dir.path().join("config/config.toml");
+    assert!(fs::read_to_string(&config_path)
+        .unwrap()
+        .contains("port_range = \"30000-30100\""));
+
+    let data_dir = DataDirectory::at_path(temp_dir.path()).unwrap();
+    let config_manager = data_dir.register::<ConfigManager>().unwrap();
+    let config = config_manager.config();
+    assert_eq!(
+        config.port_range(),
+        Some(PortRange::new(30000, 30100).unwrap())
+    );
+    assert_eq!(config.username().unwrap(), "ctf-admin");
+    assert_eq!(config.build_jobs(), None);
+}
+
+#[test]
+fn test_run_on_available_ports() {
+    let (_, mut data_dir) = temp_data_dir();
+    let mut env = fake_env(&mut data_dir);
+    let mut runtime = default_runtime();
+
+    let repo_path = fs::canonicalize("test_repo/abc").unwrap();
+    assert!(add(
+        &mut env,
+        repo_path.to_str().unwrap(),
+        Some(&"test".to_sanitized())
+    )
+    .is_ok());
+    let problems = env.repo_manager().select_probs("test").unwrap();
+    assert!(build_batch(&env, &problems, true, 2, &mut runtime).is_ok());
+
+    assert!(run(&env, "test.a", 42000, &RunOptions::default(), &mut runtime).is_ok());
+    let port_range = PortRange::new(42000, 42002).unwrap();
+    let ports = available_ports(&env, port_range, &mut runtime).unwrap();
+    assert_eq!(ports, vec![42001, 42002]);
+
+    assert!(run_batch(
+        &env,
+        &problems[1..],
+        ports,
+        &RunOptions::default(),
+        &mut runtime
+    )
+    .is_ok());
+    assert!(env
+        .printer()
+        .output()
+        .contains("test.c: running on port 42002"));
+    assert!(available_ports(&env, port_range, &mut runtime)
+        .unwrap()
+        .is_empty());
+}
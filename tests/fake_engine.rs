@@ -0,0 +1,622 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use soma::config::{PortRange, DEFAULT_BIND_ADDRESS};
+use tempfile::TempDir;
+use tokio::runtime::current_thread::Runtime;
+
+use soma::data_dir::DataDirectory;
+use soma::docker;
+use soma::docker::{
+    container_exists, container_from_prob_exists, container_from_prob_running, image_exists,
+    ContainerEngine, FakeEngine, RestartPolicy, DEFAULT_INSTANCE,
+};
+use soma::doctor::{check_engine, check_repository_manager, check_resources, CheckStatus};
+use soma::error::Error as SomaError;
+use soma::event::{Event, Stage};
+use soma::ops::{
+    add, available_ports, build, build_outdated, clean, collect_garbage, exec, export,
+    find_garbage, import, instances, logs, outdated_problems, remove, restart, run, stop, update,
+    GarbageReason, RunOptions,
+};
+use soma::Environment;
+
+pub use self::common::*;
+
+mod common;
+
+const FAKE_BOF_MANIFEST: &str = r#"name = "fake-bof"
+
+[binary]
+os = "ubuntu:16.04"
+cmd = "./fake-bof"
+
+[[binary.executable]]
+path = "fake-bof"
+public = true
+
+[[binary.readonly]]
+path = "flag"
+"#;
+
+fn create_local_repo(parent: impl AsRef<Path>) -> PathBuf {
+    let repo_path = parent.as_ref().join("fake-bof");
+    fs::create_dir(&repo_path).unwrap();
+    fs::write(repo_path.join("soma.toml"), FAKE_BOF_MANIFEST).unwrap();
+    fs::write(repo_path.join("fake-bof"), "#!/bin/sh\n").unwrap();
+    fs::write(repo_path.join("flag"), "flag{fake}\n").unwrap();
+    repo_path
+}
+
+// Data directory and a copy of fake-bof, which outlive the environment borrowing them
+struct Fixture {
+    data_dir: DataDirectory,
+    _data_root: TempDir,
+    _repo_dir: TempDir,
+    repo_path: PathBuf,
+}
+
+impl Fixture {
+    fn new() -> Fixture {
+        let (data_root, data_dir) = temp_data_dir();
+        let repo_dir = tempdir();
+        let repo_path = create_local_repo(repo_dir.path());
+        Fixture {
+            data_dir,
+            _data_root: data_root,
+            _repo_dir: repo_dir,
+            repo_path,
+        }
+    }
+
+    // Fake environment with fake-bof added, which every test starts from
+    fn setup(&mut self) -> (Environment<'_, FakeEngine, TestPrinter>, Runtime, &Path) {
+        let mut env = fake_env(&mut self.data_dir);
+        assert!(add(&mut env, self.repo_path.to_str().unwrap(), None).is_ok());
+        (env, default_runtime(), &self.repo_path)
+    }
+}
+
+#[test]
+fn test_fake_build_run_stop_clean() {
+    let mut fixture = Fixture::new();
+    let (env, mut runtime, _) = fixture.setup();
+
+    let prob_query = "fake-bof";
+    let problem = env
+        .repo_manager()
+        .search_prob(prob_query)
+        .expect("Problem not found");
+    let image_name = problem.docker_image_name(env.username());
+
+    assert!(build(&env, prob_query, true, &mut runtime).is_ok());
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    assert_eq!(images.len(), 1);
+    assert!(image_exists(&images, &image_name));
+    assert!(images[0].base_digest().is_some());
+
+    // Rebuilding moves the tag to the new image
+    assert!(build(&env, prob_query, true, &mut runtime).is_ok());
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    assert!(image_exists(&images, &image_name));
+    assert_eq!(
+        images.iter().filter(|image| !image.is_dangling()).count(),
+        1
+    );
+
+    let container_id = run(
+        &env,
+        prob_query,
+        31337,
+        &RunOptions::default(),
+        &mut runtime,
+    )
+    .unwrap();
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    assert!(container_exists(&containers, &container_id));
+    let ports = &containers[0].container().ports;
+    assert_eq!(ports[0].ip.as_deref(), Some("127.0.0.1"));
+    assert_eq!(ports[0].public_port, Some(31337));
+    assert!(container_from_prob_running(&containers, &problem));
+
+    // Problem container should be running exclusively
+    assert!(run(
+        &env,
+        prob_query,
+        31337,
+        &RunOptions::default(),
+        &mut runtime
+    )
+    .is_err());
+    // Images in use cannot be cleaned
+    assert!(clean(&env, prob_query, &mut runtime).is_err());
+
+    assert!(stop(&env, prob_query, None, &mut runtime).is_ok());
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    assert!(!container_from_prob_exists(&containers, &problem));
+    assert!(stop(&env, prob_query, None, &mut runtime).is_err());
+
+    assert!(clean(&env, prob_query, &mut runtime).is_ok());
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    assert!(!image_exists(&images, &image_name));
+}
+
+#[test]
+fn test_fake_networks() {
+    let mut fixture = Fixture::new();
+    let (mut env, mut runtime, repo_path) = fixture.setup();
+    assert!(build(&env, "fake-bof", true, &mut runtime).is_ok());
+    let problem = env.repo_manager().search_prob("fake-bof").unwrap();
+    let network_name = problem.docker_network_name(env.username(), DEFAULT_INSTANCE);
+
+    assert!(run(
+        &env,
+        "fake-bof",
+        31337,
+        &RunOptions::default(),
+        &mut runtime
+    )
+    .is_ok());
+    let networks = runtime
+        .block_on(docker::list_networks_from_prob(&env, &problem, None))
+        .unwrap();
+    assert_eq!(networks.len(), 1);
+    assert_eq!(networks[0].name, network_name);
+    assert!(!networks[0].internal);
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    assert_eq!(
+        containers[0].container().networks,
+        vec![network_name.clone()]
+    );
+
+    assert!(stop(&env, "fake-bof", None, &mut runtime).is_ok());
+    assert!(runtime
+        .block_on(docker::list_networks_from_prob(&env, &problem, None))
+        .unwrap()
+        .is_empty());
+
+    let options = RunOptions {
+        internal: true,
+        ..Default::default()
+    };
+    assert!(run(&env, "fake-bof", 31337, &options, &mut runtime).is_ok());
+    let networks = runtime
+        .block_on(docker::list_networks_from_prob(&env, &problem, None))
+        .unwrap();
+    assert_eq!(networks.len(), 1);
+    assert!(networks[0].internal);
+    assert!(env.printer().events().contains(&Event::NetworkCreated {
+        name: network_name.clone(),
+        internal: true,
+    }));
+
+    // The problem container publishes nothing, and the proxy publishes its port
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    assert_eq!(containers.len(), 1);
+    assert!(containers[0].container().ports.is_empty());
+    let proxies = runtime.block_on(docker::list_proxies(&env)).unwrap();
+    assert_eq!(proxies.len(), 1);
+    let proxy = proxies[0].container();
+    assert_eq!(proxy.state, "running");
+    assert_eq!(proxy.ports[0].public_port, Some(31337));
+    assert_eq!(
+        proxy.networks,
+        vec!["bridge".to_owned(), network_name.clone()]
+    );
+    let ports = available_ports(&env, PortRange::new(31337, 31338).unwrap(), &mut runtime).unwrap();
+    assert_eq!(ports, vec![31338]);
+
+    assert!(stop(&env, "fake-bof", None, &mut runtime).is_ok());
+    assert!(runtime
+        .block_on(env.engine().list_containers(Default::default()))
+        .unwrap()
+        .is_empty());
+    assert!(runtime
+        .block_on(docker::list_networks_from_prob(&env, &problem, None))
+        .unwrap()
+        .is_empty());
+
+    // The manifest can ask for an internal network without the option
+    let manifest = FAKE_BOF_MANIFEST.replace("[binary]", "internal = true\n\n[binary]");
+    fs::write(repo_path.join("soma.toml"), manifest).unwrap();
+    assert!(update(&mut env, &"fake-bof".to_sanitized(), false, &mut runtime).is_ok());
+    assert!(run(
+        &env,
+        "fake-bof",
+        31337,
+        &RunOptions::default(),
+        &mut runtime
+    )
+    .is_ok());
+    let networks = runtime
+        .block_on(docker::list_networks_from_prob(&env, &problem, None))
+        .unwrap();
+    assert!(networks[0].internal);
+    assert_eq!(
+        runtime.block_on(docker::list_proxies(&env)).unwrap().len(),
+        1
+    );
+}
+
+#[test]
+fn test_fake_instances() {
+    let mut fixture = Fixture::new();
+    let (env, mut runtime, _) = fixture.setup();
+    assert!(build(&env, "fake-bof", true, &mut runtime).is_ok());
+    let problem = env.repo_manager().search_prob("fake-bof").unwrap();
+
+    let alice = RunOptions {
+        instance: Some("alice".to_sanitized()),
+        ..Default::default()
+    };
+    let bob = RunOptions {
+        instance: Some("bob".to_sanitized()),
+        ..Default::default()
+    };
+    assert!(run(&env, "fake-bof", 31337, &alice, &mut runtime).is_ok());
+    assert!(run(&env, "fake-bof", 31338, &bob, &mut runtime).is_ok());
+    // Each instance runs exclusively
+    assert!(run(&env, "fake-bof", 31339, &alice, &mut runtime).is_err());
+    assert_eq!(
+        instances(&env, &problem, &mut runtime).unwrap(),
+        vec!["alice".to_owned(), "bob".to_owned()]
+    );
+    assert_eq!(
+        runtime
+            .block_on(docker::list_networks_from_prob(&env, &problem, None))
+            .unwrap()
+            .len(),
+        2
+    );
+
+    // Ambiguous without an instance name
+    assert!(matches!(
+        error_downcast(logs(&env, "fake-bof", None, &mut runtime).unwrap_err()),
+        Ok(SomaError::InstanceNotSpecified)
+    ));
+    assert!(logs(&env, "fake-bof", Some("alice"), &mut runtime).is_ok());
+    let output = exec(&env, "fake-bof", Some("bob"), vec!["id"], &mut runtime).unwrap();
+    assert_eq!(output.exit_code, 0);
+
+    assert!(stop(&env, "fake-bof", Some("alice"), &mut runtime).is_ok());
+    assert_eq!(
+        instances(&env, &problem, &mut runtime).unwrap(),
+        vec!["bob".to_owned()]
+    );
+    assert!(exec(&env, "fake-bof", Some("alice"), vec!["id"], &mut runtime).is_err());
+    assert!(logs(&env, "fake-bof", None, &mut runtime).is_ok());
+
+    assert!(stop(&env, "fake-bof", None, &mut runtime).is_ok());
+    assert!(instances(&env, &problem, &mut runtime).unwrap().is_empty());
+    assert!(runtime
+        .block_on(docker::list_networks_from_prob(&env, &problem, None))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_fake_restart() {
+    let mut fixture = Fixture::new();
+    let (mut env, mut runtime, repo_path) = fixture.setup();
+    assert!(build(&env, "fake-bof", true, &mut runtime).is_ok());
+    assert!(restart(&env, "fake-bof", None, false, &mut runtime).is_err());
+
+    let options = RunOptions {
+        bind_address: [0, 0, 0, 0].into(),
+        restart_policy: Some(RestartPolicy::UnlessStopped),
+        ..Default::default()
+    };
+    let container_id = run(&env, "fake-bof", 31337, &options, &mut runtime).unwrap();
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    assert_eq!(containers[0].restart_policy(), RestartPolicy::UnlessStopped);
+
+    // A crashed container starts again in place
+    runtime
+        .block_on(env.engine().stop_container(&container_id))
+        .unwrap();
+    assert_eq!(
+        restart(&env, "fake-bof", None, false, &mut runtime).unwrap(),
+        container_id
+    );
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    assert_eq!(containers[0].container().state, "running");
+
+    // Recreating keeps the port binding and the restart policy
+    let recreated_id = restart(&env, "fake-bof", None, true, &mut runtime).unwrap();
+    assert_ne!(recreated_id, container_id);
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    assert_eq!(containers.len(), 1);
+    assert_eq!(containers[0].binding(), Some(([0, 0, 0, 0].into(), 31337)));
+    assert_eq!(containers[0].restart_policy(), RestartPolicy::UnlessStopped);
+    assert_eq!(
+        containers[0].container().ports[0].ip.as_deref(),
+        Some("0.0.0.0")
+    );
+    assert!(stop(&env, "fake-bof", None, &mut runtime).is_ok());
+
+    // The manifest decides without the option
+    let manifest = FAKE_BOF_MANIFEST.replace("[binary]", "restart = \"on-failure\"\n\n[binary]");
+    fs::write(repo_path.join("soma.toml"), manifest).unwrap();
+    assert!(update(&mut env, &"fake-bof".to_sanitized(), false, &mut runtime).is_ok());
+    assert!(run(
+        &env,
+        "fake-bof",
+        31337,
+        &RunOptions::default(),
+        &mut runtime
+    )
+    .is_ok());
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    assert_eq!(containers[0].restart_policy(), RestartPolicy::OnFailure);
+}
+
+#[test]
+fn test_fake_healthcheck() {
+    let mut fixture = Fixture::new();
+    let (mut env, mut runtime, repo_path) = fixture.setup();
+
+    let manifest = format!(
+        "{}\n[healthcheck]\ncmd = \"false\"\ntimeout = 1\n",
+        FAKE_BOF_MANIFEST
+    );
+    fs::write(repo_path.join("soma.toml"), manifest).unwrap();
+    assert!(update(&mut env, &"fake-bof".to_sanitized(), false, &mut runtime).is_ok());
+    assert!(build(&env, "fake-bof", true, &mut runtime).is_ok());
+    let problem = env.repo_manager().search_prob("fake-bof").unwrap();
+
+    let result = run(
+        &env,
+        "fake-bof",
+        31337,
+        &RunOptions::default(),
+        &mut runtime,
+    );
+    assert!(matches!(
+        error_downcast(result.unwrap_err()),
+        Ok(SomaError::ContainerUnhealthy)
+    ));
+    assert!(env.printer().events().iter().any(|event| matches!(
+        event,
+        Event::ContainerUnhealthy { problem, .. } if problem == "fake-bof.fake-bof"
+    )));
+    // The failed container does not block the next run
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    assert!(!container_from_prob_exists(&containers, &problem));
+    assert!(runtime
+        .block_on(docker::list_networks_from_prob(&env, &problem, None))
+        .unwrap()
+        .is_empty());
+
+    // Binary problems probe the service port by default
+    fs::write(repo_path.join("soma.toml"), FAKE_BOF_MANIFEST).unwrap();
+    assert!(update(&mut env, &"fake-bof".to_sanitized(), false, &mut runtime).is_ok());
+    assert!(run(
+        &env,
+        "fake-bof",
+        31337,
+        &RunOptions::default(),
+        &mut runtime
+    )
+    .is_ok());
+}
+
+#[test]
+fn test_fake_crashed_container() {
+    let mut fixture = Fixture::new();
+    let (mut env, mut runtime, repo_path) = fixture.setup();
+
+    let template_dir = repo_path.join(".soma/templates");
+    fs::create_dir_all(&template_dir).unwrap();
+    fs::write(
+        template_dir.join("Dockerfile"),
+        "FROM {{ binary.os }}\nCMD [\"false\"]\n",
+    )
+    .unwrap();
+    assert!(update(&mut env, &"fake-bof".to_sanitized(), false, &mut runtime).is_ok());
+    assert!(build(&env, "fake-bof", true, &mut runtime).is_ok());
+    let problem = env.repo_manager().search_prob("fake-bof").unwrap();
+
+    // The exited container is removed without being stopped, and the run reports why it failed
+    let events_before_run = env.printer().events().len();
+    let result = run(
+        &env,
+        "fake-bof",
+        31337,
+        &RunOptions::default(),
+        &mut runtime,
+    );
+    assert!(matches!(
+        error_downcast(result.unwrap_err()),
+        Ok(SomaError::ContainerUnhealthy)
+    ));
+    assert!(!env.printer().events()[events_before_run..]
+        .iter()
+        .any(|event| matches!(event, Event::Warning(_))));
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    assert!(!container_from_prob_exists(&containers, &problem));
+    assert!(runtime
+        .block_on(docker::list_networks_from_prob(&env, &problem, None))
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_fake_garbage_collection() {
+    let mut fixture = Fixture::new();
+    let (env, mut runtime, _) = fixture.setup();
+    assert!(build(&env, "fake-bof", true, &mut runtime).is_ok());
+    assert!(find_garbage(&env, &mut runtime).unwrap().is_empty());
+
+    // Rebuilding leaves the previous image dangling
+    assert!(build(&env, "fake-bof", true, &mut runtime).is_ok());
+    let alice = RunOptions {
+        instance: Some("alice".to_sanitized()),
+        ..Default::default()
+    };
+    let stopped_id = run(&env, "fake-bof", 31337, &alice, &mut runtime).unwrap();
+    runtime
+        .block_on(env.engine().stop_container(&stopped_id))
+        .unwrap();
+    let running_id = run(
+        &env,
+        "fake-bof",
+        31338,
+        &RunOptions::default(),
+        &mut runtime,
+    )
+    .unwrap();
+
+    let garbage = find_garbage(&env, &mut runtime).unwrap();
+    assert_eq!(garbage.containers.len(), 1);
+    assert_eq!(garbage.containers[0].0.container().id, stopped_id);
+    assert_eq!(garbage.containers[0].1, GarbageReason::Stopped);
+    assert_eq!(garbage.images.len(), 1);
+    assert_eq!(garbage.images[0].1, GarbageReason::Dangling);
+    assert_eq!(garbage.images[0].0.image().repo_tags, vec!["<none>:<none>"]);
+    assert!(garbage.image_size() > 0);
+    // The stopped instance leaves its network behind
+    assert_eq!(garbage.networks.len(), 1);
+    assert!(garbage.networks[0].0.name.ends_with("alice"));
+    assert_eq!(garbage.networks[0].1, GarbageReason::Unused);
+
+    // A failed removal does not keep the rest of the garbage
+    runtime
+        .block_on(docker::remove_container(&env, &stopped_id))
+        .unwrap();
+    assert!(matches!(
+        error_downcast(collect_garbage(&env, &garbage, &mut runtime).unwrap_err()),
+        Ok(SomaError::BatchOperationFailed)
+    ));
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    assert!(!container_exists(&containers, &stopped_id));
+    assert!(container_exists(&containers, &running_id));
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    assert_eq!(images.len(), 1);
+    let networks = runtime.block_on(docker::list_networks(&env)).unwrap();
+    assert_eq!(networks.len(), 1);
+    assert!(find_garbage(&env, &mut runtime).unwrap().is_empty());
+}
+
+#[test]
+fn test_fake_events() {
+    let mut fixture = Fixture::new();
+    let (env, mut runtime, _) = fixture.setup();
+    assert!(build(&env, "fake-bof", true, &mut runtime).is_ok());
+    let container_id = run(
+        &env,
+        "fake-bof",
+        31337,
+        &RunOptions::default(),
+        &mut runtime,
+    )
+    .unwrap();
+
+    let printer = env.printer();
+    let events = printer.events();
+    assert!(events.contains(&Event::StageStarted(Stage::LoadingManifest)));
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, Event::BuildStep { step: 1, .. })));
+    assert!(events.contains(&Event::ImageBuilt("fake-bof.fake-bof".to_owned())));
+    assert!(events.contains(&Event::ContainerStarted {
+        problem: "fake-bof.fake-bof".to_owned(),
+        instance: DEFAULT_INSTANCE.to_owned(),
+        id: container_id,
+        bind_address: DEFAULT_BIND_ADDRESS,
+        port: 31337,
+    }));
+    assert!(printer
+        .output()
+        .contains("Built image for problem: 'fake-bof.fake-bof'"));
+}
+
+#[test]
+fn test_fake_export_import() {
+    let mut fixture = Fixture::new();
+    let (mut env, mut runtime, _) = fixture.setup();
+    assert!(build(&env, "fake-bof", true, &mut runtime).is_ok());
+
+    let archive_dir = tempdir();
+    let archive_path = archive_dir.path().join("fake-bof.tar.gz");
+    assert!(export(&env, "fake-bof", &archive_path, &mut runtime).is_ok());
+    // The temporary container which exports the filesystem is removed
+    assert!(runtime
+        .block_on(env.engine().list_containers(Default::default()))
+        .unwrap()
+        .is_empty());
+
+    let repo_name = "imported".to_sanitized();
+    assert!(import(&mut env, &archive_path, Some(&repo_name), &mut runtime).is_ok());
+    let problem = env.repo_manager().search_prob("imported.fake-bof").unwrap();
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    assert!(image_exists(
+        &images,
+        &problem.docker_image_name(env.username())
+    ));
+}
+
+#[test]
+fn test_fake_remove_in_use() {
+    let mut fixture = Fixture::new();
+    let (mut env, mut runtime, _) = fixture.setup();
+    assert!(build(&env, "fake-bof", false, &mut runtime).is_ok());
+
+    let repo_name = "fake-bof".to_sanitized();
+    assert!(remove(&mut env, &repo_name, &mut runtime).is_err());
+    assert!(clean(&env, "fake-bof", &mut runtime).is_ok());
+    assert!(remove(&mut env, &repo_name, &mut runtime).is_ok());
+}
+
+#[test]
+fn test_fake_outdated_image() {
+    let mut fixture = Fixture::new();
+    let (mut env, mut runtime, repo_path) = fixture.setup();
+    assert!(build(&env, "fake-bof", true, &mut runtime).is_ok());
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    assert!(images[0].content_hash().is_some());
+    assert!(outdated_problems(&env, &images).unwrap().is_empty());
+
+    // Updating the repository leaves the built image behind
+    fs::write(repo_path.join("flag"), "flag{updated}\n").unwrap();
+    let repo_name = "fake-bof".to_sanitized();
+    assert!(update(&mut env, &repo_name, false, &mut runtime).is_ok());
+    assert!(env
+        .printer()
+        .output()
+        .contains("Problem image outdated: 'fake-bof.fake-bof'"));
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    let outdated = outdated_problems(&env, &images).unwrap();
+    assert_eq!(outdated.len(), 1);
+    assert_eq!(outdated[0].prob_name(), "fake-bof");
+
+    assert!(build_outdated(&env, true, 2, &mut runtime).is_ok());
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    assert!(outdated_problems(&env, &images).unwrap().is_empty());
+
+    assert!(clean(&env, "fake-bof", &mut runtime).is_ok());
+}
+
+#[test]
+fn test_fake_doctor() {
+    let mut fixture = Fixture::new();
+    let (env, mut runtime, repo_path) = fixture.setup();
+    assert!(build(&env, "fake-bof", true, &mut runtime).is_ok());
+    assert_eq!(
+        check_engine(env.engine(), &mut runtime).status,
+        CheckStatus::Pass
+    );
+    assert_eq!(
+        check_resources(env.engine(), env.username(), &mut runtime).status,
+        CheckStatus::Pass
+    );
+    assert_eq!(
+        check_repository_manager(env.repo_manager()).status,
+        CheckStatus::Pass
+    );
+
+    // A local repository cannot be updated once its origin is gone
+    fs::remove_dir_all(&repo_path).unwrap();
+    let check = check_repository_manager(env.repo_manager());
+    assert_eq!(check.status, CheckStatus::Fail);
+    assert!(check.remedy.is_some());
+}
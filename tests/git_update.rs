@@ -0,0 +1,122 @@
+use std::fs;
+
+use fs_extra::dir;
+use matches::assert_matches;
+
+use soma::docker::{self, image_from_repo_exists};
+use soma::event::Event;
+use soma::ops::{add, build, clean, remove, update};
+use soma::prelude::*;
+
+pub use self::common::*;
+
+mod common;
+
+#[test]
+fn test_git_update_history() {
+    let fixture = GitFixture::from_dir("test", "test_repo/a");
+    let (_, mut data_dir) = temp_data_dir();
+    let mut env = fake_env(&mut data_dir);
+    let mut runtime = default_runtime();
+
+    let repo_name = "test".to_sanitized();
+    assert!(add(&mut env, &fixture.url(), None).is_ok());
+    assert!(env.repo_manager().search_prob("test.a").is_ok());
+    assert!(env.repo_manager().search_prob("test.b").is_err());
+
+    // Commits on other branches and tags are not followed
+    let ab_commit = fixture.commit_dir("develop", "test_repo/ab", "Add problem b");
+    fixture.tag("v1", ab_commit);
+    assert!(update(&mut env, &repo_name, false, &mut runtime).is_ok());
+    assert!(env.repo_manager().search_prob("test.b").is_err());
+
+    fixture.commit_dir("master", "test_repo/ab", "Add problem b");
+    assert!(update(&mut env, &repo_name, false, &mut runtime).is_ok());
+    assert!(env.repo_manager().search_prob("test.b").is_ok());
+    let local_path = env.repo_manager().get_repo(&repo_name).unwrap().path();
+    assert!(dir_contents_exists(&local_path, &["a", "b"]));
+
+    // Rewritten history replaces the local checkout
+    fixture.rewrite_dir("master", "test_repo/abc", "Squash problems");
+    assert!(update(&mut env, &repo_name, false, &mut runtime).is_ok());
+    assert!(env.repo_manager().search_prob("test.c").is_ok());
+    assert!(dir_contents_exists(&local_path, &["a", "b", "c"]));
+
+    assert!(remove(&mut env, &repo_name, &mut runtime).is_ok());
+    assert!(!env.repo_manager().repo_exists(&repo_name));
+}
+
+#[test]
+fn test_git_update_fetches_into_local_repository() {
+    let fixture = GitFixture::from_dir("test", "test_repo/ab");
+    let (_, mut data_dir) = temp_data_dir();
+    let mut env = fake_env(&mut data_dir);
+    let mut runtime = default_runtime();
+
+    let repo_name = "test".to_sanitized();
+    assert!(add(&mut env, &fixture.url(), None).is_ok());
+    let local_path = env.repo_manager().get_repo(&repo_name).unwrap().path();
+    // The local repository is kept, so a file inside of it survives updates
+    let marker_path = local_path.join(".git").join("soma-test-marker");
+    fs::write(&marker_path, "").unwrap();
+
+    let temp_dir = tempdir();
+    let changed_path = temp_dir.path().join("ab");
+    let mut copy_options = dir::CopyOptions::new();
+    copy_options.copy_inside = true;
+    dir::copy("test_repo/ab", &changed_path, &copy_options).unwrap();
+    let manifest_path = changed_path.join("a").join("soma.toml");
+    let manifest = fs::read_to_string(&manifest_path).unwrap();
+    fs::write(&manifest_path, format!("{}\n# changed\n", manifest)).unwrap();
+    fixture.commit_dir("master", &changed_path, "Change problem a");
+
+    assert!(update(&mut env, &repo_name, false, &mut runtime).is_ok());
+    assert!(marker_path.exists());
+    let events = env.printer().events().to_vec();
+    assert!(events.contains(&Event::ProblemChanged("test.a".to_owned())));
+    assert!(!events.contains(&Event::ProblemChanged("test.b".to_owned())));
+    assert!(fs::read_to_string(local_path.join("a").join("soma.toml"))
+        .unwrap()
+        .contains("# changed"));
+    // Nothing is left next to the local copy
+    assert_eq!(
+        fs::read_dir(local_path.parent().unwrap()).unwrap().count(),
+        1
+    );
+}
+
+#[test]
+fn test_git_update_unsupported() {
+    let fixture = GitFixture::from_dir("test", "test_repo/ab");
+    let (_, mut data_dir) = temp_data_dir();
+    let mut env = fake_env(&mut data_dir);
+    let mut runtime = default_runtime();
+
+    let repo_name = "test".to_sanitized();
+    assert!(add(&mut env, &fixture.url(), None).is_ok());
+    assert!(build(&env, "test.a", true, &mut runtime).is_ok());
+    assert!(build(&env, "test.b", true, &mut runtime).is_ok());
+
+    // image "test.b" should be removed before updating
+    fixture.commit_dir("master", "test_repo/a", "Remove problem b");
+    assert_matches!(
+        update(&mut env, &repo_name, false, &mut runtime).map_err(error_downcast),
+        Err(Ok(SomaError::UnsupportedUpdate))
+    );
+
+    // Images should be cleaned before removing the repository
+    assert_matches!(
+        remove(&mut env, &repo_name, &mut runtime).map_err(error_downcast),
+        Err(Ok(SomaError::RepositoryInUse))
+    );
+
+    assert!(clean(&env, "test.b", &mut runtime).is_ok());
+    assert!(update(&mut env, &repo_name, false, &mut runtime).is_ok());
+    assert!(env.repo_manager().search_prob("test.b").is_err());
+
+    assert!(clean(&env, "test.a", &mut runtime).is_ok());
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    assert!(!image_from_repo_exists(&images, "test"));
+
+    assert!(remove(&mut env, &repo_name, &mut runtime).is_ok());
+}
@@ -3,7 +3,7 @@ use soma::docker::{
     container_exists, container_from_prob_exists, image_exists, image_from_prob_exists,
     image_from_repo_exists,
 };
-use soma::ops::{add, build, clean, run, stop};
+use soma::ops::{add, build, clean, run, stop, RunOptions};
 
 pub use self::common::*;
 
@@ -11,11 +11,12 @@ mod common;
 
 #[test]
 fn test_run_stop1() {
+    let simple_bof = simple_bof_git();
     let (_, mut data_dir) = temp_data_dir();
     let mut env = test_env(&mut data_dir);
     let mut runtime = default_runtime();
 
-    assert!(add(&mut env, SIMPLE_BOF_GIT, None).is_ok());
+    assert!(add(&mut env, &simple_bof.url(), None).is_ok());
 
     let prob_query = "simple-bof";
     let problem = env
@@ -25,22 +26,36 @@ fn test_run_stop1() {
     let repo_name = problem.repo_name();
     let image_name = problem.docker_image_name(env.username());
 
-    assert!(build(&env, prob_query, &mut runtime).is_ok());
+    assert!(build(&env, prob_query, true, &mut runtime).is_ok());
     let images = runtime.block_on(docker::list_images(&env)).unwrap();
     assert!(image_exists(&images, &image_name));
     assert!(image_from_repo_exists(&images, repo_name));
     assert!(image_from_prob_exists(&images, &problem));
 
-    let container_id = run(&env, prob_query, 31337, &mut runtime).unwrap();
+    let container_id = run(
+        &env,
+        prob_query,
+        31337,
+        &RunOptions::default(),
+        &mut runtime,
+    )
+    .unwrap();
     let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
     assert!(container_exists(&containers, &container_id));
     assert!(container_from_prob_exists(&containers, &problem));
 
     // Problem container should be running exclusively
-    assert!(run(&env, prob_query, 31337, &mut runtime).is_err());
+    assert!(run(
+        &env,
+        prob_query,
+        31337,
+        &RunOptions::default(),
+        &mut runtime
+    )
+    .is_err());
 
     // Cleanup
-    assert!(stop(&env, prob_query, &mut runtime).is_ok());
+    assert!(stop(&env, prob_query, None, &mut runtime).is_ok());
     let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
     assert!(!container_exists(&containers, &container_id));
     assert!(!container_from_prob_exists(&containers, &problem));
@@ -54,11 +69,12 @@ fn test_run_stop1() {
 
 #[test]
 fn test_run_stop2() {
+    let bata_list = bata_list_git();
     let (_, mut data_dir) = temp_data_dir();
     let mut env = test_env(&mut data_dir);
     let mut runtime = default_runtime();
 
-    assert!(add(&mut env, BATA_LIST_GIT, None).is_ok());
+    assert!(add(&mut env, &bata_list.url(), None).is_ok());
 
     let prob_query = "babyecho";
     let problem = env
@@ -68,22 +84,36 @@ fn test_run_stop2() {
     let repo_name = problem.repo_name();
     let image_name = problem.docker_image_name(env.username());
 
-    assert!(build(&env, prob_query, &mut runtime).is_ok());
+    assert!(build(&env, prob_query, true, &mut runtime).is_ok());
     let images = runtime.block_on(docker::list_images(&env)).unwrap();
     assert!(image_exists(&images, &image_name));
     assert!(image_from_repo_exists(&images, repo_name));
     assert!(image_from_prob_exists(&images, &problem));
 
-    let container_id = run(&env, prob_query, 31338, &mut runtime).unwrap();
+    let container_id = run(
+        &env,
+        prob_query,
+        31338,
+        &RunOptions::default(),
+        &mut runtime,
+    )
+    .unwrap();
     let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
     assert!(container_exists(&containers, &container_id));
     assert!(container_from_prob_exists(&containers, &problem));
 
     // Problem container should be running exclusively
-    assert!(run(&env, prob_query, 31338, &mut runtime).is_err());
+    assert!(run(
+        &env,
+        prob_query,
+        31338,
+        &RunOptions::default(),
+        &mut runtime
+    )
+    .is_err());
 
     // Cleanup
-    assert!(stop(&env, prob_query, &mut runtime).is_ok());
+    assert!(stop(&env, prob_query, None, &mut runtime).is_ok());
     let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
     assert!(!container_exists(&containers, &container_id));
     assert!(!container_from_prob_exists(&containers, &problem));
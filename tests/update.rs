@@ -1,12 +1,17 @@
+use std::fs;
 use std::path::Path;
 
 use fs_extra::dir;
 use matches::assert_matches;
 use remove_dir_all::remove_dir_all;
+use tokio::runtime::current_thread::Runtime;
 
-use soma::docker::{self, image_from_repo_exists};
-use soma::ops::{add, build, clean, update};
+use soma::docker::{
+    self, container_from_prob_exists, image_from_prob_exists, image_from_repo_exists, FakeEngine,
+};
+use soma::ops::{add, build, build_outdated, clean, outdated_problems, run, update, RunOptions};
 use soma::prelude::*;
+use soma::Environment;
 
 pub use self::common::*;
 
@@ -22,12 +27,24 @@ fn dir_copy(from: impl AsRef<Path>, to: impl AsRef<Path>) {
     dir::copy(from.as_ref(), to.as_ref(), &copy_options).expect("Failed to copy the directory");
 }
 
+fn outdated_names(
+    env: &Environment<FakeEngine, TestPrinter>,
+    runtime: &mut Runtime,
+) -> Vec<String> {
+    let images = runtime.block_on(docker::list_images(env)).unwrap();
+    outdated_problems(env, &images)
+        .unwrap()
+        .iter()
+        .map(|problem| problem.fully_qualified_name())
+        .collect()
+}
+
 #[test]
 fn test_update() {
     let project_dir = tempdir();
 
     let (_, mut data_dir) = temp_data_dir();
-    let mut env = test_env(&mut data_dir);
+    let mut env = fake_env(&mut data_dir);
     let mut runtime = default_runtime();
 
     dir_copy("test_repo/ab", project_dir.path());
@@ -40,26 +57,155 @@ fn test_update() {
     )
     .is_ok());
 
-    assert!(build(&env, "test.a", &mut runtime).is_ok());
-    assert!(build(&env, "test.b", &mut runtime).is_ok());
+    assert!(build(&env, "test.a", true, &mut runtime).is_ok());
+    assert!(build(&env, "test.b", true, &mut runtime).is_ok());
 
     // image "test.b" should be removed before updating
     dir_copy("test_repo/a", project_dir.path());
 
     assert_matches!(
-        update(&env, &test_repo_name, &mut runtime).map_err(error_downcast),
+        update(&mut env, &test_repo_name, false, &mut runtime).map_err(error_downcast),
         Err(Ok(SomaError::UnsupportedUpdate))
     );
 
     assert!(clean(&env, "test.b", &mut runtime).is_ok());
-    assert!(update(&env, &test_repo_name, &mut runtime).is_ok());
+    assert!(update(&mut env, &test_repo_name, false, &mut runtime).is_ok());
 
     // update should not fail when there is no removed problem
     dir_copy("test_repo/abc", project_dir.path());
-    assert!(update(&env, &test_repo_name, &mut runtime).is_ok());
+    assert!(update(&mut env, &test_repo_name, false, &mut runtime).is_ok());
 
     assert!(clean(&env, "test.a", &mut runtime).is_ok());
 
     let images = runtime.block_on(docker::list_images(&env)).unwrap();
     assert!(!image_from_repo_exists(&images, "test"));
 }
+
+#[test]
+fn test_update_prune_and_rename() {
+    let project_dir = tempdir();
+
+    let (_, mut data_dir) = temp_data_dir();
+    let mut env = fake_env(&mut data_dir);
+    let mut runtime = default_runtime();
+
+    dir_copy("test_repo/abc", project_dir.path());
+
+    let test_repo_name = "test".to_sanitized();
+    assert!(add(
+        &mut env,
+        &project_dir.path().as_os_str().to_string_lossy(),
+        Some(&test_repo_name),
+    )
+    .is_ok());
+
+    assert!(build(&env, "test.a", true, &mut runtime).is_ok());
+    assert!(build(&env, "test.b", true, &mut runtime).is_ok());
+    assert!(build(&env, "test.c", true, &mut runtime).is_ok());
+    assert!(run(&env, "test.c", 31337, &RunOptions::default(), &mut runtime).is_ok());
+    let problem_b = env.repo_manager().search_prob("test.b").unwrap();
+    let problem_c = env.repo_manager().search_prob("test.c").unwrap();
+
+    // Problem "b" is renamed in place, "c" is removed, and "a" is changed
+    fs::write(
+        project_dir.path().join("soma-list.toml"),
+        "problems = [\"a\", \"b\"]\n",
+    )
+    .unwrap();
+    let manifest_b = fs::read_to_string(project_dir.path().join("b/soma.toml")).unwrap();
+    fs::write(
+        project_dir.path().join("b/soma.toml"),
+        manifest_b.replace("name = \"b\"", "name = \"b-renamed\""),
+    )
+    .unwrap();
+    let manifest_a = fs::read_to_string(project_dir.path().join("a/soma.toml")).unwrap();
+    fs::write(
+        project_dir.path().join("a/soma.toml"),
+        manifest_a.replace("cat flag", "cat /flag"),
+    )
+    .unwrap();
+
+    assert_matches!(
+        update(&mut env, &test_repo_name, false, &mut runtime).map_err(error_downcast),
+        Err(Ok(SomaError::UnsupportedUpdate))
+    );
+    assert!(env.repo_manager().search_prob("test.b").is_ok());
+
+    assert!(update(&mut env, &test_repo_name, true, &mut runtime).is_ok());
+    assert!(env.repo_manager().search_prob("test.b").is_err());
+    assert!(env.repo_manager().search_prob("test.c").is_err());
+    assert!(env.repo_manager().search_prob("test.b-renamed").is_ok());
+
+    let output = env.printer().output().to_owned();
+    assert!(output.contains("Problem renamed: 'test.b' -> 'test.b-renamed'"));
+    assert!(output.contains("Problem removed: 'test.c'"));
+    assert!(output.contains("Problem changed: 'test.a'"));
+    assert!(!output.contains("Problem added"));
+
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    let containers = runtime.block_on(docker::list_containers(&env)).unwrap();
+    assert!(!image_from_prob_exists(&images, &problem_b));
+    assert!(!image_from_prob_exists(&images, &problem_c));
+    assert!(!container_from_prob_exists(&containers, &problem_c));
+
+    assert!(clean(&env, "test.a", &mut runtime).is_ok());
+    let images = runtime.block_on(docker::list_images(&env)).unwrap();
+    assert!(!image_from_repo_exists(&images, "test"));
+}
+
+#[test]
+fn test_update_templates_outdate_images() {
+    let project_dir = tempdir();
+
+    let (_, mut data_dir) = temp_data_dir();
+    let mut env = fake_env(&mut data_dir);
+    let mut runtime = default_runtime();
+
+    dir_copy("test_repo/ab", project_dir.path());
+
+    let test_repo_name = "test".to_sanitized();
+    assert!(add(
+        &mut env,
+        &project_dir.path().as_os_str().to_string_lossy(),
+        Some(&test_repo_name),
+    )
+    .is_ok());
+    assert!(build(&env, "test.a", true, &mut runtime).is_ok());
+
+    assert!(outdated_names(&env, &mut runtime).is_empty());
+
+    // The template directory lies outside of the problem directories
+    let partial_dir = project_dir.path().join(".soma/templates/partials");
+    fs::create_dir_all(&partial_dir).unwrap();
+    fs::write(partial_dir.join("timezone.hbs"), "ENV TZ UTC\n").unwrap();
+    assert!(update(&mut env, &test_repo_name, false, &mut runtime).is_ok());
+    assert_eq!(outdated_names(&env, &mut runtime), vec!["test.a"]);
+
+    assert!(build_outdated(&env, true, 1, &mut runtime).is_ok());
+    assert!(outdated_names(&env, &mut runtime).is_empty());
+
+    // So do the templates and partials that soma-list.toml points to
+    fs::create_dir(project_dir.path().join("shared")).unwrap();
+    fs::write(
+        project_dir.path().join("shared/timezone.hbs"),
+        "ENV TZ UTC\n",
+    )
+    .unwrap();
+    fs::write(
+        project_dir.path().join("soma-list.toml"),
+        "problems = [\"a\", \"b\"]\n\n[partials]\ntimezone = \"shared/timezone.hbs\"\n",
+    )
+    .unwrap();
+    assert!(update(&mut env, &test_repo_name, false, &mut runtime).is_ok());
+    assert!(outdated_names(&env, &mut runtime).is_empty());
+
+    fs::write(
+        project_dir.path().join("shared/timezone.hbs"),
+        "ENV TZ Asia/Seoul\n",
+    )
+    .unwrap();
+    assert!(update(&mut env, &test_repo_name, false, &mut runtime).is_ok());
+    assert_eq!(outdated_names(&env, &mut runtime), vec!["test.a"]);
+
+    assert!(clean(&env, "test.a", &mut runtime).is_ok());
+}